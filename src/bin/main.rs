@@ -2,9 +2,37 @@
 #[macro_use]
 extern crate log;
 
-use luxafor::usb_hid::USBDeviceDiscovery;
-use luxafor::{webhook, Device, Pattern, SolidColor, Wave};
+use luxafor::broker::{default_socket_path, Broker, BrokerDevice};
+use luxafor::button::ButtonEvent;
+use luxafor::calendar::{CalendarMonitor, Source};
+use luxafor::config::{default_config_path, Config};
+#[cfg(feature = "dbus")]
+use luxafor::dbus::LightService;
+use luxafor::doctor::{
+    check_usb, check_webhook, write_udev_rule as write_udev_rule_to, Status, SystemPaths, UDEV_RULE,
+};
+use luxafor::emulated::Emulated;
+use luxafor::group::DeviceGroup;
+#[cfg(feature = "mqtt")]
+use luxafor::mqtt::{MqttBridge, MqttConfig, DEFAULT_BASE_TOPIC, DEFAULT_DISCOVERY_PREFIX};
+use luxafor::process::run_with_status;
+use luxafor::receiver::ReceiverServer;
+use luxafor::recorder::{read_recording, replay, replay_targeted};
+use luxafor::registry::{open_targeted_uri, open_uri, DeviceUri};
+use luxafor::schedule::{default_override_path, Engine, Override, SystemClock};
+use luxafor::server::{WebhookServer, DEFAULT_ADDRESS};
+use luxafor::shared::SharedDevice;
+use luxafor::status::{set_palette, Palette, StatusPreset};
+use luxafor::timer::{pomodoro, Phase, Timer};
+use luxafor::usb_hid::{self, USBDevice};
+use luxafor::{webhook, Command, Device, Pattern, Repeat, SolidColor, Speed, TargetedDevice, Wave};
 use std::error::Error;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -14,9 +42,23 @@ pub(crate) struct CommandLine {
     #[structopt(long, short = "v", parse(from_occurrences))]
     verbose: i8,
 
-    /// The device identifier
-    #[structopt(long, short, env = "LUX_DEVICE")]
-    device: String,
+    /// The device identifier, repeated to control a group of devices, or the name of a group in
+    /// the configuration file
+    #[structopt(long, short, env = "LUX_DEVICE", number_of_values = 1)]
+    device: Vec<String>,
+
+    /// The broker socket used to share a USB device, if it exists
+    #[structopt(long, env = "LUX_BROKER_SOCKET", parse(from_os_str))]
+    socket: Option<PathBuf>,
+
+    /// The configuration file, used by commands such as schedule
+    #[structopt(long, env = "LUX_CONFIG", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// The palette used for status presets, `standard` or `colorblind`, rather than the configured
+    /// one
+    #[structopt(long, env = "LUX_PALETTE")]
+    palette: Option<Palette>,
 
     #[structopt(subcommand)]
     cmd: SubCommand,
@@ -36,13 +78,13 @@ pub(crate) enum SubCommand {
         #[structopt(name = "COLOR")]
         color: SolidColor,
 
-        /// The speed of each strobe cycle
+        /// The speed of each strobe cycle, in 50ms units or as a duration such as `500ms`
         #[structopt(long, short, default_value = "10")]
-        speed: u8,
+        speed: Speed,
 
-        /// The number of times to repeat the strobe
+        /// The number of times to repeat the strobe, or `forever`
         #[structopt(long, short, default_value = "255")]
-        repeat: u8,
+        repeat: Repeat,
     },
     /// Set the light to fade from the current to a new color
     Fade {
@@ -50,9 +92,9 @@ pub(crate) enum SubCommand {
         #[structopt(name = "COLOR")]
         color: SolidColor,
 
-        /// The speed of each strobe cycle
+        /// The duration of the fade, in 50ms units or as a duration such as `3s`
         #[structopt(long, short, default_value = "60")]
-        fade_duration: u8,
+        fade_duration: Speed,
     },
     /// Set the light to a to a pre-defined wave pattern
     Wave {
@@ -64,28 +106,218 @@ pub(crate) enum SubCommand {
         #[structopt(default_value = "short")]
         pattern: Wave,
 
-        /// The speed of each wave cycle
+        /// The speed of each wave cycle, in 50ms units or as a duration such as `1500ms`
         #[structopt(long, short, default_value = "30")]
-        speed: u8,
+        speed: Speed,
 
-        /// The number of times to repeat the pattern
+        /// The number of times to repeat the pattern, or `forever`
         #[structopt(long, short, default_value = "255")]
-        repeat: u8,
+        repeat: Repeat,
     },
     /// Set the light to a to a pre-defined pattern
     Pattern {
         /// The pattern to set
         pattern: Pattern,
 
-        /// The number of times to repeat the pattern
+        /// The number of times to repeat the pattern, or `forever`
         #[structopt(long, short, default_value = "255")]
-        repeat: u8,
+        repeat: Repeat,
+    },
+    /// Show a status preset, `available`, `warning`, or `busy`, in the current palette
+    Status {
+        /// The status to show
+        #[structopt(name = "STATUS")]
+        status: StatusPreset,
     },
     /// Turn the light off
     Off,
+    /// Run a broker that owns the USB device and shares it with other processes
+    Broker,
+    /// Run a shell command each time the button on a Luxafor Mute is pressed
+    OnButton {
+        /// The shell command to run when the button is pressed
+        #[structopt(long)]
+        exec: String,
+
+        /// The shell command to run when the button is held, rather than the exec command
+        #[structopt(long)]
+        long_press: Option<String>,
+    },
+    /// Serve the Luxafor webhook API locally, applying all actions to the device
+    Serve {
+        /// The address, host and port, to listen on
+        #[structopt(long, short, default_value = DEFAULT_ADDRESS)]
+        address: String,
+    },
+    /// Bridge the device to an MQTT broker, with Home Assistant discovery
+    #[cfg(feature = "mqtt")]
+    Mqtt {
+        /// The host name of the MQTT broker
+        #[structopt(long, short = "H", default_value = "localhost", env = "LUX_MQTT_HOST")]
+        host: String,
+
+        /// The port of the MQTT broker
+        #[structopt(long, short, default_value = "1883", env = "LUX_MQTT_PORT")]
+        port: u16,
+
+        /// The user name to connect with
+        #[structopt(long, short, env = "LUX_MQTT_USER")]
+        user: Option<String>,
+
+        /// The password to connect with
+        #[structopt(long, env = "LUX_MQTT_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+
+        /// The base topic for command, state and availability topics
+        #[structopt(long, default_value = DEFAULT_BASE_TOPIC)]
+        base_topic: String,
+
+        /// The Home Assistant discovery prefix
+        #[structopt(long, default_value = DEFAULT_DISCOVERY_PREFIX)]
+        discovery_prefix: String,
+
+        /// Do not publish Home Assistant discovery config
+        #[structopt(long)]
+        no_discovery: bool,
+    },
+    /// Export the device as a D-Bus service on the session bus
+    #[cfg(feature = "dbus")]
+    Dbus,
+    /// Run a pomodoro session, showing the progress of each phase on the LEDs
+    Pomodoro {
+        /// The duration of each work phase
+        #[structopt(long, default_value = "25m", parse(try_from_str = humantime::parse_duration))]
+        work: Duration,
+
+        /// The duration of each break between work phases
+        #[structopt(long = "break", default_value = "5m", parse(try_from_str = humantime::parse_duration))]
+        rest: Duration,
+
+        /// The number of work phases
+        #[structopt(long, default_value = "4")]
+        cycles: u16,
+
+        /// The color shown during work phases
+        #[structopt(long, default_value = "red")]
+        work_color: SolidColor,
+
+        /// The color shown during breaks
+        #[structopt(long, default_value = "green")]
+        break_color: SolidColor,
+
+        /// How long before the end of each phase the light starts to strobe
+        #[structopt(long, default_value = "30s", parse(try_from_str = humantime::parse_duration))]
+        warning: Duration,
+
+        /// The command to apply when finished or interrupted, rather than turning the light off
+        #[structopt(long)]
+        restore: Option<Command>,
+    },
+    /// Run a countdown timer, showing the progress on the LEDs
+    Timer {
+        /// The duration of the timer
+        #[structopt(name = "DURATION", parse(try_from_str = humantime::parse_duration))]
+        duration: Duration,
+
+        /// The color shown while the timer runs
+        #[structopt(long, default_value = "red")]
+        color: SolidColor,
+
+        /// How long before the end the light starts to strobe
+        #[structopt(long, default_value = "30s", parse(try_from_str = humantime::parse_duration))]
+        warning: Duration,
+
+        /// The command to apply when finished or interrupted, rather than turning the light off
+        #[structopt(long)]
+        restore: Option<Command>,
+    },
+    /// Receive CI and alerting webhooks, applying the configured rules to the device
+    Receive {
+        /// The address, host and port, to listen on, rather than the configured one
+        #[structopt(long, short)]
+        address: Option<String>,
+    },
+    /// Run a command, showing whether it is running, succeeded, or failed on the light
+    Run {
+        /// The command applied while running, rather than the configured one
+        #[structopt(long)]
+        running: Option<Command>,
+
+        /// The command applied on success, rather than the configured one
+        #[structopt(long)]
+        success: Option<Command>,
+
+        /// The command applied on failure, rather than the configured one
+        #[structopt(long)]
+        failure: Option<Command>,
+
+        /// The command applied after the delay, rather than the configured one
+        #[structopt(long)]
+        restore: Option<Command>,
+
+        /// How long to show success or failure, rather than the configured delay
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        delay: Option<Duration>,
+
+        /// The program to run, and its arguments
+        #[structopt(name = "PROGRAM", required = true, last = true, parse(from_os_str))]
+        command_line: Vec<OsString>,
+    },
+    /// Show busy, or available, on the light from an ICS calendar file or URL
+    Calendar {
+        /// The path, or URL, of the calendar, rather than the configured one
+        #[structopt(name = "SOURCE")]
+        source: Option<Source>,
+
+        /// How long before an event starts to show the warning command
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        warning: Option<Duration>,
+
+        /// How often to reload the calendar
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        refresh: Option<Duration>,
+
+        /// Ignore events declined by this email address
+        #[structopt(long)]
+        attendee: Option<String>,
+    },
+    /// Replay the commands in a recording made by `recorder::Recorder`, with the original timing
+    Replay {
+        /// The recording, a JSON Lines file
+        #[structopt(name = "FILE", parse(from_os_str))]
+        file: PathBuf,
+
+        /// How much faster than the original to replay, such as `2x` or `0.5x`
+        #[structopt(long, short, default_value = "1x", parse(try_from_str = replay_rate))]
+        speed: f64,
+    },
+    /// Check for the common reasons a light cannot be used, such as USB permissions
+    Doctor {
+        /// Write the suggested udev rule to this file, such as /etc/udev/rules.d/60-luxafor.rules
+        #[structopt(long, parse(from_os_str))]
+        write_udev_rule: Option<PathBuf>,
+    },
+    /// Run the working-hours schedule from the configuration file
+    Schedule,
+    /// Override a running schedule with a command for a period of time
+    Override {
+        /// The command to apply, such as "solid red" or "strobe blue 10 255"
+        #[structopt(name = "COMMAND", required_unless = "clear")]
+        command: Option<Command>,
+
+        /// How long the override lasts before the schedule resumes
+        #[structopt(long = "for", default_value = "1h", parse(try_from_str = humantime::parse_duration))]
+        duration: Duration,
+
+        /// Remove any current override, resuming the schedule
+        #[structopt(long, conflicts_with = "COMMAND")]
+        clear: bool,
+    },
 }
 
-const DEVICE_CONNECTION_USB: &str = "usb";
+const SCHEME_USB: &str = "usb";
+const SCHEME_WEBHOOK: &str = "webhook";
+const SCHEME_TERM: &str = "term";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = CommandLine::from_args();
@@ -101,38 +333,541 @@ fn main() -> Result<(), Box<dyn Error>> {
         })
         .init();
 
-    if args.device == DEVICE_CONNECTION_USB {
-        let discovery = USBDeviceDiscovery::new()?;
-        let device = discovery.device()?;
-        debug!("USB device: '{}'", device.id());
-        set_lights(args, device)
+    if let SubCommand::Doctor { write_udev_rule } = &args.cmd {
+        return doctor(&args, write_udev_rule.as_deref());
+    } else if args.device.is_empty() {
+        structopt::clap::Error::with_description(
+            "The argument '--device <device>...' is required",
+            structopt::clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit()
+    }
+    set_palette(status_palette(&args)?);
+
+    if let SubCommand::Override {
+        command,
+        duration,
+        clear,
+    } = args.cmd
+    {
+        let path = default_override_path();
+        match command {
+            Some(command) if !clear => Override::new(command, duration)?.save(&path)?,
+            _ => Override::remove(&path)?,
+        }
+        Ok(())
+    } else if let Some(devices) = device_group(&args)? {
+        let socket = args.socket.clone().unwrap_or_else(default_socket_path);
+        let mut group = DeviceGroup::new(&args.device.join(","));
+        for uri in devices {
+            match broker_device(&uri, &socket) {
+                Some(device) => group.add(device),
+                None => group.add_boxed(open_device(&uri)?),
+            }
+        }
+        let group = SharedDevice::new(group);
+        set_lights(args, group.clone())?;
+        group.wait();
+        Ok(())
+    } else {
+        let uri = device_uri(&args.device[0])?;
+        let socket = args.socket.clone().unwrap_or_else(default_socket_path);
+        match (&args.cmd, uri.scheme()) {
+            (SubCommand::Broker, SCHEME_USB) => {
+                let device =
+                    open_targeted_uri(&uri)?.ok_or(luxafor::error::Error::UnsupportedCommand)?;
+                let broker = Broker::bind(&socket, device)?;
+                println!("Broker listening on {:?}", broker.path());
+                broker.serve()?;
+                Ok(())
+            }
+            (SubCommand::OnButton { exec, long_press }, SCHEME_USB) => {
+                on_button(&usb_device(&uri)?, exec, long_press.as_deref())
+            }
+            _ => {
+                if let Some(device) = broker_device(&uri, &socket) {
+                    set_targeted_lights(args, device)
+                } else if let Some(device) = open_targeted_uri(&uri)? {
+                    let device = SharedDevice::new(device);
+                    set_targeted_lights(args, device.clone())?;
+                    device.wait();
+                    Ok(())
+                } else {
+                    let device = SharedDevice::new(open_device(&uri)?);
+                    set_lights(args, device.clone())?;
+                    device.wait();
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+// The `--palette` value, or the palette in the configuration file, or the standard palette.
+fn status_palette(args: &CommandLine) -> Result<Palette, Box<dyn Error>> {
+    if let Some(palette) = args.palette {
+        return Ok(palette);
+    }
+    let config_path = args.config.clone().unwrap_or_else(default_config_path);
+    Ok(Config::load_or_default(&config_path)?
+        .palette()?
+        .unwrap_or_default())
+}
+
+// Each `--device` value is a device URI, such as `usb:serial=1234`; `usb` on its own is the first
+// USB light, `term` the terminal preview, and any other value without a scheme is a webhook device
+// ID.
+fn device_uri(value: &str) -> luxafor::error::Result<DeviceUri> {
+    if value.contains(':') {
+        value.parse()
+    } else if value == SCHEME_USB || value == SCHEME_TERM {
+        format!("{}:", value).parse()
+    } else {
+        format!("{}:{}", SCHEME_WEBHOOK, value).parse()
+    }
+}
+
+// The devices to control as a group: several `--device` values, or the name of a group in the
+// configuration file.
+fn device_group(args: &CommandLine) -> Result<Option<Vec<DeviceUri>>, Box<dyn Error>> {
+    let values = match args.device.as_slice() {
+        [value] => {
+            let config_path = args.config.clone().unwrap_or_else(default_config_path);
+            match Config::load_or_default(&config_path)?.group(value) {
+                Some(devices) => devices.to_vec(),
+                None => return Ok(None),
+            }
+        }
+        values => values.to_vec(),
+    };
+    Ok(Some(
+        values
+            .iter()
+            .map(|value| device_uri(value))
+            .collect::<luxafor::error::Result<_>>()?,
+    ))
+}
+
+// Exits with a failure status if any check failed, so that the doctor can be used in scripts.
+fn doctor(args: &CommandLine, write_udev_rule: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let mut checks = check_usb(&SystemPaths::default());
+    let endpoint = args
+        .device
+        .iter()
+        .filter_map(|value| device_uri(value).ok())
+        .find(|uri| uri.scheme() == SCHEME_WEBHOOK)
+        .and_then(|uri| uri.option("endpoint").map(str::to_string))
+        .unwrap_or_else(|| webhook::DEFAULT_ENDPOINT.to_string());
+    checks.push(check_webhook(&endpoint));
+    checks.iter().for_each(|check| println!("{}", check));
+    match write_udev_rule {
+        Some(path) => {
+            write_udev_rule_to(path)?;
+            println!(
+                "\nWrote the udev rule to {:?}, reload the rules with:",
+                path
+            );
+            println!("  udevadm control --reload-rules && udevadm trigger");
+        }
+        None if checks.iter().any(|check| check.status != Status::Ok) => {
+            println!("\nThe suggested udev rule, for /etc/udev/rules.d/60-luxafor.rules, is:");
+            println!("  {}", UDEV_RULE);
+        }
+        None => {}
+    }
+    if checks.iter().any(|check| check.status == Status::Failed) {
+        std::process::exit(1)
+    }
+    Ok(())
+}
+
+// Opens `uri` from the registry; a webhook light is emulated, as the webhook API supports only a
+// few commands.
+fn open_device(uri: &DeviceUri) -> luxafor::error::Result<Box<dyn Device + Send>> {
+    let device = open_uri(uri)?;
+    if uri.scheme() == SCHEME_WEBHOOK {
+        Ok(Box::new(Emulated::new(SharedDevice::new(device))))
+    } else {
+        Ok(device)
+    }
+}
+
+// Button events are only read from a USB light.
+fn usb_device(uri: &DeviceUri) -> Result<USBDevice, Box<dyn Error>> {
+    let device = usb_hid::open(uri)?;
+    debug!("USB device: '{}'", device.id());
+    Ok(device)
+}
+
+// Without a long press command the exec command runs as the button goes down, with one it runs
+// on release if the button was not held long enough to run the long press command instead.
+fn on_button(
+    device: &USBDevice,
+    exec: &str,
+    long_press: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    println!("Waiting for button events from '{}'", device.id());
+    let mut held = false;
+    for event in device.events() {
+        let event = event?;
+        debug!("Button event: {}", event);
+        let command = match (event, long_press) {
+            (ButtonEvent::Pressed, None) => Some(exec),
+            (ButtonEvent::Pressed, Some(_)) => {
+                held = false;
+                None
+            }
+            (ButtonEvent::LongPressed, Some(long_press)) => {
+                held = true;
+                Some(long_press)
+            }
+            (ButtonEvent::Released, Some(_)) if !held => Some(exec),
+            _ => None,
+        };
+        if let Some(command) = command {
+            run_shell(command, event)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_shell(command: &str, event: ButtonEvent) -> Result<(), Box<dyn Error>> {
+    info!("Running '{}' for button {}", command, event);
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let status = std::process::Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .env("LUX_BUTTON_EVENT", event.to_string())
+        .status()?;
+    if !status.success() {
+        warn!("Command '{}' failed: {}", command, status);
+    }
+    Ok(())
+}
+
+// The replay rate is a positive multiple of the original speed, with or without a trailing `x`.
+fn replay_rate(value: &str) -> Result<f64, String> {
+    match value.trim().trim_end_matches(['x', 'X']).parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!("'{}' is not a replay speed, such as 2x", value)),
+    }
+}
+
+fn broker_device(uri: &DeviceUri, socket: &Path) -> Option<BrokerDevice> {
+    // The broker owns the first USB light, a specific light is always opened directly.
+    if uri.scheme() == SCHEME_USB && uri.target().is_empty() && socket.exists() {
+        match BrokerDevice::connect(socket) {
+            Ok(device) => {
+                debug!("Broker device: '{}'", device.id());
+                Some(device)
+            }
+            Err(e) => {
+                warn!("Could not connect to broker at {:?}: {}", socket, e);
+                None
+            }
+        }
     } else {
-        let device_id = args.device.clone();
-        set_lights(args, webhook::new_device_for(&device_id)?)
+        None
     }
 }
 
+fn set_targeted_lights(
+    args: CommandLine,
+    mut device: impl TargetedDevice + Send + 'static,
+) -> Result<(), Box<dyn Error>> {
+    match args.cmd {
+        SubCommand::Pomodoro { .. } | SubCommand::Timer { .. } => {
+            run_timer(args.cmd, &mut device, |timer, device| {
+                let completed = timer.run(device)?;
+                device.set_specific_led(luxafor::SpecificLED::All)?;
+                Ok(completed)
+            })
+        }
+        SubCommand::Replay { file, speed } => {
+            let entries = read_recording(BufReader::new(File::open(&file)?))?;
+            let count = replay_targeted(&entries, &mut device, speed)?;
+            println!("Replayed {} commands to device '{}'", count, device.id());
+            Ok(())
+        }
+        #[cfg(feature = "dbus")]
+        SubCommand::Dbus => {
+            let device_id = device.id();
+            let service = LightService::session(device)?;
+            println!("Exported device '{}' on the session bus", device_id);
+            service.wait()
+        }
+        _ => set_lights(args, device),
+    }
+}
+
+// Runs the timer of the `pomodoro` and `timer` subcommands with `run`, until it ends or Ctrl-C
+// cancels it, and then turns the light off or applies the restore command.
+fn run_timer<D: Device>(
+    cmd: SubCommand,
+    device: &mut D,
+    run: impl FnOnce(&Timer, &mut D) -> luxafor::error::Result<bool>,
+) -> Result<(), Box<dyn Error>> {
+    let (phases, warning, restore) = match cmd {
+        SubCommand::Pomodoro {
+            work,
+            rest,
+            cycles,
+            work_color,
+            break_color,
+            warning,
+            restore,
+        } => (
+            pomodoro(work, work_color, rest, break_color, cycles),
+            warning,
+            restore,
+        ),
+        SubCommand::Timer {
+            duration,
+            color,
+            warning,
+            restore,
+        } => (vec![Phase::new("timer", duration, color)], warning, restore),
+        _ => return Err(luxafor::error::Error::UnsupportedCommand.into()),
+    };
+    let timer = Timer::new(phases, warning);
+    let cancelled = timer.cancel_handle();
+    ctrlc::set_handler(move || cancelled.store(true, Ordering::SeqCst))?;
+    let completed = run(&timer, device)?;
+    debug!("Timer completed: {}", completed);
+    match restore {
+        None => device.turn_off()?,
+        Some(command) => command.apply(device)?,
+    }
+    Ok(())
+}
+
 fn set_lights(args: CommandLine, device: impl Device) -> Result<(), Box<dyn Error>> {
+    let config_path = args.config.unwrap_or_else(default_config_path);
     match args.cmd {
-        SubCommand::Solid { color } => device.set_solid_color(color),
+        SubCommand::Solid { color } => apply(&device, Command::Solid { color }),
         SubCommand::Fade {
             color,
             fade_duration,
-        } => device.set_fade_to_color(color, fade_duration),
+        } => apply(
+            &device,
+            Command::Fade {
+                color,
+                fade_duration,
+            },
+        ),
         SubCommand::Strobe {
             color,
             speed,
             repeat,
-        } => device.set_color_strobe(color, speed, repeat),
+        } => apply(
+            &device,
+            Command::Strobe {
+                color,
+                strobe_speed: speed,
+                repeat_count: repeat,
+            },
+        ),
         SubCommand::Wave {
             color,
             pattern,
             speed,
             repeat,
-        } => device.set_color_wave(color, pattern, speed, repeat),
-        SubCommand::Pattern { pattern, repeat } => device.set_pattern(pattern, repeat),
-        SubCommand::Off => device.turn_off(),
+        } => apply(
+            &device,
+            Command::Wave {
+                color,
+                wave_pattern: pattern,
+                wave_speed: speed,
+                repeat_count: repeat,
+            },
+        ),
+        SubCommand::Pattern { pattern, repeat } => apply(
+            &device,
+            Command::Pattern {
+                pattern,
+                repeat_count: repeat,
+            },
+        ),
+        SubCommand::Status { status } => apply(&device, status.command_for(&device.capabilities())),
+        SubCommand::Off => apply(&device, Command::TurnOff),
+        SubCommand::Broker | SubCommand::OnButton { .. } | SubCommand::Doctor { .. } => {
+            Err(luxafor::error::Error::UnsupportedCommand)
+        }
+        #[cfg(feature = "dbus")]
+        SubCommand::Dbus => Err(luxafor::error::Error::UnsupportedCommand),
+        SubCommand::Pomodoro { .. } | SubCommand::Timer { .. } => {
+            return run_timer(args.cmd, &mut &device, |timer, device| {
+                timer.run_untargeted(device)
+            });
+        }
+        SubCommand::Override { .. } => Err(luxafor::error::Error::UnsupportedCommand),
+        SubCommand::Replay { file, speed } => {
+            let entries = read_recording(BufReader::new(File::open(&file)?))?;
+            let count = replay(&entries, &device, speed)?;
+            println!("Replayed {} commands to device '{}'", count, device.id());
+            Ok(())
+        }
+        SubCommand::Receive { address } => {
+            let config = Config::load_or_default(&config_path)?
+                .receive
+                .unwrap_or_default();
+            let address = address.as_deref().unwrap_or_else(|| config.address());
+            let server = ReceiverServer::bind(address, device, config.rules()?)?;
+            println!("Receiving webhooks at {}", server.url());
+            server.serve()
+        }
+        SubCommand::Run {
+            running,
+            success,
+            failure,
+            restore,
+            delay,
+            command_line,
+        } => {
+            let config = Config::load_or_default(&config_path)?
+                .run
+                .unwrap_or_default();
+            let mut status = config.status_commands()?;
+            fit_presets(
+                &device,
+                [
+                    (&mut status.running, &config.running, StatusPreset::Warning),
+                    (
+                        &mut status.success,
+                        &config.success,
+                        StatusPreset::Available,
+                    ),
+                    (&mut status.failure, &config.failure, StatusPreset::Busy),
+                ],
+            );
+            status.running = running.unwrap_or(status.running);
+            status.success = success.unwrap_or(status.success);
+            status.failure = failure.unwrap_or(status.failure);
+            status.restore = restore.unwrap_or(status.restore);
+            status.restore_delay = delay.unwrap_or(status.restore_delay);
+            check(
+                &device,
+                &[
+                    &status.running,
+                    &status.success,
+                    &status.failure,
+                    &status.restore,
+                ],
+            )?;
+            let code = run_with_status(&device, &command_line, &status)?;
+            std::process::exit(code)
+        }
+        SubCommand::Calendar {
+            source,
+            warning,
+            refresh,
+            attendee,
+        } => {
+            let config = Config::load_or_default(&config_path)?
+                .calendar
+                .unwrap_or_default();
+            let source = match source {
+                Some(source) => source,
+                None => config
+                    .source()?
+                    .ok_or(luxafor::error::Error::InvalidCalendar)?,
+            };
+            let mut options = config.options()?;
+            fit_presets(
+                &device,
+                [
+                    (&mut options.busy, &config.busy, StatusPreset::Busy),
+                    (&mut options.warning, &config.warning, StatusPreset::Warning),
+                    (
+                        &mut options.available,
+                        &config.available,
+                        StatusPreset::Available,
+                    ),
+                ],
+            );
+            options.warning_before = warning.unwrap_or(options.warning_before);
+            options.refresh = refresh.unwrap_or(options.refresh);
+            options.attendee = attendee.or(options.attendee);
+            check(
+                &device,
+                &[&options.busy, &options.warning, &options.available],
+            )?;
+            let mut monitor = CalendarMonitor::new(source, options, SystemClock);
+            monitor.run(&device)
+        }
+        SubCommand::Schedule => {
+            let config = Config::load(&config_path)?;
+            let schedule = config
+                .schedule
+                .ok_or(luxafor::error::Error::InvalidSchedule)?
+                .schedule()?;
+            let mut engine = Engine::new(schedule, SystemClock);
+            engine.run(&device, Some(&default_override_path()))
+        }
+        SubCommand::Serve { address } => {
+            let server = WebhookServer::bind(&address, device)?;
+            println!("Serving webhook API at {}", server.endpoint());
+            server.serve()
+        }
+        #[cfg(feature = "mqtt")]
+        SubCommand::Mqtt {
+            host,
+            port,
+            user,
+            password,
+            base_topic,
+            discovery_prefix,
+            no_discovery,
+        } => {
+            let mut config = MqttConfig::new(&host, port);
+            config.base_topic = base_topic;
+            config.discovery_prefix = if no_discovery {
+                None
+            } else {
+                Some(discovery_prefix)
+            };
+            if let Some(user) = user {
+                config.credentials = Some((user, password.unwrap_or_default()));
+            }
+            MqttBridge::new(device, config).run()
+        }
     }?;
 
     Ok(())
 }
+
+fn apply(device: &impl Device, command: Command) -> luxafor::error::Result<()> {
+    check(device, &[&command])?;
+    command.apply(device)
+}
+
+// Replace each command that was not configured, and so is its status preset, with the preset as
+// the device can show it. This is done before any command line values are applied, those are also
+// left as given.
+fn fit_presets(device: &impl Device, commands: [(&mut Command, &Option<String>, StatusPreset); 3]) {
+    let capabilities = device.capabilities();
+    for (command, configured, preset) in commands {
+        if configured.is_none() {
+            *command = preset.command_for(&capabilities);
+        }
+    }
+}
+
+fn check(device: &impl Device, commands: &[&Command]) -> luxafor::error::Result<()> {
+    let capabilities = device.capabilities();
+    for command in commands {
+        if let Err(e) = capabilities.check(command) {
+            error!(
+                "The device '{}' does not support '{}'",
+                device.id(),
+                command
+            );
+            return Err(e);
+        }
+    }
+    Ok(())
+}
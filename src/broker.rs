@@ -0,0 +1,518 @@
+/*!
+A device broker that allows multiple processes to share a single light over a Unix domain socket.
+
+Only one process can sensibly hold a USB HID handle, and when several tools open the same light
+their writes interleave unpredictably. The [Broker] owns the device and serializes all requests
+from its clients, while [BrokerDevice] is a client that implements both [Device] and
+[TargetedDevice] and so can be used wherever a device is expected.
+
+# Protocol
+
+The protocol is line based; each request is a single line of text and the broker responds with a
+single line. A request is either `id`, which returns the broker's device identifier,
+`capabilities`, which returns the capabilities of the broker's device, or a
+[Command](../enum.Command.html) in its text form optionally prefixed by the LED to target.
+
+| Request                     | Response                                         |
+|-----------------------------|--------------------------------------------------|
+| `id`                        | `ok <device id>`                                 |
+| `capabilities`              | `ok commands=off,solid;leds=false;patterns=;...` |
+| `solid red`                 | `ok`                                             |
+| `led front strobe red 10 5` | `ok`                                             |
+| `pattern rainbow 5`         | `error <kind> <text>`                            |
+
+The LED is applied only for the request in which it appears, requests without an LED target all
+LEDs. Because the broker holds the device for the duration of each request, the LED selection and
+the command it applies to are never interleaved with requests from other clients.
+
+# Example
+
+```rust,ignore
+use luxafor::broker::{default_socket_path, BrokerDevice};
+use luxafor::{Device, SolidColor, SpecificLED, TargetedDevice};
+use luxafor::error::Result;
+
+fn set_do_not_disturb() -> Result<()> {
+    let mut device = BrokerDevice::connect(default_socket_path())?;
+    device.set_specific_led(SpecificLED::AllFront)?;
+    device.set_solid_color(SolidColor::Red)
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{
+    Capabilities, Command, CommandKind, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed,
+    TargetedDevice, Wave,
+};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The broker daemon, this owns the device and serves requests from clients.
+///
+#[allow(missing_debug_implementations)]
+pub struct Broker<D: TargetedDevice> {
+    listener: UnixListener,
+    path: PathBuf,
+    device: Arc<Mutex<D>>,
+}
+
+///
+/// A client of the broker which implements the device traits.
+///
+#[derive(Debug)]
+pub struct BrokerDevice {
+    id: String,
+    capabilities: Capabilities,
+    target_led: Option<SpecificLED>,
+    connection: Mutex<Connection>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+const SOCKET_FILE_NAME: &str = "luxafor.sock";
+
+const REQUEST_ID: &str = "id";
+const REQUEST_CAPABILITIES: &str = "capabilities";
+const REQUEST_LED: &str = "led";
+
+const RESPONSE_OK: &str = "ok";
+const RESPONSE_ERROR: &str = "error";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the socket path used when none is specified; this is `luxafor.sock` in the directory
+/// named by `XDG_RUNTIME_DIR` if set, otherwise in the system temporary directory.
+///
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join(SOCKET_FILE_NAME),
+        _ => std::env::temp_dir().join(SOCKET_FILE_NAME),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<D: TargetedDevice + Send + 'static> Broker<D> {
+    ///
+    /// Bind the broker to the socket at `path`. A socket file left behind by a broker that is no
+    /// longer running is replaced, however if another broker is listening this fails.
+    ///
+    pub fn bind<P: AsRef<Path>>(path: P, device: D) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                error!("A broker is already listening on {:?}", path);
+                return Err(Error::IoError {
+                    source: std::io::Error::from(std::io::ErrorKind::AddrInUse),
+                });
+            }
+            warn!("Removing stale broker socket {:?}", path);
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        info!(
+            "Broker for device '{}' listening on {:?}",
+            device.id(),
+            path
+        );
+        Ok(Self {
+            listener,
+            path,
+            device: Arc::new(Mutex::new(device)),
+        })
+    }
+
+    ///
+    /// Return the path of the socket the broker is listening on.
+    ///
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    ///
+    /// Serve clients until the process is terminated, each client connection is handled on its
+    /// own thread.
+    ///
+    pub fn serve(&self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let device = self.device.clone();
+            let _ = std::thread::spawn(move || {
+                if let Err(e) = serve_client(stream, device) {
+                    warn!("Broker client connection failed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<D: TargetedDevice> Drop for Broker<D> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl BrokerDevice {
+    ///
+    /// Connect to the broker listening on the socket at `path`, the identifier and capabilities
+    /// of the broker's device are read once, when connecting.
+    ///
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let writer = UnixStream::connect(path.as_ref())?;
+        let reader = BufReader::new(writer.try_clone()?);
+        let mut connection = Connection { reader, writer };
+        let id = connection.request(REQUEST_ID)?;
+        let capabilities = capabilities_from_str(&connection.request(REQUEST_CAPABILITIES)?)?;
+        debug!("Connected to broker for device '{}'", id);
+        Ok(Self {
+            id,
+            capabilities,
+            target_led: None,
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn send(&self, command: Command) -> Result<()> {
+        let request = match &self.target_led {
+            None => command.to_string(),
+            Some(led) => format!("{} {} {}", REQUEST_LED, led, command),
+        };
+        info!("Sending '{}' to broker for device '{}'", request, self.id);
+        let mut connection = self.connection.lock().map_err(|_| Error::InvalidRequest)?;
+        let _ = connection
+            .request(&request)
+            .map_err(|e| e.with_context(&self.id, Some(&command.to_string())))?;
+        Ok(())
+    }
+}
+
+impl Device for BrokerDevice {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn turn_off(&self) -> Result<()> {
+        self.send(Command::TurnOff)
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
+        self.send(Command::Solid { color })
+    }
+
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
+        self.send(Command::Fade {
+            color,
+            fade_duration,
+        })
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.send(Command::Strobe {
+            color,
+            strobe_speed,
+            repeat_count,
+        })
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.send(Command::Wave {
+            color,
+            wave_pattern,
+            wave_speed,
+            repeat_count,
+        })
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
+        self.send(Command::Pattern {
+            pattern,
+            repeat_count,
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone()
+    }
+}
+
+impl TargetedDevice for BrokerDevice {
+    fn set_specific_led(&mut self, led: SpecificLED) -> Result<()> {
+        self.target_led = Some(led);
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Connection {
+    fn request(&mut self, request: &str) -> Result<String> {
+        debug!("Broker request: {:?}", request);
+        writeln!(self.writer, "{}", request)?;
+        let mut response = String::new();
+        if self.reader.read_line(&mut response)? == 0 {
+            return Err(Error::IoError {
+                source: std::io::Error::from(std::io::ErrorKind::UnexpectedEof),
+            });
+        }
+        debug!("Broker response: {:?}", response);
+        let response = response.trim_end();
+        match response.split_once(' ') {
+            None if response == RESPONSE_OK => Ok(String::new()),
+            Some((RESPONSE_OK, value)) => Ok(value.to_string()),
+            Some((RESPONSE_ERROR, error)) => {
+                let kind = error.split(' ').next().unwrap_or_default();
+                Err(error_from_kind(kind))
+            }
+            _ => Err(Error::InvalidRequest),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn serve_client<D: TargetedDevice>(stream: UnixStream, device: Arc<Mutex<D>>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let response = match handle_request(line.trim(), &device) {
+            Ok(value) if value.is_empty() => RESPONSE_OK.to_string(),
+            Ok(value) => format!("{} {}", RESPONSE_OK, value),
+            Err(e) => format!("{} {} {}", RESPONSE_ERROR, error_kind(e.root()), e),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+fn handle_request<D: TargetedDevice>(request: &str, device: &Mutex<D>) -> Result<String> {
+    debug!("Broker received request: {:?}", request);
+    let (led, command) = match request.split_once(' ') {
+        Some((REQUEST_LED, rest)) => match rest.split_once(' ') {
+            Some((led, command)) => (led.parse()?, command),
+            None => return Err(Error::InvalidCommand),
+        },
+        _ => (SpecificLED::All, request),
+    };
+    let mut device = device.lock().map_err(|_| Error::InvalidRequest)?;
+    if command == REQUEST_ID {
+        Ok(device.id())
+    } else if command == REQUEST_CAPABILITIES {
+        Ok(capabilities_to_string(&device.capabilities()))
+    } else {
+        let command: Command = command.parse()?;
+        device.set_specific_led(led)?;
+        command.apply(&*device)?;
+        Ok(String::new())
+    }
+}
+
+fn capabilities_to_string(capabilities: &Capabilities) -> String {
+    let kinds = |kinds: &HashSet<CommandKind>| {
+        CommandKind::all()
+            .into_iter()
+            .filter(|kind| kinds.contains(kind))
+            .map(|kind| kind.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+    let list = |values: Vec<String>| values.join(",");
+    format!(
+        "commands={};leds={};patterns={};waves={};repeat={};speed={}",
+        kinds(&capabilities.commands),
+        capabilities.led_targeting,
+        list(
+            capabilities
+                .patterns
+                .iter()
+                .map(Pattern::to_string)
+                .collect()
+        ),
+        list(capabilities.waves.iter().map(Wave::to_string).collect()),
+        kinds(&capabilities.repeat),
+        kinds(&capabilities.speed),
+    )
+}
+
+fn capabilities_from_str(s: &str) -> Result<Capabilities> {
+    fn values<T>(value: &str, parse: impl Fn(&str) -> Result<T>) -> Result<Vec<T>> {
+        value
+            .split(',')
+            .filter(|value| !value.is_empty())
+            .map(parse)
+            .collect()
+    }
+    let kind = |value: &str| {
+        CommandKind::all()
+            .into_iter()
+            .find(|kind| kind.to_string() == value)
+            .ok_or(Error::InvalidRequest)
+    };
+    let mut capabilities = Capabilities {
+        commands: HashSet::new(),
+        led_targeting: false,
+        patterns: Vec::new(),
+        waves: Vec::new(),
+        repeat: HashSet::new(),
+        speed: HashSet::new(),
+    };
+    for field in s.split(';') {
+        match field.split_once('=') {
+            Some(("commands", value)) => {
+                capabilities.commands = values(value, kind)?.into_iter().collect()
+            }
+            Some(("leds", value)) => {
+                capabilities.led_targeting = value.parse().map_err(|_| Error::InvalidRequest)?
+            }
+            Some(("patterns", value)) => capabilities.patterns = values(value, str::parse)?,
+            Some(("waves", value)) => capabilities.waves = values(value, str::parse)?,
+            Some(("repeat", value)) => {
+                capabilities.repeat = values(value, kind)?.into_iter().collect()
+            }
+            Some(("speed", value)) => {
+                capabilities.speed = values(value, kind)?.into_iter().collect()
+            }
+            _ => debug!("Ignoring unknown broker capability {:?}", field),
+        }
+    }
+    Ok(capabilities)
+}
+
+fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::InvalidColor => "InvalidColor",
+        Error::InvalidPattern => "InvalidPattern",
+        Error::InvalidCommand => "InvalidCommand",
+        Error::InvalidSpeed => "InvalidSpeed",
+        Error::InvalidRepeat => "InvalidRepeat",
+        Error::InvalidLED => "InvalidLED",
+        Error::DeviceNotFound => "DeviceNotFound",
+        Error::UnsupportedCommand => "UnsupportedCommand",
+        _ => "InvalidRequest",
+    }
+}
+
+fn error_from_kind(kind: &str) -> Error {
+    match kind {
+        "InvalidColor" => Error::InvalidColor,
+        "InvalidPattern" => Error::InvalidPattern,
+        "InvalidCommand" => Error::InvalidCommand,
+        "InvalidSpeed" => Error::InvalidSpeed,
+        "InvalidRepeat" => Error::InvalidRepeat,
+        "InvalidLED" => Error::InvalidLED,
+        "DeviceNotFound" => Error::DeviceNotFound,
+        "UnsupportedCommand" => Error::UnsupportedCommand,
+        _ => Error::InvalidRequest,
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{capabilities_from_str, capabilities_to_string, Broker, BrokerDevice};
+    use crate::mock::MockDevice;
+    use crate::{
+        Device, DeviceModel, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice, Wave,
+    };
+
+    #[test]
+    fn test_broker_device_round_trip() {
+        let path = std::env::temp_dir().join(format!("luxafor-test-{}.sock", std::process::id()));
+        let mock = MockDevice::default();
+        let broker = Broker::bind(&path, mock.clone()).unwrap();
+        assert!(Broker::bind(&path, MockDevice::default()).is_err());
+        let _ = std::thread::spawn(move || broker.serve());
+
+        let mut device = BrokerDevice::connect(&path).unwrap();
+        assert_eq!(device.id(), "mock");
+        device.set_solid_color(SolidColor::Red).unwrap();
+        device.set_specific_led(SpecificLED::AllFront).unwrap();
+        device
+            .set_color_wave(
+                SolidColor::Blue,
+                Wave::OverlappingLong,
+                Speed::new(30),
+                Repeat::times(2).unwrap(),
+            )
+            .unwrap();
+
+        let other = BrokerDevice::connect(&path).unwrap();
+        other.turn_off().unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec![
+                "led all",
+                "solid red",
+                "led front",
+                "wave blue overlapping long 30 2",
+                "led all",
+                "off",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_broker_device_capabilities() {
+        let path = std::env::temp_dir().join(format!(
+            "luxafor-test-capabilities-{}.sock",
+            std::process::id()
+        ));
+        let mute = DeviceModel::Mute.capabilities();
+        let broker = Broker::bind(&path, MockDevice::with_capabilities(mute.clone())).unwrap();
+        let _ = std::thread::spawn(move || broker.serve());
+
+        let device = BrokerDevice::connect(&path).unwrap();
+        assert_eq!(device.capabilities(), mute);
+
+        for model in [DeviceModel::Flag, DeviceModel::Orb, DeviceModel::Mute] {
+            let capabilities = model.capabilities();
+            assert_eq!(
+                capabilities_from_str(&capabilities_to_string(&capabilities)).unwrap(),
+                capabilities
+            );
+        }
+        assert!(capabilities_from_str("commands=sparkle").is_err());
+    }
+}
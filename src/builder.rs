@@ -0,0 +1,398 @@
+/*!
+A fluent builder for device commands, started with [Device::command](../trait.Device.html#method.command).
+
+Rather than a sequence of calls with positional arguments, a command is described step by step and
+then either built, as a [TargetedCommand] value that can be stored, logged, and replayed, or sent
+directly to the device. Any arguments not provided take the same defaults as the `lux` command line,
+and the command is validated against the device's capabilities before anything is sent.
+
+# Example
+
+```rust,ignore
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::{Device, Repeat, SolidColor, SpecificLED, Speed};
+use luxafor::error::Result;
+use std::time::Duration;
+
+fn alert() -> Result<()> {
+    let discovery = USBDeviceDiscovery::new()?;
+    let mut device = discovery.device()?;
+    device
+        .command()
+        .led(SpecificLED::AllFront)
+        .color(SolidColor::Red)
+        .strobe()
+        .speed(Speed::from_duration(Duration::from_millis(500))?)
+        .repeat(Repeat::Forever)
+        .send()
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{
+    Capabilities, Command, CommandKind, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed,
+    TargetedDevice, Wave,
+};
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroU8;
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A command, optionally preceded by the LED it targets. The text form is that of the command,
+/// prefixed by `led` and the LED when one is targeted, for example `led front strobe red 10 255`.
+///
+#[derive(Clone, Debug)]
+pub struct TargetedCommand {
+    /// The LED to target before the command is applied, if any
+    pub led: Option<SpecificLED>,
+    /// The command to apply
+    pub command: Command,
+}
+
+///
+/// Builds a command for a device, see the [module documentation](index.html).
+///
+#[allow(missing_debug_implementations)]
+pub struct CommandBuilder<'a, D: Device> {
+    device: &'a mut D,
+    set_led: Option<fn(&mut D, SpecificLED) -> Result<()>>,
+    led: Option<SpecificLED>,
+    kind: Option<CommandKind>,
+    color: Option<SolidColor>,
+    wave: Option<Wave>,
+    pattern: Option<Pattern>,
+    speed: Option<Speed>,
+    repeat: Option<Repeat>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const DEFAULT_FADE_DURATION: Speed = Speed::new(60);
+const DEFAULT_STROBE_SPEED: Speed = Speed::DEFAULT_STROBE;
+const DEFAULT_WAVE_SPEED: Speed = Speed::new(30);
+const DEFAULT_REPEAT_COUNT: Repeat = Repeat::Times(NonZeroU8::MAX);
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for TargetedCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.led {
+            None => write!(f, "{}", self.command),
+            Some(led) => write!(f, "led {} {}", led, self.command),
+        }
+    }
+}
+
+impl FromStr for TargetedCommand {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_prefix("led ") {
+            None => Ok(Self::from(s.parse::<Command>()?)),
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let (led, command) = rest.split_once(' ').ok_or(Error::InvalidCommand)?;
+                Ok(Self {
+                    led: Some(led.parse()?),
+                    command: command.parse()?,
+                })
+            }
+        }
+    }
+}
+
+impl From<Command> for TargetedCommand {
+    fn from(command: Command) -> Self {
+        Self { led: None, command }
+    }
+}
+
+impl TargetedCommand {
+    ///
+    /// Return an error if the device, with these capabilities, does not support this command or
+    /// cannot target its LED.
+    ///
+    pub fn check(&self, capabilities: &Capabilities) -> Result<()> {
+        if self.led.is_some() && !capabilities.led_targeting {
+            return Err(Error::UnsupportedCommand);
+        }
+        capabilities.check(&self.command)
+    }
+
+    ///
+    /// Apply this command to the provided device, targeting the LED first if one is set.
+    ///
+    pub fn apply<D: TargetedDevice + ?Sized>(self, device: &mut D) -> Result<()> {
+        if let Some(led) = self.led {
+            device.set_specific_led(led)?;
+        }
+        self.command.apply(device)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<'a, D: Device> CommandBuilder<'a, D> {
+    pub(crate) fn new(device: &'a mut D) -> Self {
+        Self {
+            device,
+            set_led: None,
+            led: None,
+            kind: None,
+            color: None,
+            wave: None,
+            pattern: None,
+            speed: None,
+            repeat: None,
+        }
+    }
+
+    ///
+    /// Set the color for the command; on its own this builds a solid color command.
+    ///
+    pub fn color(mut self, color: SolidColor) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    ///
+    /// Build a command to turn the light off.
+    ///
+    pub fn off(self) -> Self {
+        self.with_kind(CommandKind::TurnOff)
+    }
+
+    ///
+    /// Build a command to set a solid color.
+    ///
+    pub fn solid(self) -> Self {
+        self.with_kind(CommandKind::Solid)
+    }
+
+    ///
+    /// Build a command to fade to the color, the speed is the duration of the fade.
+    ///
+    pub fn fade(self) -> Self {
+        self.with_kind(CommandKind::Fade)
+    }
+
+    ///
+    /// Build a command to strobe the color.
+    ///
+    pub fn strobe(self) -> Self {
+        self.with_kind(CommandKind::Strobe)
+    }
+
+    ///
+    /// Build a command to show the color as a wave.
+    ///
+    pub fn wave(mut self, wave: Wave) -> Self {
+        self.wave = Some(wave);
+        self.with_kind(CommandKind::Wave)
+    }
+
+    ///
+    /// Build a command to show a pre-defined pattern.
+    ///
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        self.pattern = Some(pattern);
+        self.with_kind(CommandKind::Pattern)
+    }
+
+    ///
+    /// Set the speed of a strobe or wave, or the duration of a fade.
+    ///
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    ///
+    /// Set the number of times a strobe, wave, or pattern is repeated.
+    ///
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = Some(repeat);
+        self
+    }
+
+    ///
+    /// Build the command, returning an error if the arguments provided do not make a command, or
+    /// the device does not support it.
+    ///
+    pub fn build(&self) -> Result<TargetedCommand> {
+        let kind = match (self.kind, &self.color) {
+            (Some(kind), _) => kind,
+            (None, Some(_)) => CommandKind::Solid,
+            (None, None) => return Err(Error::InvalidCommand),
+        };
+        if (self.speed.is_some() && !kind.has_speed())
+            || (self.repeat.is_some() && !kind.has_repeat())
+        {
+            return Err(Error::InvalidCommand);
+        }
+        let color = || self.color.clone().ok_or(Error::InvalidCommand);
+        let repeat_count = self.repeat.unwrap_or(DEFAULT_REPEAT_COUNT);
+        let command = match kind {
+            CommandKind::TurnOff => Command::TurnOff,
+            CommandKind::Solid => Command::Solid { color: color()? },
+            CommandKind::Fade => Command::Fade {
+                color: color()?,
+                fade_duration: self.speed.unwrap_or(DEFAULT_FADE_DURATION),
+            },
+            CommandKind::Strobe => Command::Strobe {
+                color: color()?,
+                strobe_speed: self.speed.unwrap_or(DEFAULT_STROBE_SPEED),
+                repeat_count,
+            },
+            CommandKind::Wave => Command::Wave {
+                color: color()?,
+                wave_pattern: self.wave.clone().ok_or(Error::InvalidCommand)?,
+                wave_speed: self.speed.unwrap_or(DEFAULT_WAVE_SPEED),
+                repeat_count,
+            },
+            CommandKind::Pattern => Command::Pattern {
+                pattern: self.pattern.clone().ok_or(Error::InvalidCommand)?,
+                repeat_count,
+            },
+        };
+        let command = TargetedCommand {
+            led: self.led.clone(),
+            command,
+        };
+        command
+            .check(&self.device.capabilities())
+            .map_err(|e| e.with_context(&self.device.id(), Some(&command.to_string())))?;
+        Ok(command)
+    }
+
+    ///
+    /// Build the command, and if it is valid send it to the device.
+    ///
+    pub fn send(self) -> Result<()> {
+        let command = self.build()?;
+        debug!("Sending built command '{}'", command);
+        if let (Some(set_led), Some(led)) = (self.set_led, command.led) {
+            set_led(self.device, led)?;
+        }
+        command.command.apply(self.device)
+    }
+
+    fn with_kind(mut self, kind: CommandKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+}
+
+impl<D: TargetedDevice> CommandBuilder<'_, D> {
+    ///
+    /// Target a specific LED, this is only available for devices that implement `TargetedDevice`.
+    ///
+    pub fn led(mut self, led: SpecificLED) -> Self {
+        self.set_led = Some(D::set_specific_led);
+        self.led = Some(led);
+        self
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::TargetedCommand;
+    use crate::mock::MockDevice;
+    use crate::{Capabilities, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed, Wave};
+
+    #[test]
+    fn test_build_and_send() {
+        let mut device = MockDevice::default();
+        device
+            .command()
+            .led(SpecificLED::AllFront)
+            .color(SolidColor::Red)
+            .strobe()
+            .speed("500ms".parse().unwrap())
+            .repeat(Repeat::Forever)
+            .send()
+            .unwrap();
+        device.command().color(SolidColor::Blue).send().unwrap();
+        device
+            .command()
+            .wave(Wave::Short)
+            .color(SolidColor::Green)
+            .send()
+            .unwrap();
+        assert_eq!(
+            device.calls(),
+            vec![
+                "led front",
+                "strobe red 10 forever",
+                "solid blue",
+                "wave green short 30 255"
+            ]
+        );
+
+        let command = device
+            .command()
+            .pattern(Pattern::Police)
+            .repeat(Repeat::times(3).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(command.to_string(), "pattern police 3");
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let mut device = MockDevice::default();
+        assert!(device.command().strobe().build().is_err());
+        assert!(device.command().build().is_err());
+        assert!(device
+            .command()
+            .color(SolidColor::Red)
+            .speed(Speed::new(10))
+            .build()
+            .is_err());
+
+        let mut limited = MockDevice::with_capabilities(Capabilities {
+            led_targeting: false,
+            ..Capabilities::all()
+        });
+        let error = limited
+            .command()
+            .led(SpecificLED::AllBack)
+            .color(SolidColor::Red)
+            .send()
+            .unwrap_err();
+        assert_eq!(error.command(), Some("led back solid red"));
+        assert!(limited.calls().is_empty());
+    }
+
+    #[test]
+    fn test_targeted_command_round_trip() {
+        for text in ["led 3 fade red 60", "led all off", "pattern police 5"] {
+            let command: TargetedCommand = text.parse().unwrap();
+            assert_eq!(command.to_string(), text);
+        }
+        let mut device = MockDevice::default();
+        "led back green"
+            .parse::<TargetedCommand>()
+            .unwrap()
+            .apply(&mut device)
+            .unwrap();
+        assert_eq!(device.calls(), vec!["led back", "solid green"]);
+        assert!("led".parse::<TargetedCommand>().is_err());
+        assert!("led 9 off".parse::<TargetedCommand>().is_err());
+    }
+}
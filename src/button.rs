@@ -0,0 +1,275 @@
+/*!
+Events from the button on the Luxafor Mute, decoded from HID input reports.
+
+The Mute sends an input report each time the button is pressed or released:
+
+| Byte 0 | Byte 1 | Meaning          |
+|--------|--------|------------------|
+| `0x83` | `0x01` | Button pressed   |
+| `0x83` | `0x00` | Button released  |
+
+Any other report is ignored. A [Button] decodes these reports, read from any [InputReports]
+transport, into [ButtonEvent]s; a press held for longer than the long press duration is reported
+as a long press as well as a press, and the release follows either. For a USB connected light the
+same decoding is provided by `USBDevice::next_event` and `USBDevice::events`.
+
+# Example
+
+```rust,ignore
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::button::ButtonEvent;
+use luxafor::error::Result;
+
+fn print_presses() -> Result<()> {
+    let device = USBDeviceDiscovery::new()?.device()?;
+    for event in device.events() {
+        if event? == ButtonEvent::Pressed {
+            println!("pressed");
+        }
+    }
+    Ok(())
+}
+```
+
+*/
+
+use crate::error::Result;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// An event from the button.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ButtonEvent {
+    /// The button was pressed.
+    Pressed,
+    /// The button has been held down for the long press duration.
+    LongPressed,
+    /// The button was released.
+    Released,
+}
+
+///
+/// A source of HID input reports, such as a USB device, or a fake used in tests.
+///
+pub trait InputReports {
+    ///
+    /// Read one input report into `buffer`, waiting at most `timeout`, or forever if `None`;
+    /// return the length of the report, or zero if none arrived before the timeout.
+    ///
+    fn read_report(&self, buffer: &mut [u8], timeout: Option<Duration>) -> Result<usize>;
+}
+
+///
+/// Decodes the input reports from a transport into button events.
+///
+#[derive(Debug)]
+pub struct Button<R> {
+    reports: R,
+    long_press: Duration,
+    state: Mutex<ButtonState>,
+}
+
+///
+/// The default duration for which the button must be held to be a long press.
+///
+pub const DEFAULT_LONG_PRESS: Duration = Duration::from_secs(1);
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const REPORT_BUTTON: u8 = 0x83;
+const BUTTON_PRESSED: u8 = 0x01;
+const BUTTON_RELEASED: u8 = 0x00;
+const REPORT_LENGTH: usize = 8;
+
+#[derive(Debug, Default)]
+pub(crate) struct ButtonState {
+    pressed_at: Option<Instant>,
+    long_press_sent: bool,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for ButtonEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ButtonEvent::Pressed => "press",
+                ButtonEvent::LongPressed => "long press",
+                ButtonEvent::Released => "release",
+            }
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<R: InputReports> Button<R> {
+    ///
+    /// Construct a new button reading from `reports`, with the default long press duration.
+    ///
+    pub fn new(reports: R) -> Self {
+        Self::with_long_press(reports, DEFAULT_LONG_PRESS)
+    }
+
+    ///
+    /// Construct a new button reading from `reports`, where a press held for `long_press` is a
+    /// long press.
+    ///
+    pub fn with_long_press(reports: R, long_press: Duration) -> Self {
+        Self {
+            reports,
+            long_press,
+            state: Default::default(),
+        }
+    }
+
+    ///
+    /// Wait at most `timeout`, or forever if `None`, for the next event; return `None` if there
+    /// was no event before the timeout.
+    ///
+    pub fn next_event(&self, timeout: Option<Duration>) -> Result<Option<ButtonEvent>> {
+        next_event(&self.reports, &self.state, self.long_press, timeout)
+    }
+
+    ///
+    /// Return a blocking iterator over the events from the button.
+    ///
+    pub fn events(&self) -> impl Iterator<Item = Result<ButtonEvent>> + '_ {
+        std::iter::from_fn(move || self.next_event(None).transpose())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+pub(crate) fn next_event<R: InputReports + ?Sized>(
+    reports: &R,
+    state: &Mutex<ButtonState>,
+    long_press: Duration,
+    timeout: Option<Duration>,
+) -> Result<Option<ButtonEvent>> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut state = state.lock().unwrap();
+    let mut buffer = [0u8; REPORT_LENGTH];
+    loop {
+        let now = Instant::now();
+        let until_long_press = match state.pressed_at {
+            Some(pressed_at) if !state.long_press_sent => {
+                let due = pressed_at + long_press;
+                if now >= due {
+                    state.long_press_sent = true;
+                    return Ok(Some(ButtonEvent::LongPressed));
+                }
+                Some(due - now)
+            }
+            _ => None,
+        };
+        let until_deadline = deadline.map(|deadline| deadline.saturating_duration_since(now));
+        if until_deadline == Some(Duration::ZERO) {
+            return Ok(None);
+        }
+        let wait = match (until_long_press, until_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let length = reports.read_report(&mut buffer, wait)?;
+        trace!("read input report {:02x?}", &buffer[..length]);
+        match &buffer[..length] {
+            [REPORT_BUTTON, BUTTON_PRESSED, ..] if state.pressed_at.is_none() => {
+                state.pressed_at = Some(Instant::now());
+                state.long_press_sent = false;
+                return Ok(Some(ButtonEvent::Pressed));
+            }
+            [REPORT_BUTTON, BUTTON_RELEASED, ..] if state.pressed_at.is_some() => {
+                state.pressed_at = None;
+                return Ok(Some(ButtonEvent::Released));
+            }
+            _ => {}
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Button, ButtonEvent, InputReports};
+    use crate::error::Result;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    // Each read returns the next report; an empty report stands for silence until the timeout.
+    struct FakeReports(Mutex<VecDeque<Vec<u8>>>);
+
+    impl FakeReports {
+        fn new(reports: &[&[u8]]) -> Self {
+            Self(Mutex::new(reports.iter().map(|r| r.to_vec()).collect()))
+        }
+    }
+
+    impl InputReports for FakeReports {
+        fn read_report(&self, buffer: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+            let report = self.0.lock().unwrap().pop_front().unwrap_or_default();
+            if report.is_empty() {
+                std::thread::sleep(timeout.expect("fake reports would block forever"));
+            }
+            buffer[..report.len()].copy_from_slice(&report);
+            Ok(report.len())
+        }
+    }
+
+    #[test]
+    fn test_press_and_release() {
+        let button = Button::new(FakeReports::new(&[
+            &[0x83, 0x01],
+            &[0x01, 0x02, 0x03],
+            &[0x83, 0x01],
+            &[0x83, 0x00],
+        ]));
+        let events: Vec<ButtonEvent> = button.events().take(2).map(Result::unwrap).collect();
+        assert_eq!(events, vec![ButtonEvent::Pressed, ButtonEvent::Released]);
+        assert_eq!(
+            button.next_event(Some(Duration::from_millis(5))).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_long_press() {
+        let button = Button::with_long_press(
+            FakeReports::new(&[&[0x83, 0x01], &[], &[0x83, 0x00]]),
+            Duration::from_millis(20),
+        );
+        let timeout = Some(Duration::from_secs(1));
+        assert_eq!(
+            button.next_event(timeout).unwrap(),
+            Some(ButtonEvent::Pressed)
+        );
+        assert_eq!(
+            button.next_event(timeout).unwrap(),
+            Some(ButtonEvent::LongPressed)
+        );
+        assert_eq!(
+            button.next_event(timeout).unwrap(),
+            Some(ButtonEvent::Released)
+        );
+        assert_eq!(ButtonEvent::LongPressed.to_string(), "long press");
+    }
+}
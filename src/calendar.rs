@@ -0,0 +1,988 @@
+/*!
+Show calendar availability on a light, from an iCalendar (ICS) file or URL.
+
+A [Calendar] is parsed from the text of an ICS feed and expanded into the [Occurrence]s of its
+events within a time window, including recurring events (`RRULE`, `RDATE`, and `EXDATE`) and
+modified instances of them (`RECURRENCE-ID`). Times may be in UTC, in a named time zone using an
+IANA `TZID` such as `Europe/London`, or floating, in which case the local time zone is used.
+All-day events, dates marked `VALUE=DATE` or given without a time, run from midnight to midnight
+in the local time zone. An event with a property that cannot be parsed is skipped with a warning.
+
+The following events never make a user busy:
+
+* cancelled events, those with `STATUS:CANCELLED`,
+* free time, those with `TRANSP:TRANSPARENT`,
+* all-day events, unless `ignore_all_day` is `false`,
+* events declined by the `attendee` email address, if one is set.
+
+A [CalendarMonitor] shows the [Availability] at any time on a device; the light shows `busy` while
+an event is in progress, `warning` for the period before an event starts, and `available`
+otherwise, by default these are the [status presets](../status/index.html) in the current
+palette. The calendar is reloaded from its [Source] on an interval, and the monitor uses a
+[Clock](../schedule/trait.Clock.html) so that it may be tested with a simulated one.
+
+# Example
+
+```rust,ignore
+use luxafor::calendar::{CalendarMonitor, CalendarOptions};
+use luxafor::schedule::SystemClock;
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::error::Result;
+
+fn show_availability() -> Result<()> {
+    let discovery = USBDeviceDiscovery::new()?;
+    let source = "https://calendar.example.com/me/basic.ics".parse()?;
+    let mut monitor = CalendarMonitor::new(source, CalendarOptions::default(), SystemClock);
+    monitor.run(&discovery.device()?)
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::schedule::Clock;
+use crate::status::StatusPreset;
+use crate::{Command, Device};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use rrule::{RRule, RRuleSet, Unvalidated};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The events parsed from an iCalendar feed.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Calendar {
+    events: Vec<CalendarEvent>,
+}
+
+///
+/// A single occurrence of an event.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    /// The summary, or title, of the event.
+    pub summary: String,
+    /// When the occurrence starts.
+    pub start: DateTime<Utc>,
+    /// When the occurrence ends.
+    pub end: DateTime<Utc>,
+}
+
+///
+/// Availability, as determined by the calendar.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Availability {
+    /// No event is in progress or about to start.
+    Available,
+    /// An event starts within the warning period.
+    Warning,
+    /// An event is in progress.
+    Busy,
+}
+
+///
+/// Where a calendar is loaded from.
+///
+#[derive(Clone, Debug)]
+pub enum Source {
+    /// A local ICS file.
+    File(PathBuf),
+    /// An ICS feed fetched over HTTP(S); `webcal:` URLs are fetched using HTTPS.
+    Url(String),
+}
+
+///
+/// Options for a [CalendarMonitor].
+///
+#[derive(Clone, Debug)]
+pub struct CalendarOptions {
+    /// Applied while an event is in progress.
+    pub busy: Command,
+    /// Applied for the `warning` period before an event starts.
+    pub warning: Command,
+    /// Applied at all other times.
+    pub available: Command,
+    /// How long before an event starts to apply the `warning` command.
+    pub warning_before: Duration,
+    /// How often the calendar is reloaded from its source.
+    pub refresh: Duration,
+    /// If `true`, all-day events are ignored.
+    pub ignore_all_day: bool,
+    /// If set, events declined by this email address are ignored.
+    pub attendee: Option<String>,
+}
+
+///
+/// Applies the availability from a calendar to a device as time passes.
+///
+#[derive(Debug)]
+pub struct CalendarMonitor<C: Clock> {
+    source: Source,
+    options: CalendarOptions,
+    clock: C,
+    occurrences: Vec<Occurrence>,
+    refreshed: Option<DateTime<Utc>>,
+    applied: Option<String>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+struct CalendarEvent {
+    uid: String,
+    summary: String,
+    start: IcalTime,
+    end: Option<IcalTime>,
+    duration: Option<TimeDelta>,
+    rrules: Vec<String>,
+    rdates: Vec<IcalTime>,
+    exdates: Vec<IcalTime>,
+    recurrence_id: Option<IcalTime>,
+    cancelled: bool,
+    transparent: bool,
+    declined_by: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+enum IcalTime {
+    Utc(NaiveDateTime),
+    Zoned(NaiveDateTime, chrono_tz::Tz),
+    Floating(NaiveDateTime),
+    Date(NaiveDate),
+}
+
+#[derive(Debug)]
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+const DATE_FORMAT: &str = "%Y%m%d";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+// The most occurrences of a single recurring event expanded within one window.
+const MAX_OCCURRENCES: u16 = 1000;
+
+// Occurrences are loaded for this long beyond the next refresh, so that a failed refresh does not
+// leave the light showing stale availability.
+const LOOKAHEAD_DAYS: i64 = 1;
+
+const DEFAULT_WARNING_MINUTES: u64 = 5;
+const DEFAULT_REFRESH_MINUTES: u64 = 15;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the availability at `at` given `occurrences`, warning `warning_before` an event starts.
+///
+pub fn availability_at(
+    occurrences: &[Occurrence],
+    at: &DateTime<Utc>,
+    warning_before: TimeDelta,
+) -> Availability {
+    if occurrences.iter().any(|o| o.start <= *at && *at < o.end) {
+        Availability::Busy
+    } else if occurrences
+        .iter()
+        .any(|o| *at < o.start && o.start - warning_before <= *at)
+    {
+        Availability::Warning
+    } else {
+        Availability::Available
+    }
+}
+
+///
+/// Return the first time after `after` at which the availability may change.
+///
+pub fn next_change(
+    occurrences: &[Occurrence],
+    after: &DateTime<Utc>,
+    warning_before: TimeDelta,
+) -> Option<DateTime<Utc>> {
+    occurrences
+        .iter()
+        .flat_map(|o| [o.start - warning_before, o.start, o.end])
+        .filter(|at| at > after)
+        .min()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl FromStr for Calendar {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        let mut event: Option<CalendarEvent> = None;
+        // Components nested within an event, such as alarms, are skipped.
+        let mut nested = 0;
+        // The name of the first property of the current event that could not be parsed, if any.
+        let mut invalid_property: Option<String> = None;
+        for line in unfold(s) {
+            let property = Property::parse(&line)?;
+            match (property.name.as_str(), property.value) {
+                ("BEGIN", "VEVENT") if event.is_none() => {
+                    event = Some(CalendarEvent::default());
+                    invalid_property = None;
+                }
+                ("BEGIN", _) if event.is_some() => nested += 1,
+                ("END", "VEVENT") if nested == 0 => match (event.take(), invalid_property.take()) {
+                    (Some(invalid), Some(name)) => {
+                        warn!("Ignoring event '{}' with an invalid {}", invalid.uid, name)
+                    }
+                    (Some(complete), None) if complete.is_valid() => events.push(complete),
+                    (Some(invalid), None) => {
+                        warn!("Ignoring event '{}' with no start", invalid.uid)
+                    }
+                    (None, _) => return Err(Error::InvalidCalendar),
+                },
+                ("END", _) if event.is_some() => nested -= 1,
+                _ => {
+                    if let (Some(event), 0) = (&mut event, nested) {
+                        if event.set_property(&property).is_err() && invalid_property.is_none() {
+                            invalid_property = Some(property.name.clone());
+                        }
+                    }
+                }
+            }
+        }
+        if event.is_some() {
+            return Err(Error::InvalidCalendar);
+        }
+        debug!("Parsed {} events", events.len());
+        Ok(Self { events })
+    }
+}
+
+impl Calendar {
+    ///
+    /// Return all occurrences of events that overlap the time from `from` until `until`, ordered by
+    /// start time. Cancelled and free events are always excluded, all-day events are excluded if
+    /// `ignore_all_day` is `true`, and events declined by `attendee` are excluded if it is set.
+    ///
+    pub fn occurrences(
+        &self,
+        from: &DateTime<Utc>,
+        until: &DateTime<Utc>,
+        ignore_all_day: bool,
+        attendee: Option<&str>,
+    ) -> Vec<Occurrence> {
+        let replaced: HashSet<(&str, DateTime<Utc>)> = self
+            .events
+            .iter()
+            .filter_map(|event| {
+                event
+                    .recurrence_id
+                    .as_ref()
+                    .and_then(IcalTime::to_utc)
+                    .map(|at| (event.uid.as_str(), at))
+            })
+            .collect();
+
+        let mut occurrences: Vec<Occurrence> = self
+            .events
+            .iter()
+            .filter(|event| !event.is_ignored(ignore_all_day, attendee))
+            .flat_map(|event| match event.starts(from, until) {
+                Ok(starts) => {
+                    let length = event.length();
+                    starts
+                        .into_iter()
+                        .filter(|start| {
+                            event.recurrence_id.is_some()
+                                || !replaced.contains(&(event.uid.as_str(), *start))
+                        })
+                        .map(|start| Occurrence {
+                            summary: event.summary.clone(),
+                            start,
+                            end: start + length,
+                        })
+                        .collect()
+                }
+                Err(e) => {
+                    warn!("Ignoring event '{}': {}", event.uid, e);
+                    Vec::new()
+                }
+            })
+            .filter(|occurrence| occurrence.start < *until && occurrence.end > *from)
+            .collect();
+        occurrences.sort_by_key(|occurrence| occurrence.start);
+        occurrences
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Availability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Availability::Available => "available",
+                Availability::Warning => "warning",
+                Availability::Busy => "busy",
+            }
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Url(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err(Error::InvalidCalendar)
+        } else if let Some(rest) = s.strip_prefix("webcal://") {
+            Ok(Source::Url(format!("https://{}", rest)))
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Source::Url(s.to_string()))
+        } else {
+            Ok(Source::File(PathBuf::from(s)))
+        }
+    }
+}
+
+impl Source {
+    ///
+    /// Load and parse the calendar.
+    ///
+    pub fn load(&self) -> Result<Calendar> {
+        info!("Loading calendar from {}", self);
+        let text = match self {
+            Source::File(path) => std::fs::read_to_string(path)?,
+            Source::Url(url) => {
+                let response = reqwest::blocking::get(url)?;
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(Error::Http {
+                        status: status.as_u16(),
+                        body: response.text().unwrap_or_default(),
+                    });
+                }
+                response.text()?
+            }
+        };
+        text.parse()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Default for CalendarOptions {
+    fn default() -> Self {
+        Self {
+            busy: StatusPreset::Busy.command(),
+            warning: StatusPreset::Warning.command(),
+            available: StatusPreset::Available.command(),
+            warning_before: Duration::from_secs(DEFAULT_WARNING_MINUTES * 60),
+            refresh: Duration::from_secs(DEFAULT_REFRESH_MINUTES * 60),
+            ignore_all_day: true,
+            attendee: None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<C: Clock> CalendarMonitor<C> {
+    ///
+    /// Construct a new monitor for the calendar at `source`, reading the time from `clock`.
+    ///
+    pub fn new(source: Source, options: CalendarOptions, clock: C) -> Self {
+        Self {
+            source,
+            options,
+            clock,
+            occurrences: Vec::new(),
+            refreshed: None,
+            applied: None,
+        }
+    }
+
+    ///
+    /// Return the clock used by this monitor.
+    ///
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    ///
+    /// Reload the calendar if it is due, apply the current availability if it differs from the
+    /// last one applied, and return the time at which the monitor should next step.
+    ///
+    /// An error loading the calendar is returned the first time, after that the previously loaded
+    /// calendar continues to be used.
+    ///
+    pub fn step<D: Device + ?Sized>(&mut self, device: &D) -> Result<DateTime<C::TimeZone>> {
+        let local_now = self.clock.now();
+        let now = local_now.with_timezone(&Utc);
+        let refresh = delta(self.options.refresh);
+        let warning_before = delta(self.options.warning_before);
+
+        if self
+            .refreshed
+            .is_none_or(|refreshed| now >= refreshed + refresh)
+        {
+            let until = now + refresh + warning_before + TimeDelta::days(LOOKAHEAD_DAYS);
+            match self.source.load() {
+                Ok(calendar) => {
+                    self.occurrences = calendar.occurrences(
+                        &now,
+                        &until,
+                        self.options.ignore_all_day,
+                        self.options.attendee.as_deref(),
+                    );
+                    debug!("Loaded {} upcoming occurrences", self.occurrences.len());
+                }
+                Err(e) if self.refreshed.is_none() => return Err(e),
+                Err(e) => warn!("Could not reload calendar, keeping previous: {}", e),
+            }
+            self.refreshed = Some(now);
+        }
+
+        let availability = availability_at(&self.occurrences, &now, warning_before);
+        let command = match availability {
+            Availability::Available => &self.options.available,
+            Availability::Warning => &self.options.warning,
+            Availability::Busy => &self.options.busy,
+        };
+        let text = command.to_string();
+        if self.applied.as_ref() != Some(&text) {
+            info!("Calendar {} at {}, applying '{}'", availability, now, text);
+            command.clone().apply(device)?;
+            self.applied = Some(text);
+        }
+
+        let next_refresh = self.refreshed.unwrap_or(now) + refresh;
+        let next = match next_change(&self.occurrences, &now, warning_before) {
+            Some(change) if change < next_refresh => change,
+            _ => next_refresh,
+        };
+        Ok(next.with_timezone(&local_now.timezone()))
+    }
+
+    ///
+    /// Run the monitor until the process is terminated, or the device fails.
+    ///
+    pub fn run<D: Device + ?Sized>(&mut self, device: &D) -> Result<()> {
+        loop {
+            let next = self.step(device)?;
+            self.clock.sleep_until(&next);
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Default for CalendarEvent {
+    fn default() -> Self {
+        Self {
+            uid: String::new(),
+            summary: String::new(),
+            start: IcalTime::Date(NaiveDate::MIN),
+            end: None,
+            duration: None,
+            rrules: Vec::new(),
+            rdates: Vec::new(),
+            exdates: Vec::new(),
+            recurrence_id: None,
+            cancelled: false,
+            transparent: false,
+            declined_by: Vec::new(),
+        }
+    }
+}
+
+impl CalendarEvent {
+    fn set_property(&mut self, property: &Property<'_>) -> Result<()> {
+        match property.name.as_str() {
+            "UID" => self.uid = property.value.to_string(),
+            "SUMMARY" => self.summary = unescape(property.value),
+            "DTSTART" => self.start = IcalTime::parse(property)?,
+            "DTEND" => self.end = Some(IcalTime::parse(property)?),
+            "DURATION" => self.duration = Some(parse_duration(property.value)?),
+            "RRULE" => self.rrules.push(property.value.to_string()),
+            "RDATE" => self.rdates.extend(IcalTime::parse_list(property)?),
+            "EXDATE" => self.exdates.extend(IcalTime::parse_list(property)?),
+            "RECURRENCE-ID" => self.recurrence_id = Some(IcalTime::parse(property)?),
+            "STATUS" => self.cancelled = property.value.eq_ignore_ascii_case("CANCELLED"),
+            "TRANSP" => self.transparent = property.value.eq_ignore_ascii_case("TRANSPARENT"),
+            "ATTENDEE"
+                if property
+                    .param("PARTSTAT")
+                    .is_some_and(|status| status.eq_ignore_ascii_case("DECLINED")) =>
+            {
+                self.declined_by.push(email(property.value))
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn is_valid(&self) -> bool {
+        !matches!(self.start, IcalTime::Date(NaiveDate::MIN))
+    }
+
+    fn is_all_day(&self) -> bool {
+        matches!(self.start, IcalTime::Date(_))
+    }
+
+    fn is_ignored(&self, ignore_all_day: bool, attendee: Option<&str>) -> bool {
+        self.cancelled
+            || self.transparent
+            || (ignore_all_day && self.is_all_day())
+            || attendee.is_some_and(|attendee| {
+                self.declined_by
+                    .iter()
+                    .any(|declined| declined.eq_ignore_ascii_case(attendee))
+            })
+    }
+
+    fn length(&self) -> TimeDelta {
+        match (&self.duration, self.end.as_ref().and_then(IcalTime::to_utc)) {
+            (Some(duration), _) => *duration,
+            (None, Some(end)) => self
+                .start
+                .to_utc()
+                .map(|start| end - start)
+                .unwrap_or_default(),
+            (None, None) if self.is_all_day() => TimeDelta::days(1),
+            (None, None) => TimeDelta::zero(),
+        }
+    }
+
+    fn starts(&self, from: &DateTime<Utc>, until: &DateTime<Utc>) -> Result<Vec<DateTime<Utc>>> {
+        let start = self.start.to_rrule().ok_or(Error::InvalidCalendar)?;
+        if self.rrules.is_empty() && self.rdates.is_empty() {
+            return Ok(vec![start.with_timezone(&Utc)]);
+        }
+        // An occurrence that starts before the window may still overlap it.
+        let after = *from - self.length();
+        let mut set = RRuleSet::new(start)
+            .after(after.with_timezone(&rrule::Tz::UTC))
+            .before(until.with_timezone(&rrule::Tz::UTC));
+        for rule in &self.rrules {
+            let rule: RRule<Unvalidated> = rule.parse().map_err(|e| {
+                warn!("Invalid RRULE '{}': {}", rule, e);
+                Error::InvalidCalendar
+            })?;
+            set = set.rrule(rule.validate(start).map_err(|_| Error::InvalidCalendar)?);
+        }
+        for rdate in self.rdates.iter().filter_map(IcalTime::to_rrule) {
+            set = set.rdate(rdate);
+        }
+        for exdate in self.exdates.iter().filter_map(IcalTime::to_rrule) {
+            set = set.exdate(exdate);
+        }
+        Ok(set
+            .all(MAX_OCCURRENCES)
+            .dates
+            .into_iter()
+            .map(|start| start.with_timezone(&Utc))
+            .collect())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl IcalTime {
+    fn parse(property: &Property<'_>) -> Result<Self> {
+        Self::parse_value(property.value, property)
+    }
+
+    fn parse_list(property: &Property<'_>) -> Result<Vec<Self>> {
+        property
+            .value
+            .split(',')
+            .map(|value| Self::parse_value(value, property))
+            .collect()
+    }
+
+    fn parse_value(value: &str, property: &Property<'_>) -> Result<Self> {
+        let is_date = property
+            .param("VALUE")
+            .is_some_and(|kind| kind.eq_ignore_ascii_case("DATE"));
+        // Some feeds omit `VALUE=DATE`, so a value that parses as a date alone is also a date.
+        match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+            Ok(date) => return Ok(IcalTime::Date(date)),
+            Err(_) if is_date => return Err(Error::InvalidCalendar),
+            Err(_) => {}
+        }
+        let (local, is_utc) = match value.strip_suffix('Z') {
+            Some(local) => (local, true),
+            None => (value, false),
+        };
+        let local = NaiveDateTime::parse_from_str(local, DATE_TIME_FORMAT)
+            .map_err(|_| Error::InvalidCalendar)?;
+        if is_utc {
+            return Ok(IcalTime::Utc(local));
+        }
+        match property.param("TZID") {
+            None => Ok(IcalTime::Floating(local)),
+            Some(tzid) => match tzid.trim_start_matches('/').parse::<chrono_tz::Tz>() {
+                Ok(timezone) => Ok(IcalTime::Zoned(local, timezone)),
+                Err(_) => {
+                    warn!("Unknown time zone '{}', using local time", tzid);
+                    Ok(IcalTime::Floating(local))
+                }
+            },
+        }
+    }
+
+    fn to_rrule(&self) -> Option<DateTime<rrule::Tz>> {
+        match self {
+            IcalTime::Utc(local) => Some(rrule::Tz::UTC.from_utc_datetime(local)),
+            IcalTime::Zoned(local, timezone) => rrule::Tz::Tz(*timezone)
+                .from_local_datetime(local)
+                .earliest(),
+            IcalTime::Floating(local) => rrule::Tz::LOCAL.from_local_datetime(local).earliest(),
+            IcalTime::Date(date) => rrule::Tz::LOCAL
+                .from_local_datetime(&date.and_time(Default::default()))
+                .earliest(),
+        }
+    }
+
+    fn to_utc(&self) -> Option<DateTime<Utc>> {
+        self.to_rrule().map(|at| at.with_timezone(&Utc))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<'a> Property<'a> {
+    fn parse(line: &'a str) -> Result<Self> {
+        // The value follows the first colon that is not within a quoted parameter value.
+        let mut quoted = false;
+        let split = line
+            .char_indices()
+            .find(|(_, c)| {
+                if *c == '"' {
+                    quoted = !quoted;
+                }
+                *c == ':' && !quoted
+            })
+            .map(|(index, _)| index)
+            .ok_or(Error::InvalidCalendar)?;
+        let (head, value) = (&line[..split], &line[split + 1..]);
+
+        let mut parts = split_unquoted(head, ';').into_iter();
+        let name = parts.next().unwrap_or_default().to_ascii_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.to_ascii_uppercase(), value.trim_matches('"')))
+            .collect();
+        Ok(Self {
+            name,
+            params,
+            value,
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (index, c) in s.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&s[start..index]);
+            start = index + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+fn email(value: &str) -> String {
+    let lower = value.to_ascii_lowercase();
+    lower.strip_prefix("mailto:").unwrap_or(&lower).to_string()
+}
+
+fn parse_duration(value: &str) -> Result<TimeDelta> {
+    // For example P1W, P1DT2H, or PT15M; the sign is ignored as only event lengths are parsed.
+    let value = value.trim_start_matches(['+', '-']);
+    let value = value.strip_prefix('P').ok_or(Error::InvalidCalendar)?;
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            _ => {
+                let n: i64 = number.parse().map_err(|_| Error::InvalidCalendar)?;
+                number.clear();
+                total += match (c, in_time) {
+                    ('W', false) => TimeDelta::weeks(n),
+                    ('D', false) => TimeDelta::days(n),
+                    ('H', true) => TimeDelta::hours(n),
+                    ('M', true) => TimeDelta::minutes(n),
+                    ('S', true) => TimeDelta::seconds(n),
+                    _ => return Err(Error::InvalidCalendar),
+                };
+            }
+        }
+    }
+    if number.is_empty() {
+        Ok(total)
+    } else {
+        Err(Error::InvalidCalendar)
+    }
+}
+
+fn delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        availability_at, next_change, parse_duration, Availability, Calendar, CalendarMonitor,
+        CalendarOptions, Source,
+    };
+    use crate::mock::MockDevice;
+    use crate::schedule::{Clock, SimulatedClock};
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Once;
+    use std::time::Duration;
+
+    const CALENDAR: &str = include_str!("../tests/fixtures/calendar.ics");
+
+    // All-day and floating times are in local time, so the tests pin the local time zone to one
+    // ten hours ahead of UTC; no other test depends on the local offset.
+    fn parse_calendar(text: &str) -> Calendar {
+        static PIN_TIME_ZONE: Once = Once::new();
+        PIN_TIME_ZONE.call_once(|| std::env::set_var("TZ", "<+10>-10"));
+        text.parse().unwrap()
+    }
+
+    fn utc(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, d, h, m, 0).unwrap()
+    }
+
+    fn serve_calendar() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/calendar.ics", listener.local_addr().unwrap());
+        let _ = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).unwrap();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/calendar\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    CALENDAR.len(),
+                    CALENDAR
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let calendar = parse_calendar(CALENDAR);
+        let occurrences = calendar.occurrences(
+            &utc(23, 0, 0),
+            &utc(31, 23, 59),
+            true,
+            Some("sam@example.com"),
+        );
+        let found: Vec<(String, String)> = occurrences
+            .iter()
+            .map(|o| (o.summary.clone(), o.start.format("%d %H:%M").to_string()))
+            .collect();
+        let expected = vec![
+            ("Daily stand-up", "23 09:30"),
+            ("Daily stand-up", "24 09:30"),
+            ("Customer call", "24 15:00"),
+            ("Daily stand-up (moved)", "26 11:00"),
+            ("Daily stand-up", "27 09:30"),
+            // The clocks go forward on the 29th, the stand-up stays at 09:30 local time.
+            ("Daily stand-up", "30 08:30"),
+            ("Daily stand-up", "31 08:30"),
+            (
+                "Design review with a long description that is folded onto a second line",
+                "31 13:00",
+            ),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(summary, start)| (summary.to_string(), start.to_string()))
+            .collect();
+        assert_eq!(found, expected);
+        assert_eq!(occurrences[3].end, utc(26, 11, 15));
+
+        let with_declined_and_all_day =
+            calendar.occurrences(&utc(24, 0, 0), &utc(28, 0, 0), false, None);
+        let summaries: Vec<&str> = with_declined_and_all_day
+            .iter()
+            .map(|o| o.summary.as_str())
+            .collect();
+        assert!(summaries.contains(&"Optional sync"));
+        let offsite = with_declined_and_all_day
+            .iter()
+            .find(|o| o.summary == "Team offsite")
+            .unwrap();
+        assert_eq!(
+            (offsite.start, offsite.end),
+            (utc(26, 14, 0), utc(27, 14, 0))
+        );
+        assert!(!summaries.contains(&"Cancelled one-to-one"));
+        assert!(!summaries.contains(&"Focus time"));
+    }
+
+    #[test]
+    fn test_availability() {
+        let calendar = parse_calendar(CALENDAR);
+        let occurrences = calendar.occurrences(&utc(24, 0, 0), &utc(25, 0, 0), true, None);
+        let warning = TimeDelta::minutes(5);
+        assert_eq!(
+            availability_at(&occurrences, &utc(24, 9, 0), warning),
+            Availability::Available
+        );
+        assert_eq!(
+            availability_at(&occurrences, &utc(24, 9, 25), warning),
+            Availability::Warning
+        );
+        assert_eq!(
+            availability_at(&occurrences, &utc(24, 9, 30), warning),
+            Availability::Busy
+        );
+        assert_eq!(
+            availability_at(&occurrences, &utc(24, 9, 45), warning),
+            Availability::Available
+        );
+        assert_eq!(
+            next_change(&occurrences, &utc(24, 9, 45), warning),
+            Some(utc(24, 13, 55))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("BEGIN:VEVENT\nDTSTART:20260101T090000Z\n"
+            .parse::<Calendar>()
+            .is_err());
+        let skipped = parse_calendar(
+            "BEGIN:VEVENT\nUID:bad\nDTSTART:tomorrow\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:good\nDTSTART:20260101\nEND:VEVENT",
+        );
+        assert_eq!(skipped.events.len(), 1);
+        assert_eq!(skipped.events[0].uid, "good");
+        assert!(
+            "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20260101T090000\nEND:VEVENT"
+                .parse::<Calendar>()
+                .is_ok_and(|calendar| calendar.events.is_empty())
+        );
+        assert!("no colon".parse::<Calendar>().is_err());
+        assert_eq!(
+            parse_duration("P1DT2H30M").unwrap(),
+            TimeDelta::minutes(1590)
+        );
+        assert_eq!(parse_duration("-PT15M").unwrap(), TimeDelta::minutes(15));
+        assert!(parse_duration("PT15").is_err());
+        assert!(matches!(
+            "webcal://example.com/a.ics".parse::<Source>().unwrap(),
+            Source::Url(url) if url == "https://example.com/a.ics"
+        ));
+    }
+
+    #[test]
+    fn test_monitor_with_simulated_clock() {
+        let _ = parse_calendar(CALENDAR);
+        let source: Source = serve_calendar().parse().unwrap();
+        let device = MockDevice::default();
+        let options = CalendarOptions {
+            refresh: Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let clock = SimulatedClock::new(utc(24, 9, 0));
+        let mut monitor = CalendarMonitor::new(source, options, clock);
+
+        let mut wake_times = Vec::new();
+        while monitor.clock().now() < utc(24, 11, 0) {
+            let next = monitor.step(&device).unwrap();
+            wake_times.push(next.format("%H:%M").to_string());
+            monitor.clock().sleep_until(&next);
+        }
+        assert_eq!(
+            wake_times,
+            vec!["09:25", "09:30", "09:45", "10:00", "11:00"]
+        );
+        assert_eq!(
+            device.calls(),
+            vec!["solid green", "solid yellow", "solid red", "solid green"]
+        );
+    }
+}
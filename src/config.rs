@@ -0,0 +1,487 @@
+/*!
+The configuration file read by the `lux` command line tool.
+
+The file is in [TOML](https://toml.io) format and is read from `luxafor/config.toml` in the
+directory named by `XDG_CONFIG_HOME`, or `$HOME/.config` if that is not set. All sections are
+optional.
+
+```toml
+palette = "colorblind"
+
+[groups]
+team-room = ["usb", "2a0f2c73b72", "3b1e4d84c83"]
+
+[calendar]
+source = "https://calendar.example.com/me/basic.ics"
+attendee = "me@example.com"
+warning_minutes = 5
+refresh_minutes = 15
+
+[schedule]
+default = "off"
+rules = [
+    "mon-fri 09:00-17:30 green",
+    "mon-fri 12:00-13:00 yellow",
+]
+
+[receive]
+address = "127.0.0.1:5384"
+rules = [
+    "alertmanager firing:critical pattern police 255",
+    "* failure strobe red 10 255",
+    "* resolved green",
+]
+
+[run]
+running = "wave yellow short 30 255"
+success = "green"
+failure = "strobe red 10 255"
+restore = "off"
+restore_delay = 10
+```
+
+*/
+
+use crate::error::Result;
+use crate::status::Palette;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "calendar")]
+use crate::calendar::{CalendarOptions, Source};
+#[cfg(feature = "process")]
+use crate::process::StatusCommands;
+#[cfg(feature = "receiver")]
+use crate::receiver::{default_rules, EventRule, DEFAULT_ADDRESS};
+#[cfg(feature = "schedule")]
+use crate::schedule::Schedule;
+#[cfg(any(feature = "calendar", feature = "process"))]
+use std::time::Duration;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The contents of a configuration file.
+///
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The calendar source and commands used by `lux calendar`.
+    #[cfg(feature = "calendar")]
+    pub calendar: Option<CalendarConfig>,
+    /// Named groups of devices, each a list of device identifiers, that may be used in place of a
+    /// single device identifier.
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
+    /// The palette used for the status presets, `standard` or `colorblind`.
+    pub palette: Option<String>,
+    /// The address and rules used by `lux receive`.
+    #[cfg(feature = "receiver")]
+    pub receive: Option<ReceiveConfig>,
+    /// The status commands used by `lux run`.
+    #[cfg(feature = "process")]
+    pub run: Option<RunConfig>,
+    /// The working-hours schedule used by `lux schedule`.
+    #[cfg(feature = "schedule")]
+    pub schedule: Option<ScheduleConfig>,
+}
+
+///
+/// The `[calendar]` section of a configuration file; any value not set uses the default.
+///
+#[cfg(feature = "calendar")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// The path, or URL, of the ICS calendar.
+    pub source: Option<String>,
+    /// Applied while an event is in progress.
+    pub busy: Option<String>,
+    /// Applied for the warning period before an event starts.
+    pub warning: Option<String>,
+    /// Applied at all other times.
+    pub available: Option<String>,
+    /// The warning period, in minutes.
+    pub warning_minutes: Option<u64>,
+    /// How often the calendar is reloaded, in minutes.
+    pub refresh_minutes: Option<u64>,
+    /// Whether all-day events are ignored, by default they are.
+    pub ignore_all_day: Option<bool>,
+    /// The email address whose declined events are ignored.
+    pub attendee: Option<String>,
+}
+
+///
+/// The `[receive]` section of a configuration file.
+///
+#[cfg(feature = "receiver")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReceiveConfig {
+    /// The address, host and port, to listen on.
+    pub address: Option<String>,
+    /// The event rules, in the text form of a `receiver::EventRule`; the default rules are used
+    /// if not set.
+    pub rules: Option<Vec<String>>,
+}
+
+///
+/// The `[run]` section of a configuration file; any value not set uses the default.
+///
+#[cfg(feature = "process")]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunConfig {
+    /// Applied while the process is running.
+    pub running: Option<String>,
+    /// Applied when the process exits successfully.
+    pub success: Option<String>,
+    /// Applied when the process fails.
+    pub failure: Option<String>,
+    /// Applied after the restore delay.
+    pub restore: Option<String>,
+    /// The restore delay, in seconds.
+    pub restore_delay: Option<u64>,
+}
+
+///
+/// The `[schedule]` section of a configuration file.
+///
+#[cfg(feature = "schedule")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// The command applied when no rule is active, in the text form of a `Command`.
+    #[serde(default = "default_command")]
+    pub default: String,
+    /// The rules, in the text form of a `schedule::Rule`.
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const CONFIG_DIR_NAME: &str = "luxafor";
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the configuration file path used when none is specified.
+///
+pub fn default_config_path() -> PathBuf {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".config"))
+            .unwrap_or_default(),
+    };
+    config_dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Config {
+    ///
+    /// Load the configuration file at `path`.
+    ///
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    ///
+    /// Load the configuration file at `path`, returning an empty configuration if the file does
+    /// not exist.
+    ///
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            debug!("No configuration file at {:?}", path);
+            Ok(Self::default())
+        }
+    }
+
+    ///
+    /// Parse the text of a configuration file.
+    ///
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    ///
+    /// Return the device identifiers of the group `name`, if it is defined.
+    ///
+    pub fn group(&self, name: &str) -> Option<&[String]> {
+        self.groups.get(name).map(Vec::as_slice)
+    }
+
+    ///
+    /// Parse the configured palette, if one is set.
+    ///
+    pub fn palette(&self) -> Result<Option<Palette>> {
+        self.palette.as_deref().map(str::parse).transpose()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "calendar")]
+impl CalendarConfig {
+    ///
+    /// Parse the configured source, if one is set.
+    ///
+    pub fn source(&self) -> Result<Option<Source>> {
+        self.source.as_deref().map(str::parse).transpose()
+    }
+
+    ///
+    /// Parse the configured commands and options, using the defaults for any not set.
+    ///
+    pub fn options(&self) -> Result<CalendarOptions> {
+        let mut options = CalendarOptions::default();
+        let configured = [
+            (&self.busy, &mut options.busy),
+            (&self.warning, &mut options.warning),
+            (&self.available, &mut options.available),
+        ];
+        for (text, command) in configured {
+            if let Some(text) = text {
+                *command = text.parse()?;
+            }
+        }
+        if let Some(minutes) = self.warning_minutes {
+            options.warning_before = Duration::from_secs(minutes * 60);
+        }
+        if let Some(minutes) = self.refresh_minutes {
+            options.refresh = Duration::from_secs(minutes * 60);
+        }
+        if let Some(ignore_all_day) = self.ignore_all_day {
+            options.ignore_all_day = ignore_all_day;
+        }
+        options.attendee = self.attendee.clone();
+        Ok(options)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "receiver")]
+impl ReceiveConfig {
+    ///
+    /// Return the configured address, or the default.
+    ///
+    pub fn address(&self) -> &str {
+        self.address.as_deref().unwrap_or(DEFAULT_ADDRESS)
+    }
+
+    ///
+    /// Parse the configured rules, or return the default rules if none are configured.
+    ///
+    pub fn rules(&self) -> Result<Vec<EventRule>> {
+        match &self.rules {
+            None => Ok(default_rules()),
+            Some(rules) => rules.iter().map(|rule| rule.parse()).collect(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "process")]
+impl RunConfig {
+    ///
+    /// Parse the configured commands, using the defaults for any not set.
+    ///
+    pub fn status_commands(&self) -> Result<StatusCommands> {
+        let mut status = StatusCommands::default();
+        let configured = [
+            (&self.running, &mut status.running),
+            (&self.success, &mut status.success),
+            (&self.failure, &mut status.failure),
+            (&self.restore, &mut status.restore),
+        ];
+        for (text, command) in configured {
+            if let Some(text) = text {
+                *command = text.parse()?;
+            }
+        }
+        if let Some(seconds) = self.restore_delay {
+            status.restore_delay = Duration::from_secs(seconds);
+        }
+        Ok(status)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "schedule")]
+impl ScheduleConfig {
+    ///
+    /// Parse the rules and default command into a `Schedule`.
+    ///
+    pub fn schedule(&self) -> Result<Schedule> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| rule.parse())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Schedule::new(rules, self.default.parse()?))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(feature = "schedule")]
+fn default_command() -> String {
+    "off".to_string()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(
+    test,
+    feature = "calendar",
+    feature = "process",
+    feature = "receiver",
+    feature = "schedule"
+))]
+mod tests {
+    use super::Config;
+    use crate::status::Palette;
+
+    #[test]
+    fn test_parse_schedule() {
+        let config = Config::parse(
+            r#"
+[schedule]
+rules = [
+    "mon-fri 09:00-17:30 green",
+    "mon-fri 12:00-13:00 yellow",
+]
+"#,
+        )
+        .unwrap();
+        let schedule = config.schedule.unwrap().schedule().unwrap();
+        assert_eq!(schedule.rules().len(), 2);
+        assert_eq!(
+            schedule.rules()[1].to_string(),
+            "mon-fri 12:00-13:00 solid yellow"
+        );
+    }
+
+    #[test]
+    fn test_parse_groups() {
+        let config = Config::parse(
+            r#"
+[groups]
+team-room = ["usb", "2a0f2c73b72"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.group("team-room").unwrap(),
+            &["usb".to_string(), "2a0f2c73b72".to_string()]
+        );
+        assert!(config.group("office").is_none());
+        assert!(Config::parse("").unwrap().groups.is_empty());
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        let config = Config::parse("palette = \"colorblind\"\n[groups]").unwrap();
+        assert_eq!(config.palette().unwrap(), Some(Palette::Colorblind));
+        assert_eq!(Config::parse("").unwrap().palette().unwrap(), None);
+        assert!(Config::parse("palette = \"sepia\"")
+            .unwrap()
+            .palette()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_run() {
+        let config = Config::parse(
+            r#"
+[run]
+failure = "strobe red 10 255"
+restore_delay = 2
+"#,
+        )
+        .unwrap();
+        let status = config.run.unwrap().status_commands().unwrap();
+        assert_eq!(status.running.to_string(), "solid yellow");
+        assert_eq!(status.failure.to_string(), "strobe red 10 255");
+        assert_eq!(status.restore_delay.as_secs(), 2);
+        assert!(Config::parse("[run]\nsuccess = \"greenish\"")
+            .unwrap()
+            .run
+            .unwrap()
+            .status_commands()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_receive() {
+        let config = Config::parse(
+            r#"
+[receive]
+rules = ["github failure pattern police 5"]
+"#,
+        )
+        .unwrap();
+        let receive = config.receive.unwrap();
+        assert_eq!(receive.address(), "127.0.0.1:5384");
+        let rules = receive.rules().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].to_string(), "github failure pattern police 5");
+        let config = Config::parse("[receive]\naddress = \"0.0.0.0:80\"").unwrap();
+        assert_eq!(config.receive.unwrap().rules().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_parse_calendar() {
+        let config = Config::parse(
+            r#"
+[calendar]
+source = "webcal://example.com/me.ics"
+busy = "strobe red 10 255"
+warning_minutes = 10
+ignore_all_day = false
+"#,
+        )
+        .unwrap();
+        let calendar = config.calendar.unwrap();
+        assert_eq!(
+            calendar.source().unwrap().unwrap().to_string(),
+            "https://example.com/me.ics"
+        );
+        let options = calendar.options().unwrap();
+        assert_eq!(options.busy.to_string(), "strobe red 10 255");
+        assert_eq!(options.available.to_string(), "solid green");
+        assert_eq!(options.warning_before.as_secs(), 600);
+        assert_eq!(options.refresh.as_secs(), 900);
+        assert!(!options.ignore_all_day);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Config::parse("[schedule]\nrules = \"green\"").is_err());
+        assert!(Config::parse("[shedule]").is_err());
+        let config = Config::parse("[schedule]\nrules = [\"mon-fri green\"]").unwrap();
+        assert!(config.schedule.unwrap().schedule().is_err());
+    }
+}
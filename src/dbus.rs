@@ -0,0 +1,413 @@
+/*!
+A D-Bus service that exports a device, so that desktop tools can control the light without the
+need to run the `lux` command line tool.
+
+# Interface
+
+The service exports the object `/org/luxafor/Light`, implementing the interface
+`org.luxafor.Light`, and by default requests the well-known name `org.luxafor.Light` on the
+session bus. Colors, waves, patterns and LEDs are passed as strings in the same form accepted by
+the command line tool.
+
+| Member            | Kind     | Signature | Corresponds to                       |
+|-------------------|----------|-----------|--------------------------------------|
+| `TurnOff`         | Method   | ``        | `Device::turn_off`                   |
+| `SetSolidColor`   | Method   | `s`       | `Device::set_solid_color`            |
+| `SetFadeToColor`  | Method   | `sy`      | `Device::set_fade_to_color`          |
+| `SetColorStrobe`  | Method   | `syy`     | `Device::set_color_strobe`           |
+| `SetColorWave`    | Method   | `ssyy`    | `Device::set_color_wave`             |
+| `SetPattern`      | Method   | `sy`      | `Device::set_pattern`                |
+| `SetSpecificLed`  | Method   | `s`       | `TargetedDevice::set_specific_led`   |
+| `Apply`           | Method   | `s`       | `Command::apply`                     |
+| `Id`              | Property | `s`       | `Device::id`                         |
+| `LastState`       | Property | `s`       | The last command applied, or empty   |
+| `StateChanged`    | Signal   | `s`       | Emitted with each new state          |
+
+The state reported by `LastState` and `StateChanged` is the text form of the last
+[Command](../enum.Command.html) successfully applied to the device, for example `strobe red 10 5`.
+
+# Example
+
+```bash
+❯ busctl --user call org.luxafor.Light /org/luxafor/Light org.luxafor.Light SetColorStrobe syy red 10 5
+❯ busctl --user get-property org.luxafor.Light /org/luxafor/Light org.luxafor.Light LastState
+s "strobe red 10 5"
+```
+
+*/
+
+use crate::error::Result;
+use crate::TargetedDevice;
+use interface::LightInterface;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A running D-Bus service, the service is available for as long as this value is kept.
+///
+#[allow(missing_debug_implementations)]
+pub struct LightService {
+    connection: Connection,
+}
+
+///
+/// The well-known bus name requested by the service.
+///
+pub const SERVICE_NAME: &str = "org.luxafor.Light";
+
+///
+/// The path of the exported light object.
+///
+pub const OBJECT_PATH: &str = "/org/luxafor/Light";
+
+///
+/// The name of the interface implemented by the light object.
+///
+pub const INTERFACE_NAME: &str = "org.luxafor.Light";
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl LightService {
+    ///
+    /// Export `device` on the session bus.
+    ///
+    pub fn session<D: TargetedDevice + Send + 'static>(device: D) -> Result<Self> {
+        Self::serve(Builder::session()?, device)
+    }
+
+    ///
+    /// Export `device` on the bus at `address`, this is primarily useful for testing with a
+    /// private bus daemon.
+    ///
+    pub fn at_address<D: TargetedDevice + Send + 'static>(
+        address: &str,
+        device: D,
+    ) -> Result<Self> {
+        Self::serve(Builder::address(address)?, device)
+    }
+
+    ///
+    /// Return the underlying bus connection.
+    ///
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    ///
+    /// Block the current thread while the service handles requests.
+    ///
+    pub fn wait(&self) -> ! {
+        loop {
+            std::thread::park();
+        }
+    }
+
+    fn serve<D: TargetedDevice + Send + 'static>(builder: Builder<'_>, device: D) -> Result<Self> {
+        info!("Exporting device '{}' on D-Bus", device.id());
+        let interface = LightInterface::new(Box::new(device));
+        let connection = builder
+            .name(SERVICE_NAME)?
+            .serve_at(OBJECT_PATH, interface)?
+            .build()?;
+        Ok(Self { connection })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Modules
+// ------------------------------------------------------------------------------------------------
+
+// The interface macro generates a public trait, for signal emission, that has no documentation.
+#[allow(missing_docs)]
+mod interface {
+    use crate::{Command, Repeat, SpecificLED, Speed, TargetedDevice};
+    use std::str::FromStr;
+    use std::sync::Mutex;
+    use zbus::fdo;
+    use zbus::object_server::SignalEmitter;
+
+    pub(super) struct LightInterface {
+        device: Mutex<Box<dyn TargetedDevice + Send>>,
+        last_state: String,
+    }
+
+    #[zbus::interface(name = "org.luxafor.Light")]
+    impl LightInterface {
+        /// Turn the light off.
+        async fn turn_off(
+            &mut self,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            self.update(Command::TurnOff, &emitter).await
+        }
+
+        /// Set the light to a continuous solid color.
+        async fn set_solid_color(
+            &mut self,
+            color: &str,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let command = Command::Solid {
+                color: parse(color)?,
+            };
+            self.update(command, &emitter).await
+        }
+
+        /// Set the light to fade from its current color to a new one.
+        async fn set_fade_to_color(
+            &mut self,
+            color: &str,
+            fade_duration: u8,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let command = Command::Fade {
+                color: parse(color)?,
+                fade_duration: Speed::new(fade_duration),
+            };
+            self.update(command, &emitter).await
+        }
+
+        /// Strobe the light, this will dim and brighten the same color. A repeat count of zero
+        /// repeats forever.
+        async fn set_color_strobe(
+            &mut self,
+            color: &str,
+            strobe_speed: u8,
+            repeat_count: u8,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let command = Command::Strobe {
+                color: parse(color)?,
+                strobe_speed: Speed::new(strobe_speed),
+                repeat_count: Repeat::from_firmware(repeat_count),
+            };
+            self.update(command, &emitter).await
+        }
+
+        /// Set the light to repeat one of a pre-defined set of wave patterns. A repeat count of
+        /// zero repeats forever.
+        async fn set_color_wave(
+            &mut self,
+            color: &str,
+            wave_pattern: &str,
+            wave_speed: u8,
+            repeat_count: u8,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let command = Command::Wave {
+                color: parse(color)?,
+                wave_pattern: parse(wave_pattern)?,
+                wave_speed: Speed::new(wave_speed),
+                repeat_count: Repeat::from_firmware(repeat_count),
+            };
+            self.update(command, &emitter).await
+        }
+
+        /// Set the light to repeat one of a pre-defined set of patterns. A repeat count of zero
+        /// repeats forever.
+        async fn set_pattern(
+            &mut self,
+            pattern: &str,
+            repeat_count: u8,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let command = Command::Pattern {
+                pattern: parse(pattern)?,
+                repeat_count: Repeat::from_firmware(repeat_count),
+            };
+            self.update(command, &emitter).await
+        }
+
+        /// Apply a command in its text form, for example `strobe red 10 5`.
+        async fn apply(
+            &mut self,
+            command: &str,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let command: Command = parse(command)?;
+            self.update(command, &emitter).await
+        }
+
+        /// Set the LED to be used for future operations.
+        fn set_specific_led(&mut self, led: &str) -> fdo::Result<()> {
+            let led: SpecificLED = parse(led)?;
+            let device = self
+                .device
+                .get_mut()
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+            device.set_specific_led(led).map_err(to_fdo_error)
+        }
+
+        /// The identifier of the exported device.
+        #[zbus(property)]
+        fn id(&self) -> fdo::Result<String> {
+            let device = self
+                .device
+                .lock()
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+            Ok(device.id())
+        }
+
+        /// The text form of the last command applied to the device.
+        #[zbus(property)]
+        fn last_state(&self) -> String {
+            self.last_state.clone()
+        }
+
+        /// Emitted with the text form of each command applied to the device.
+        #[zbus(signal)]
+        async fn state_changed(emitter: &SignalEmitter<'_>, state: &str) -> zbus::Result<()>;
+    }
+
+    impl LightInterface {
+        pub(super) fn new(device: Box<dyn TargetedDevice + Send>) -> Self {
+            Self {
+                device: Mutex::new(device),
+                last_state: String::new(),
+            }
+        }
+
+        async fn update(
+            &mut self,
+            command: Command,
+            emitter: &SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            let state = command.to_string();
+            {
+                let device = self
+                    .device
+                    .get_mut()
+                    .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+                command.apply(&**device).map_err(to_fdo_error)?;
+            }
+            debug!("Device state changed to '{}'", state);
+            self.last_state = state;
+            self.last_state_changed(emitter).await?;
+            Self::state_changed(emitter, &self.last_state).await?;
+            Ok(())
+        }
+    }
+
+    fn parse<T: FromStr<Err = crate::error::Error>>(value: &str) -> fdo::Result<T> {
+        T::from_str(value).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))
+    }
+
+    fn to_fdo_error(error: crate::error::Error) -> fdo::Error {
+        match error.root() {
+            crate::error::Error::UnsupportedCommand => fdo::Error::NotSupported(error.to_string()),
+            crate::error::Error::InvalidLED => fdo::Error::InvalidArgs(error.to_string()),
+            _ => fdo::Error::Failed(error.to_string()),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+///
+/// These tests start a private session bus, and so require `dbus-daemon`. Run with
+/// `RUSTFLAGS="--cfg dbus_test" cargo test --features dbus`.
+///
+#[cfg(all(test, dbus_test))]
+mod tests {
+    use super::{LightService, INTERFACE_NAME, OBJECT_PATH, SERVICE_NAME};
+    use crate::mock::MockDevice;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::proxy::CacheProperties;
+
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus_test requires dbus-daemon");
+            let mut address = String::new();
+            let _ = BufReader::new(daemon.stdout.as_mut().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Self {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    fn test_service_on_private_bus() {
+        let bus = PrivateBus::start();
+        let mock = MockDevice::default();
+        let _service = LightService::at_address(&bus.address, mock.clone()).unwrap();
+
+        let client = zbus::blocking::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let proxy = light_proxy(&client);
+        let mut signals = proxy.receive_signal("StateChanged").unwrap();
+
+        assert_eq!(proxy.get_property::<String>("Id").unwrap(), "mock");
+        assert_eq!(proxy.get_property::<String>("LastState").unwrap(), "");
+
+        let _: () = proxy.call("SetSpecificLed", &("back",)).unwrap();
+        let _: () = proxy
+            .call("SetColorWave", &("red", "overlapping short", 30u8, 5u8))
+            .unwrap();
+        let signal = signals.next().unwrap();
+        let state: String = signal.body().deserialize().unwrap();
+        assert_eq!(state, "wave red overlapping short 30 5");
+
+        let _: () = proxy.call("Apply", &("pattern police 2",)).unwrap();
+        assert_eq!(
+            proxy.get_property::<String>("LastState").unwrap(),
+            "pattern police 2"
+        );
+
+        assert!(proxy
+            .call::<_, _, ()>("SetSolidColor", &("mauve",))
+            .is_err());
+        assert_eq!(
+            mock.calls(),
+            vec![
+                "led back",
+                "wave red overlapping short 30 5",
+                "pattern police 2"
+            ]
+        );
+    }
+
+    fn light_proxy(connection: &Connection) -> Proxy<'_> {
+        // Cached properties are updated asynchronously, which makes assertions racy.
+        zbus::blocking::proxy::Builder::new(connection)
+            .destination(SERVICE_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface(INTERFACE_NAME)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap()
+    }
+}
@@ -0,0 +1,354 @@
+/*!
+Diagnostics for the most common reasons a light cannot be used, as run by `lux doctor`.
+
+On Linux a USB light that is connected but cannot be opened is almost always a permissions
+problem: the `/dev/hidrawN` node is owned by root, and `USBDeviceDiscovery` reports only that no
+device was found. The checks in this module look for the Luxafor vendor and product IDs in
+`/sys/class/hidraw`, try to open each matching node for reading and writing, and look for a udev
+rule that grants access; [check_webhook] checks that a webhook endpoint answers at all.
+
+The udev rule [UDEV_RULE] gives the logged in user access to any Luxafor light, it can be written
+with [write_udev_rule] to a file such as `/etc/udev/rules.d/60-luxafor.rules`, after which the
+rules must be reloaded with `udevadm control --reload-rules && udevadm trigger`.
+
+# Example
+
+```rust,ignore
+use luxafor::doctor::{check_usb, Status, SystemPaths};
+
+fn diagnose() -> bool {
+    let checks = check_usb(&SystemPaths::default());
+    checks.iter().for_each(|check| println!("{}", check));
+    checks.iter().all(|check| check.status != Status::Failed)
+}
+```
+
+*/
+
+use crate::error::Result;
+use crate::hid::{hidraw_nodes, HidrawNode};
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The system directories examined by the checks, these may be changed to examine a fake tree.
+///
+#[derive(Clone, Debug)]
+pub struct SystemPaths {
+    /// The root of sysfs, usually `/sys`.
+    pub sysfs: PathBuf,
+    /// The directory of device nodes, usually `/dev`.
+    pub dev: PathBuf,
+    /// The directories searched for udev rules.
+    pub udev_rules: Vec<PathBuf>,
+}
+
+///
+/// The outcome of a check.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Nothing is wrong.
+    Ok,
+    /// Something may be wrong, or could not be checked.
+    Warning,
+    /// Something is wrong, and the light cannot be used until it is fixed.
+    Failed,
+}
+
+///
+/// The result of one check, displayed as `[status] name: detail`.
+///
+#[derive(Clone, Debug)]
+pub struct Check {
+    /// What was checked.
+    pub name: String,
+    /// The outcome.
+    pub status: Status,
+    /// A description of what was found.
+    pub detail: String,
+}
+
+///
+/// A udev rule giving the logged in user access to the hidraw node of any Luxafor light.
+///
+pub const UDEV_RULE: &str = "SUBSYSTEM==\"hidraw\", ATTRS{idVendor}==\"04d8\", ATTRS{idProduct}==\"f372\", MODE=\"0660\", TAG+=\"uaccess\"";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Check that a USB light is present, that its hidraw nodes can be opened for reading and
+/// writing, and that a udev rule for it is installed.
+///
+pub fn check_usb(paths: &SystemPaths) -> Vec<Check> {
+    let class = paths.sysfs.join("class/hidraw");
+    let nodes: Vec<HidrawNode> = match hidraw_nodes(&paths.sysfs) {
+        Ok(nodes) => nodes.into_iter().filter(HidrawNode::is_luxafor).collect(),
+        Err(e) => {
+            return vec![Check::new(
+                "device present",
+                Status::Warning,
+                format!(
+                    "could not scan {:?} ({}), these checks require Linux",
+                    class, e
+                ),
+            )]
+        }
+    };
+    let mut checks = vec![if nodes.is_empty() {
+        Check::new(
+            "device present",
+            Status::Failed,
+            "no Luxafor light (VID 04D8, PID F372) is connected".to_string(),
+        )
+    } else {
+        Check::new(
+            "device present",
+            Status::Ok,
+            nodes
+                .iter()
+                .map(|node| format!("{} ({})", node.name, node.product().unwrap_or_default()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }];
+    for node in &nodes {
+        let path = paths.dev.join(&node.name);
+        let name = format!("access {}", path.display());
+        checks.push(
+            match OpenOptions::new().read(true).write(true).open(&path) {
+                Ok(_) => Check::new(&name, Status::Ok, "readable and writable".to_string()),
+                Err(e) if e.kind() == ErrorKind::PermissionDenied => Check::new(
+                    &name,
+                    Status::Failed,
+                    "permission denied, install the udev rule and reconnect the light".to_string(),
+                ),
+                Err(e) => Check::new(&name, Status::Failed, e.to_string()),
+            },
+        );
+    }
+    checks.push(match find_udev_rule(&paths.udev_rules) {
+        Some(path) => Check::new("udev rule", Status::Ok, format!("found in {:?}", path)),
+        None => Check::new(
+            "udev rule",
+            Status::Warning,
+            "no rule for the Luxafor VID/PID was found".to_string(),
+        ),
+    });
+    checks
+}
+
+///
+/// Check that the webhook `endpoint` answers; any HTTP response, even an error status, shows that
+/// the endpoint can be reached.
+///
+#[cfg(feature = "webhook")]
+pub fn check_webhook(endpoint: &str) -> Check {
+    let name = format!("webhook {}", endpoint);
+    let response = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .and_then(|client| client.get(endpoint).send());
+    match response {
+        Ok(response) => Check::new(
+            &name,
+            Status::Ok,
+            format!("answered with status {}", response.status().as_u16()),
+        ),
+        Err(e) => Check::new(&name, Status::Failed, e.to_string()),
+    }
+}
+
+///
+/// Write [UDEV_RULE] to the file at `path`, replacing any existing file.
+///
+pub fn write_udev_rule(path: &Path) -> Result<()> {
+    info!("Writing udev rule to {:?}", path);
+    std::fs::write(path, format!("# Luxafor lights\n{}\n", UDEV_RULE))?;
+    Ok(())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for SystemPaths {
+    fn default() -> Self {
+        Self {
+            sysfs: PathBuf::from("/sys"),
+            dev: PathBuf::from("/dev"),
+            udev_rules: [
+                "/etc/udev/rules.d",
+                "/run/udev/rules.d",
+                "/lib/udev/rules.d",
+                "/usr/lib/udev/rules.d",
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Status::Ok => "ok",
+                Status::Warning => "warning",
+                Status::Failed => "failed",
+            }
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.name, self.detail)
+    }
+}
+
+impl Check {
+    fn new(name: &str, status: Status, detail: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn find_udev_rule(directories: &[PathBuf]) -> Option<PathBuf> {
+    directories
+        .iter()
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "rules")
+        })
+        .find(|path| {
+            std::fs::read_to_string(path)
+                .map(|rules| {
+                    let rules = rules.to_lowercase();
+                    rules.contains("04d8") && rules.contains("f372")
+                })
+                .unwrap_or_default()
+        })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{check_usb, write_udev_rule, Status, SystemPaths};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn fake_tree(name: &str) -> SystemPaths {
+        let root = std::env::temp_dir().join(format!(
+            "luxafor-test-doctor-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        let paths = SystemPaths {
+            sysfs: root.join("sys"),
+            dev: root.join("dev"),
+            udev_rules: vec![root.join("rules.d")],
+        };
+        fs::create_dir_all(&paths.dev).unwrap();
+        fs::create_dir_all(&paths.udev_rules[0]).unwrap();
+        paths
+    }
+
+    fn add_hidraw(paths: &SystemPaths, node: &str, hid_id: &str, name: &str) -> PathBuf {
+        let device = paths.sysfs.join("class/hidraw").join(node).join("device");
+        fs::create_dir_all(&device).unwrap();
+        fs::write(
+            device.join("uevent"),
+            format!("DRIVER=hid-generic\nHID_ID={}\nHID_NAME={}\n", hid_id, name),
+        )
+        .unwrap();
+        paths.dev.join(node)
+    }
+
+    fn statuses(paths: &SystemPaths) -> Vec<(String, Status)> {
+        check_usb(paths)
+            .into_iter()
+            .map(|check| (check.name, check.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_checks() {
+        let paths = fake_tree("checks");
+        let _ = add_hidraw(&paths, "hidraw0", "0003:0000046D:0000C52B", "Logitech");
+        let node = add_hidraw(
+            &paths,
+            "hidraw2",
+            "0003:000004D8:0000F372",
+            "Microchip Technology Inc. LUXAFOR FLAG",
+        );
+        let access = format!("access {}", node.display());
+
+        let checks = check_usb(&paths);
+        assert_eq!(
+            checks[0].detail,
+            "hidraw2 (Microchip Technology Inc. LUXAFOR FLAG)"
+        );
+        assert_eq!(
+            statuses(&paths),
+            vec![
+                ("device present".to_string(), Status::Ok),
+                (access.clone(), Status::Failed),
+                ("udev rule".to_string(), Status::Warning),
+            ]
+        );
+
+        fs::write(&node, b"").unwrap();
+        write_udev_rule(&paths.udev_rules[0].join("60-luxafor.rules")).unwrap();
+        assert_eq!(
+            statuses(&paths),
+            vec![
+                ("device present".to_string(), Status::Ok),
+                (access, Status::Ok),
+                ("udev rule".to_string(), Status::Ok),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_device() {
+        let paths = fake_tree("missing");
+        assert_eq!(check_usb(&paths)[0].status, Status::Warning);
+        fs::create_dir_all(paths.sysfs.join("class/hidraw")).unwrap();
+        let checks = check_usb(&paths);
+        assert_eq!(checks[0].status, Status::Failed);
+        assert_eq!(
+            checks[0].to_string(),
+            "[failed] device present: no Luxafor light (VID 04D8, PID F372) is connected"
+        );
+    }
+}
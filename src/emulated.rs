@@ -0,0 +1,546 @@
+/*!
+Emulation of the commands a device does not support, using timed sequences of solid colors.
+
+An [Emulated] device wraps any other device and implements the whole [Device](../trait.Device.html)
+trait; calls the wrapped device supports, according to its capabilities, are forwarded to it and
+the others are emulated using `set_solid_color`:
+
+* a fade steps from the last color set to the new color,
+* a strobe alternates the color with off, at the requested speed, or the minimum interval if
+  that is slower, in which case it flashes fewer times,
+* a wave fades the color up and back down, as the LEDs cannot be targeted individually.
+
+Speeds, and fade durations, are emulated using `Speed::as_duration`. An emulated sequence runs
+in the background, the first color is set before the call returns, and it is cancelled by the next
+call on the same device. No call is made to the wrapped device less than the minimum interval
+after the previous one, so that a remote API, such as the Luxafor webhook API, is not flooded with
+requests; a sequence has fewer steps, rather than taking longer, to respect the interval. A
+sequence that would run longer than [MAX_SEQUENCE], including one that repeats forever, is cut
+short and the light is left showing its color.
+
+A device that supports a strobe, but ignores its speed, is assumed to strobe at
+`Speed::DEFAULT_STROBE`; a strobe at that speed is forwarded to the device rather than emulated,
+so that the webhook API's own `blink` is used where it can be.
+
+# Example
+
+```rust,ignore
+use luxafor::emulated::Emulated;
+use luxafor::webhook::new_device_for;
+use luxafor::{Device, SolidColor, Speed};
+use luxafor::error::Result;
+
+fn fade_to_red(device_id: &str) -> Result<()> {
+    let device = Emulated::new(new_device_for(device_id)?);
+    device.set_fade_to_color(SolidColor::Red, Speed::new(60))?;
+    device.wait();
+    Ok(())
+}
+```
+
+*/
+
+use crate::error::Result;
+use crate::sequencer::{blend, Sequencer, Step};
+use crate::{Capabilities, CommandKind, Device, Pattern, Repeat, SolidColor, Speed, Wave};
+use std::num::NonZeroU8;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A device that emulates the commands not supported by the device it wraps. Clones share the
+/// wrapped device, and cancel each other's emulated sequences.
+///
+#[derive(Debug)]
+pub struct Emulated<D> {
+    shared: Arc<Shared<D>>,
+}
+
+///
+/// The minimum interval between calls to the wrapped device used by `Emulated::new`.
+///
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+///
+/// The longest an emulated sequence runs before it is cut short.
+///
+pub const MAX_SEQUENCE: Duration = Duration::from_secs(60);
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct Shared<D> {
+    device: D,
+    capabilities: Capabilities,
+    min_interval: Duration,
+    last_call: Mutex<Option<Instant>>,
+    current: Mutex<Rgb>,
+    sequencer: Sequencer,
+}
+
+type Rgb = (u8, u8, u8);
+
+const MAX_FADE_STEPS: u32 = 16;
+const OFF: Rgb = (0, 0, 0);
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<D> Clone for Emulated<D> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<D: Device + Send + Sync + 'static> Emulated<D> {
+    ///
+    /// Wrap `device`, using the default minimum interval between calls.
+    ///
+    pub fn new(device: D) -> Self {
+        Self::with_min_interval(device, DEFAULT_MIN_INTERVAL)
+    }
+
+    ///
+    /// Wrap `device`, making no call to it less than `min_interval` after the previous one.
+    ///
+    pub fn with_min_interval(device: D, min_interval: Duration) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                capabilities: device.capabilities(),
+                device,
+                min_interval,
+                last_call: Mutex::new(None),
+                current: Mutex::new(OFF),
+                sequencer: Sequencer::default(),
+            }),
+        }
+    }
+
+    ///
+    /// Return the wrapped device.
+    ///
+    pub fn inner(&self) -> &D {
+        &self.shared.device
+    }
+
+    fn forward(&self, color: Option<Rgb>, call: impl FnOnce(&D) -> Result<()>) -> Result<()> {
+        self.shared.sequencer.show(|| {
+            self.shared.call(call)?;
+            if let Some(color) = color {
+                *self.shared.current.lock().unwrap() = color;
+            }
+            Ok(())
+        })
+    }
+
+    fn emulate(&self, cycle: Vec<Step<Rgb>>, repeat_count: Repeat, color: Rgb) -> Result<()> {
+        let cycle_length: Duration = cycle.iter().map(|step| step.hold).sum();
+        let max_count = MAX_SEQUENCE.as_millis() / cycle_length.as_millis().max(1);
+        let max_count = NonZeroU8::new(max_count.clamp(1, u8::MAX.into()) as u8).unwrap();
+        let (repeat_count, last) = match repeat_count {
+            Repeat::Times(count) if count <= max_count => (repeat_count, None),
+            _ => {
+                warn!(
+                    "Emulating {} repeats rather than {}, the light is then left showing the color",
+                    max_count, repeat_count
+                );
+                (Repeat::Times(max_count), Some(color))
+            }
+        };
+        let shared = self.shared.clone();
+        self.shared.sequencer.play(
+            self.shared.device.id(),
+            cycle,
+            repeat_count,
+            last,
+            move |color| shared.show(*color),
+        )
+    }
+
+    fn can_emulate(&self) -> bool {
+        self.shared
+            .capabilities
+            .commands
+            .contains(&CommandKind::Solid)
+    }
+
+    fn is_native(&self, kind: CommandKind, speed: Speed) -> bool {
+        let capabilities = &self.shared.capabilities;
+        let native_speed = capabilities.speed.contains(&kind)
+            || (kind == CommandKind::Strobe && speed == Speed::DEFAULT_STROBE);
+        !self.can_emulate() || (capabilities.commands.contains(&kind) && native_speed)
+    }
+}
+
+impl<D: Device + Send + Sync + 'static> Device for Emulated<D> {
+    fn id(&self) -> String {
+        self.shared.device.id()
+    }
+
+    fn turn_off(&self) -> Result<()> {
+        self.forward(Some(OFF), |device| device.turn_off())
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
+        self.forward(Some(color.to_rgb()), |device| device.set_solid_color(color))
+    }
+
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
+        if self.is_native(CommandKind::Fade, fade_duration) {
+            self.forward(Some(color.to_rgb()), |device| {
+                device.set_fade_to_color(color, fade_duration)
+            })
+        } else {
+            debug!("Emulating fade to {} over {}", color, fade_duration);
+            let from = *self.shared.current.lock().unwrap();
+            self.emulate(
+                fade_steps(
+                    from,
+                    color.to_rgb(),
+                    fade_duration.as_duration(),
+                    self.shared.min_interval,
+                ),
+                Repeat::Times(NonZeroU8::MIN),
+                color.to_rgb(),
+            )
+        }
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        if self.is_native(CommandKind::Strobe, strobe_speed) {
+            self.forward(None, |device| {
+                device.set_color_strobe(color, strobe_speed, repeat_count)
+            })
+        } else {
+            debug!(
+                "Emulating strobe {}, at {}, {} times",
+                color, strobe_speed, repeat_count
+            );
+            let half_cycle = strobe_speed.as_duration();
+            let steps = strobe_steps(color.to_rgb(), half_cycle, self.shared.min_interval);
+            let repeat_count = strobe_repeat(repeat_count, half_cycle, steps[0].hold);
+            self.emulate(steps, repeat_count, color.to_rgb())
+        }
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        if self.is_native(CommandKind::Wave, wave_speed)
+            && self.shared.capabilities.waves.contains(&wave_pattern)
+        {
+            self.forward(None, |device| {
+                device.set_color_wave(color, wave_pattern, wave_speed, repeat_count)
+            })
+        } else {
+            debug!(
+                "Emulating wave {} {}, at {}, {} times",
+                color, wave_pattern, wave_speed, repeat_count
+            );
+            self.emulate(
+                wave_steps(
+                    color.to_rgb(),
+                    &wave_pattern,
+                    wave_speed.as_duration(),
+                    self.shared.min_interval,
+                ),
+                repeat_count,
+                color.to_rgb(),
+            )
+        }
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
+        self.forward(None, |device| device.set_pattern(pattern, repeat_count))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = self.shared.capabilities.clone();
+        if self.can_emulate() {
+            for kind in [CommandKind::Fade, CommandKind::Strobe, CommandKind::Wave] {
+                let _ = capabilities.commands.insert(kind);
+                let _ = capabilities.speed.insert(kind);
+            }
+            for kind in [CommandKind::Strobe, CommandKind::Wave] {
+                let _ = capabilities.repeat.insert(kind);
+            }
+            capabilities.waves = Wave::all();
+        }
+        capabilities
+    }
+
+    // Any emulated sequence, and then anything the wrapped device shows in the background.
+    fn wait(&self) {
+        self.shared.sequencer.wait();
+        self.shared.device.wait()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<D: Device> Shared<D> {
+    fn call(&self, call: impl FnOnce(&D) -> Result<()>) -> Result<()> {
+        let mut last_call = self.last_call.lock().unwrap();
+        if let Some(last_call) = *last_call {
+            let wait = self.min_interval.saturating_sub(last_call.elapsed());
+            if !wait.is_zero() {
+                debug!("Waiting {:?} before the next call", wait);
+                std::thread::sleep(wait);
+            }
+        }
+        let result = call(&self.device);
+        *last_call = Some(Instant::now());
+        result
+    }
+
+    fn show(&self, color: Rgb) -> Result<()> {
+        let (red, green, blue) = color;
+        self.call(|device| device.set_solid_color(SolidColor::Custom { red, green, blue }))?;
+        *self.current.lock().unwrap() = color;
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn fade_steps(from: Rgb, to: Rgb, duration: Duration, min_interval: Duration) -> Vec<Step<Rgb>> {
+    let count = if min_interval.is_zero() {
+        MAX_FADE_STEPS
+    } else {
+        (duration.as_millis() / min_interval.as_millis().max(1)).clamp(1, MAX_FADE_STEPS as u128)
+            as u32
+    };
+    let hold = duration / count;
+    (1..=count)
+        .map(|step| Step {
+            value: blend(from, to, f64::from(step) / f64::from(count)),
+            hold,
+        })
+        .collect()
+}
+
+fn strobe_steps(color: Rgb, half_cycle: Duration, min_interval: Duration) -> Vec<Step<Rgb>> {
+    let hold = half_cycle.max(min_interval);
+    vec![Step { value: color, hold }, Step { value: OFF, hold }]
+}
+
+// A strobe slowed down to the minimum interval flashes fewer times, so that it takes no longer.
+fn strobe_repeat(repeat_count: Repeat, half_cycle: Duration, hold: Duration) -> Repeat {
+    match repeat_count {
+        Repeat::Times(count) if hold > half_cycle => {
+            let count = half_cycle.as_nanos() * u128::from(count.get()) / hold.as_nanos();
+            Repeat::Times(NonZeroU8::new(count as u8).unwrap_or(NonZeroU8::MIN))
+        }
+        repeat_count => repeat_count,
+    }
+}
+
+fn wave_steps(
+    color: Rgb,
+    wave_pattern: &Wave,
+    cycle: Duration,
+    min_interval: Duration,
+) -> Vec<Step<Rgb>> {
+    let cycle = match wave_pattern {
+        Wave::Short | Wave::OverlappingShort => cycle,
+        Wave::Long | Wave::OverlappingLong => cycle * 2,
+    };
+    let mut wave = fade_steps(OFF, color, cycle / 2, min_interval);
+    wave.extend(fade_steps(color, OFF, cycle / 2, min_interval));
+    if let (Wave::Short | Wave::Long, Some(last)) = (wave_pattern, wave.last_mut()) {
+        // Non-overlapping waves complete, and stay off, before the next starts.
+        last.hold += cycle / 2;
+    }
+    wave
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{fade_steps, strobe_repeat, strobe_steps, wave_steps, Emulated};
+    use crate::mock::MockDevice;
+    use crate::sequencer::Step;
+    use crate::{Capabilities, CommandKind, Device, Repeat, SolidColor, Speed, Wave};
+    use std::time::{Duration, Instant};
+
+    fn webhook_like() -> MockDevice {
+        let mut capabilities = Capabilities::all();
+        capabilities
+            .commands
+            .retain(|kind| !matches!(kind, CommandKind::Fade | CommandKind::Wave));
+        capabilities.speed.clear();
+        capabilities.waves.clear();
+        MockDevice::with_capabilities(capabilities)
+    }
+
+    #[test]
+    fn test_fade_steps() {
+        let steps = fade_steps(
+            (0, 0, 0),
+            (255, 0, 100),
+            Duration::from_secs(2),
+            Duration::from_millis(500),
+        );
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0].value, (64, 0, 25));
+        assert_eq!(steps[3].value, (255, 0, 100));
+        assert!(steps.iter().all(|s| s.hold == Duration::from_millis(500)));
+
+        let steps = fade_steps(
+            (0, 0, 0),
+            (255, 0, 0),
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+        );
+        assert_eq!(
+            steps,
+            vec![Step {
+                value: (255, 0, 0),
+                hold: Duration::from_millis(100)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_strobe_and_wave_steps() {
+        let steps = strobe_steps((255, 0, 0), Duration::from_millis(100), Duration::ZERO);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].value, (0, 0, 0));
+        let steps = strobe_steps(
+            (255, 0, 0),
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+        );
+        assert!(steps.iter().all(|s| s.hold == Duration::from_secs(1)));
+        let times = |count| Repeat::times(count).unwrap();
+        let (fast, slow) = (Duration::from_millis(100), Duration::from_millis(400));
+        assert_eq!(strobe_repeat(times(20), fast, slow), times(5));
+        assert_eq!(strobe_repeat(times(2), fast, slow), times(1));
+        assert_eq!(strobe_repeat(times(2), slow, slow), times(2));
+        assert_eq!(strobe_repeat(Repeat::Forever, fast, slow), Repeat::Forever);
+
+        let short = wave_steps(
+            (0, 0, 255),
+            &Wave::Short,
+            Duration::from_millis(400),
+            Duration::from_millis(100),
+        );
+        assert_eq!(short.len(), 4);
+        assert_eq!(short[1].value, (0, 0, 255));
+        assert_eq!(short[3].value, (0, 0, 0));
+        assert_eq!(short[3].hold, Duration::from_millis(300));
+        let overlapping = wave_steps(
+            (0, 0, 255),
+            &Wave::OverlappingShort,
+            Duration::from_millis(400),
+            Duration::from_millis(100),
+        );
+        assert_eq!(overlapping[3].hold, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_forward_and_emulate() {
+        let inner = webhook_like();
+        let device = Emulated::with_min_interval(inner.clone(), Duration::from_millis(50));
+        assert!(device.capabilities().commands.contains(&CommandKind::Fade));
+
+        device.set_solid_color(SolidColor::Red).unwrap();
+        device
+            .set_pattern("police".parse().unwrap(), Repeat::times(5).unwrap())
+            .unwrap();
+        device
+            .set_fade_to_color(SolidColor::Blue, Speed::new(2))
+            .unwrap();
+        device.wait();
+        device
+            .set_color_strobe(SolidColor::Red, Speed::new(1), Repeat::times(1).unwrap())
+            .unwrap();
+        device.wait();
+        assert_eq!(
+            inner.calls(),
+            vec![
+                "solid red",
+                "pattern police 5",
+                // The pattern leaves the current color unchanged, the fade starts from red.
+                "solid 800080",
+                "solid 0000ff",
+                "solid ff0000",
+                "solid 000000",
+            ]
+        );
+
+        let native = MockDevice::default();
+        let device = Emulated::with_min_interval(native.clone(), Duration::ZERO);
+        device
+            .set_fade_to_color(SolidColor::Blue, Speed::new(60))
+            .unwrap();
+        device
+            .set_color_strobe(SolidColor::Red, Speed::new(10), Repeat::times(2).unwrap())
+            .unwrap();
+        assert_eq!(native.calls(), vec!["fade blue 60", "strobe red 10 2"]);
+    }
+
+    #[test]
+    fn test_default_strobe_and_max_sequence() {
+        let inner = webhook_like();
+        let device = Emulated::with_min_interval(inner.clone(), Duration::ZERO);
+        device
+            .set_color_strobe(SolidColor::Red, Speed::DEFAULT_STROBE, Repeat::Forever)
+            .unwrap();
+        assert_eq!(inner.calls(), vec!["strobe red 10 forever"]);
+
+        device
+            .set_color_strobe(SolidColor::Blue, Speed::new(0), Repeat::Forever)
+            .unwrap();
+        device.wait();
+        let calls = inner.calls();
+        assert_eq!(calls.len(), 1 + 2 * 255 + 1);
+        assert_eq!(calls[calls.len() - 2], "solid 000000");
+        assert_eq!(calls[calls.len() - 1], "solid 0000ff");
+    }
+
+    #[test]
+    fn test_cancel_and_min_interval() {
+        let inner = webhook_like();
+        let min_interval = Duration::from_millis(50);
+        let device = Emulated::with_min_interval(inner.clone(), min_interval);
+        let start = Instant::now();
+        device
+            .set_color_wave(
+                SolidColor::Green,
+                Wave::Long,
+                Speed::new(255),
+                Repeat::Forever,
+            )
+            .unwrap();
+        device.set_solid_color(SolidColor::Yellow).unwrap();
+        device.wait();
+        assert!(start.elapsed() >= min_interval);
+        let calls = inner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1], "solid yellow");
+    }
+}
@@ -0,0 +1,392 @@
+/*!
+A group of devices, of any mix of backends, controlled as one.
+
+A [DeviceGroup] implements the [Device](../trait.Device.html) trait by applying each call to every
+member concurrently; every member is called even if others fail. The [DeviceGroup::apply] method
+returns a [GroupReport] with the result for each member, the `Device` methods return an
+`Error::Group` listing each failure if any member fails. The capabilities of a group are those
+supported by all of its members.
+
+# Example
+
+```rust,ignore
+use luxafor::group::DeviceGroup;
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::webhook::new_device_for;
+use luxafor::{Command, Device, SolidColor};
+use luxafor::error::Result;
+
+fn team_room(device_ids: &[&str]) -> Result<()> {
+    let mut group = DeviceGroup::new("team-room");
+    group.add(USBDeviceDiscovery::new()?.device()?);
+    for device_id in device_ids {
+        group.add(new_device_for(device_id)?);
+    }
+    let report = group.apply(&Command::Solid { color: SolidColor::Red });
+    println!("{}", report);
+    report.into_result()
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{Capabilities, Command, Device, Pattern, Repeat, SolidColor, Speed, Wave};
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A named group of devices, see the [module documentation](index.html).
+///
+#[allow(missing_debug_implementations)]
+pub struct DeviceGroup {
+    id: String,
+    members: Vec<Mutex<Box<dyn Device + Send>>>,
+}
+
+///
+/// The result of applying a command to each member of a group, in the order the members were
+/// added.
+///
+#[derive(Debug)]
+pub struct GroupReport {
+    /// The identifier of each member, and the result of applying the command to it.
+    pub results: Vec<(String, Result<()>)>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceGroup {
+    ///
+    /// Construct a new, empty, group; the name is used as the group's device identifier.
+    ///
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: name.to_string(),
+            members: Vec::new(),
+        }
+    }
+
+    ///
+    /// Add a device to the group.
+    ///
+    pub fn add<D: Device + Send + 'static>(&mut self, device: D) {
+        self.add_boxed(Box::new(device));
+    }
+
+    ///
+    /// Add a boxed device, such as one returned by [open](../fn.open.html), to the group.
+    ///
+    pub fn add_boxed(&mut self, device: Box<dyn Device + Send>) {
+        self.members.push(Mutex::new(device));
+    }
+
+    ///
+    /// Return the number of members in the group.
+    ///
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    ///
+    /// Return `true` if the group has no members.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    ///
+    /// Return the identifiers of the members of the group.
+    ///
+    pub fn member_ids(&self) -> Vec<String> {
+        self.members
+            .iter()
+            .map(|member| member.lock().unwrap().id())
+            .collect()
+    }
+
+    ///
+    /// Apply `command` to every member concurrently, returning the result for each.
+    ///
+    pub fn apply(&self, command: &Command) -> GroupReport {
+        info!(
+            "Applying '{}' to the {} members of group '{}'",
+            command,
+            self.members.len(),
+            self.id
+        );
+        let text = command.to_string();
+        let results = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .members
+                .iter()
+                .map(|member| {
+                    let text = &text;
+                    scope.spawn(move || {
+                        let member = member.lock().unwrap();
+                        let id = member.id();
+                        let result = command
+                            .clone()
+                            .apply(member.as_ref())
+                            .map_err(|e| e.with_context(&id, Some(text)));
+                        (id, result)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("group member thread panicked"))
+                .collect()
+        });
+        let report = GroupReport { results };
+        for failure in report.failures() {
+            warn!("Group '{}' member failed: {}", self.id, failure);
+        }
+        report
+    }
+}
+
+impl Device for DeviceGroup {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn turn_off(&self) -> Result<()> {
+        self.apply(&Command::TurnOff).into_result()
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
+        self.apply(&Command::Solid { color }).into_result()
+    }
+
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
+        self.apply(&Command::Fade {
+            color,
+            fade_duration,
+        })
+        .into_result()
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.apply(&Command::Strobe {
+            color,
+            strobe_speed,
+            repeat_count,
+        })
+        .into_result()
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.apply(&Command::Wave {
+            color,
+            wave_pattern,
+            wave_speed,
+            repeat_count,
+        })
+        .into_result()
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
+        self.apply(&Command::Pattern {
+            pattern,
+            repeat_count,
+        })
+        .into_result()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let all = Capabilities {
+            led_targeting: false,
+            ..Capabilities::all()
+        };
+        self.members.iter().fold(all, |mut common, member| {
+            let member = member.lock().unwrap().capabilities();
+            common
+                .commands
+                .retain(|kind| member.commands.contains(kind));
+            common.patterns.retain(|p| member.patterns.contains(p));
+            common.waves.retain(|w| member.waves.contains(w));
+            common.repeat.retain(|kind| member.repeat.contains(kind));
+            common.speed.retain(|kind| member.speed.contains(kind));
+            common
+        })
+    }
+
+    fn wait(&self) {
+        for member in &self.members {
+            member.lock().unwrap().wait();
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for GroupReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (id, result) in &self.results {
+            match result {
+                Ok(()) => writeln!(f, "{}: ok", id)?,
+                Err(e) => writeln!(f, "{}: {}", id, e.root())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GroupReport {
+    ///
+    /// Return `true` if the command was applied to every member.
+    ///
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+
+    ///
+    /// Return the errors of the members that failed.
+    ///
+    pub fn failures(&self) -> impl Iterator<Item = &Error> {
+        self.results
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().err())
+    }
+
+    ///
+    /// Return `Ok` if the command was applied to every member, else an `Error::Group` with each
+    /// failure.
+    ///
+    pub fn into_result(self) -> Result<()> {
+        let members = self.results.len();
+        let failures: Vec<Error> = self
+            .results
+            .into_iter()
+            .filter_map(|(_, result)| result.err())
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Group { members, failures })
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::DeviceGroup;
+    use crate::error::{Error, Result};
+    use crate::mock::MockDevice;
+    use crate::{
+        Capabilities, Command, CommandKind, Device, Pattern, Repeat, SolidColor, Speed, Wave,
+    };
+
+    struct FailingDevice;
+
+    impl Device for FailingDevice {
+        fn id(&self) -> String {
+            "failing".to_string()
+        }
+
+        fn turn_off(&self) -> Result<()> {
+            Err(Error::DeviceNotFound)
+        }
+
+        fn set_solid_color(&self, _: SolidColor) -> Result<()> {
+            Err(Error::DeviceNotFound)
+        }
+
+        fn set_fade_to_color(&self, _: SolidColor, _: Speed) -> Result<()> {
+            Err(Error::DeviceNotFound)
+        }
+
+        fn set_color_strobe(&self, _: SolidColor, _: Speed, _: Repeat) -> Result<()> {
+            Err(Error::DeviceNotFound)
+        }
+
+        fn set_color_wave(&self, _: SolidColor, _: Wave, _: Speed, _: Repeat) -> Result<()> {
+            Err(Error::DeviceNotFound)
+        }
+
+        fn set_pattern(&self, _: Pattern, _: Repeat) -> Result<()> {
+            Err(Error::DeviceNotFound)
+        }
+    }
+
+    #[test]
+    fn test_apply_to_all_members() {
+        let first = MockDevice::default();
+        let second = MockDevice::default();
+        let mut group = DeviceGroup::new("team");
+        group.add(first.clone());
+        group.add(FailingDevice);
+        group.add(second.clone());
+        assert_eq!(group.len(), 3);
+        assert_eq!(group.member_ids(), vec!["mock", "failing", "mock"]);
+
+        let report = group.apply(&"solid red".parse().unwrap());
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "mock: ok\nfailing: No device was discovered, or the ID did not resolve to a device\nmock: ok\n"
+        );
+        assert_eq!(first.calls(), vec!["solid red"]);
+        assert_eq!(second.calls(), vec!["solid red"]);
+
+        let error = group.turn_off().unwrap_err();
+        match &error {
+            Error::Group { members, failures } => {
+                assert_eq!(*members, 3);
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].device_id(), Some("failing"));
+                assert_eq!(failures[0].command(), Some("off"));
+            }
+            _ => panic!("expected a group error, not {:?}", error),
+        }
+        assert_eq!(second.calls(), vec!["solid red", "off"]);
+    }
+
+    #[test]
+    fn test_common_capabilities() {
+        let mut limited = Capabilities::all();
+        let _ = limited.commands.remove(&CommandKind::Fade);
+        limited.patterns.retain(|p| *p == Pattern::Police);
+        let mut group = DeviceGroup::new("team");
+        group.add(MockDevice::default());
+        group.add(MockDevice::with_capabilities(limited));
+
+        let capabilities = group.capabilities();
+        assert!(!capabilities.led_targeting);
+        assert!(!capabilities.commands.contains(&CommandKind::Fade));
+        assert_eq!(capabilities.patterns, vec![Pattern::Police]);
+        assert!(capabilities
+            .check(&Command::Solid {
+                color: SolidColor::Green
+            })
+            .is_ok());
+
+        group.add(FailingDevice);
+        let capabilities = group.capabilities();
+        assert_eq!(capabilities.commands.len(), 2);
+        assert!(capabilities.commands.contains(&CommandKind::Solid));
+        assert!(capabilities.patterns.is_empty() && capabilities.waves.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+/*!
+The USB HID identity of the Luxafor lights, and the scan of `/sys/class/hidraw` used to find them
+on Linux.
+
+The scan is shared by the `hidraw` backend of `usb_hid` and by the `doctor` checks, so that both
+agree on which nodes are Luxafor lights. It uses only the standard library and so is available on
+every platform, although it finds nothing where there is no sysfs.
+
+*/
+
+use std::path::{Path, PathBuf};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The USB vendor identifier (VID) of all Luxafor lights.
+///
+pub(crate) const LUXAFOR_VENDOR_ID: u16 = 0x04d8;
+
+///
+/// The USB product identifier (PID) of all Luxafor lights.
+///
+pub(crate) const LUXAFOR_PRODUCT_ID: u16 = 0xf372;
+
+///
+/// A node in `/sys/class/hidraw`, with the identity of the HID device behind it.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct HidrawNode {
+    /// The node name, such as `hidraw3`, which is also its name in `/dev`.
+    pub(crate) name: String,
+    /// The USB vendor identifier from `HID_ID`.
+    pub(crate) vendor_id: u16,
+    /// The USB product identifier from `HID_ID`.
+    pub(crate) product_id: u16,
+    /// The `HID_NAME` of the HID device.
+    pub(crate) hid_name: Option<String>,
+    /// The `HID_UNIQ` of the HID device, usually its serial number.
+    pub(crate) hid_uniq: Option<String>,
+    /// The first ancestor of the HID device in sysfs that is a USB device, if any.
+    pub(crate) usb_device: Option<PathBuf>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return each node in `class/hidraw` below the sysfs root `sysfs` whose HID device can be
+/// identified, in name order.
+///
+pub(crate) fn hidraw_nodes(sysfs: &Path) -> std::io::Result<Vec<HidrawNode>> {
+    let mut nodes = Vec::new();
+    for entry in std::fs::read_dir(sysfs.join("class/hidraw"))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(node) = HidrawNode::read(name, &entry.path()) {
+            nodes.push(node);
+        }
+    }
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    debug!("Found {} hidraw nodes", nodes.len());
+    Ok(nodes)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HidrawNode {
+    ///
+    /// Return `true` if this is the node of a Luxafor light.
+    ///
+    pub(crate) fn is_luxafor(&self) -> bool {
+        self.vendor_id == LUXAFOR_VENDOR_ID && self.product_id == LUXAFOR_PRODUCT_ID
+    }
+
+    ///
+    /// Return the manufacturer of the USB device, if any.
+    ///
+    #[cfg(all(target_os = "linux", feature = "usb-hidraw"))]
+    pub(crate) fn manufacturer(&self) -> Option<String> {
+        self.usb_attribute("manufacturer")
+    }
+
+    ///
+    /// Return the product name of the USB device, or else the HID name.
+    ///
+    pub(crate) fn product(&self) -> Option<String> {
+        self.usb_attribute("product")
+            .or_else(|| self.hid_name.clone())
+    }
+
+    ///
+    /// Return the serial number of the USB device, or else the HID unique identifier.
+    ///
+    #[cfg(all(target_os = "linux", feature = "usb-hidraw"))]
+    pub(crate) fn serial(&self) -> Option<String> {
+        self.usb_attribute("serial")
+            .or_else(|| self.hid_uniq.clone())
+    }
+
+    fn usb_attribute(&self, name: &str) -> Option<String> {
+        std::fs::read_to_string(self.usb_device.as_ref()?.join(name))
+            .ok()
+            .map(|value| value.trim().to_string())
+    }
+
+    // The uevent of the HID device has its IDs, as `HID_ID=0003:000004D8:0000F372`, and a name.
+    fn read(name: String, class_entry: &Path) -> Option<Self> {
+        let device = class_entry.join("device").canonicalize().ok()?;
+        let uevent = std::fs::read_to_string(device.join("uevent")).ok()?;
+        let value = |key: &str| {
+            uevent
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let hid_id = value("HID_ID")?;
+        let mut ids = hid_id.rsplitn(3, ':');
+        let product_id = u16::from_str_radix(ids.next()?, 16).ok()?;
+        let vendor_id = u16::from_str_radix(ids.next()?, 16).ok()?;
+        Some(Self {
+            name,
+            vendor_id,
+            product_id,
+            hid_name: value("HID_NAME"),
+            hid_uniq: value("HID_UNIQ"),
+            usb_device: device
+                .ancestors()
+                .find(|ancestor| ancestor.join("idVendor").is_file())
+                .map(Path::to_path_buf),
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::hidraw_nodes;
+    use std::fs;
+
+    #[test]
+    fn test_hid_ids() {
+        let root = std::env::temp_dir().join(format!("luxafor-test-hid-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (node, hid_id) in [
+            ("hidraw2", "0003:000004D8:0000F372"),
+            ("hidraw0", "0003:0000046D:0000C52B"),
+            ("hidraw1", "0003:000004D8:1F372"),
+            ("hidraw3", "garbage"),
+        ] {
+            let device = root.join("class/hidraw").join(node).join("device");
+            fs::create_dir_all(&device).unwrap();
+            fs::write(
+                device.join("uevent"),
+                format!("HID_ID={}\nHID_NAME={}\n", hid_id, node),
+            )
+            .unwrap();
+        }
+
+        let nodes = hidraw_nodes(&root).unwrap();
+        let names: Vec<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["hidraw0", "hidraw2"]);
+        assert!(!nodes[0].is_luxafor());
+        assert!(nodes[1].is_luxafor());
+        assert_eq!(nodes[1].product().as_deref(), Some("hidraw2"));
+        assert!(hidraw_nodes(&root.join("missing")).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+/*!
+A pure Rust replacement, for Linux, for the parts of the `hidapi` library used by `usb_hid`.
+
+Devices are found by the scan of `/sys/class/hidraw` shared with `doctor`, the manufacturer,
+product, and serial number are read from the USB device above the HID device in sysfs, and reports are written to, and read from, the `/dev/hidrawN` node directly. The types
+here have the same names and methods as their `hidapi` counterparts so that `usb_hid` can use
+either, this backend is used when the `usb-hidraw` feature is enabled.
+
+*/
+
+use crate::error::{Error, Result};
+use crate::hid::{hidraw_nodes, HidrawNode};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use std::ffi::{CStr, CString};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::AsFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The list of hidraw devices found when this was constructed.
+///
+#[derive(Debug)]
+pub(crate) struct HidApi {
+    devices: Vec<DeviceInfo>,
+}
+
+///
+/// A hidraw device found in sysfs.
+///
+#[derive(Clone, Debug)]
+pub(crate) struct DeviceInfo {
+    path: CString,
+    vendor_id: u16,
+    product_id: u16,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial: Option<String>,
+}
+
+///
+/// An open hidraw device node.
+///
+#[derive(Debug)]
+pub(crate) struct HidDevice {
+    file: File,
+    info: Option<DeviceInfo>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HidApi {
+    pub(crate) fn new() -> Result<Self> {
+        Self::with_roots(Path::new("/sys"), Path::new("/dev"))
+    }
+
+    pub(crate) fn with_roots(sysfs: &Path, dev: &Path) -> Result<Self> {
+        let devices = hidraw_nodes(sysfs)?
+            .into_iter()
+            .filter_map(|node| DeviceInfo::new(&node, &dev.join(&node.name)))
+            .collect();
+        Ok(Self { devices })
+    }
+
+    pub(crate) fn device_list(&self) -> impl Iterator<Item = &DeviceInfo> {
+        self.devices.iter()
+    }
+
+    pub(crate) fn open(&self, vendor_id: u16, product_id: u16) -> Result<HidDevice> {
+        self.open_first(|info| info.vendor_id == vendor_id && info.product_id == product_id)
+    }
+
+    pub(crate) fn open_serial(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        serial: &str,
+    ) -> Result<HidDevice> {
+        self.open_first(|info| {
+            info.vendor_id == vendor_id
+                && info.product_id == product_id
+                && info.serial.as_deref() == Some(serial)
+        })
+    }
+
+    pub(crate) fn open_path(&self, path: &CStr) -> Result<HidDevice> {
+        let info = self
+            .devices
+            .iter()
+            .find(|info| info.path.as_c_str() == path);
+        let path = Path::new(std::ffi::OsStr::from_bytes(path.to_bytes()));
+        debug!("Opening hidraw device {:?}", path);
+        Ok(HidDevice {
+            file: OpenOptions::new().read(true).write(true).open(path)?,
+            info: info.cloned(),
+        })
+    }
+
+    fn open_first(&self, predicate: impl Fn(&DeviceInfo) -> bool) -> Result<HidDevice> {
+        let info = self
+            .devices
+            .iter()
+            .find(|info| predicate(info))
+            .ok_or(Error::DeviceNotFound)?;
+        self.open_path(&info.path)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceInfo {
+    pub(crate) fn vendor_id(&self) -> u16 {
+        self.vendor_id
+    }
+
+    pub(crate) fn product_id(&self) -> u16 {
+        self.product_id
+    }
+
+    pub(crate) fn path(&self) -> &CStr {
+        &self.path
+    }
+
+    pub(crate) fn product_string(&self) -> Option<&str> {
+        self.product.as_deref()
+    }
+
+    pub(crate) fn serial_number(&self) -> Option<&str> {
+        self.serial.as_deref()
+    }
+
+    fn new(node: &HidrawNode, path: &Path) -> Option<Self> {
+        Some(Self {
+            path: CString::new(path.as_os_str().as_bytes()).ok()?,
+            vendor_id: node.vendor_id,
+            product_id: node.product_id,
+            manufacturer: node.manufacturer(),
+            product: node.product(),
+            serial: node.serial(),
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl HidDevice {
+    pub(crate) fn get_manufacturer_string(&self) -> Result<Option<String>> {
+        Ok(self
+            .info
+            .as_ref()
+            .and_then(|info| info.manufacturer.clone()))
+    }
+
+    pub(crate) fn get_product_string(&self) -> Result<Option<String>> {
+        Ok(self.info.as_ref().and_then(|info| info.product.clone()))
+    }
+
+    pub(crate) fn get_serial_number_string(&self) -> Result<Option<String>> {
+        Ok(self.info.as_ref().and_then(|info| info.serial.clone()))
+    }
+
+    pub(crate) fn write(&self, data: &[u8]) -> Result<usize> {
+        Ok((&self.file).write(data)?)
+    }
+
+    pub(crate) fn read_timeout(&self, buffer: &mut [u8], timeout: i32) -> std::io::Result<usize> {
+        let mut fds = [PollFd::new(self.file.as_fd(), PollFlags::POLLIN)];
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::NONE);
+        if poll(&mut fds, timeout)? == 0 {
+            return Ok(0);
+        }
+        (&self.file).read(buffer)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::HidApi;
+    use crate::error::Error;
+    use crate::DeviceModel;
+    use std::fs;
+    use std::path::Path;
+
+    fn add_hid(root: &Path, node: &str, usb: &str, hid_id: &str, strings: &[(&str, &str)]) {
+        let usb_device = root.join("sys/devices/pci0000:00/usb1").join(usb);
+        let hid_device = usb_device.join(format!("{}:1.0/{}.0001", usb, hid_id));
+        fs::create_dir_all(&hid_device).unwrap();
+        fs::write(usb_device.join("idVendor"), "").unwrap();
+        for (name, value) in strings {
+            fs::write(usb_device.join(name), format!("{}\n", value)).unwrap();
+        }
+        fs::write(
+            hid_device.join("uevent"),
+            format!("DRIVER=hid-generic\nHID_ID={}\nHID_NAME=name\n", hid_id),
+        )
+        .unwrap();
+        let class_entry = root.join("sys/class/hidraw").join(node);
+        fs::create_dir_all(&class_entry).unwrap();
+        std::os::unix::fs::symlink(&hid_device, class_entry.join("device")).unwrap();
+        fs::write(root.join("dev").join(node), b"").unwrap();
+    }
+
+    #[test]
+    fn test_fake_sysfs() {
+        let root = std::env::temp_dir().join(format!("luxafor-test-hidraw-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("dev")).unwrap();
+        add_hid(&root, "hidraw0", "1-2", "0003:0000046D:0000C52B", &[]);
+        add_hid(
+            &root,
+            "hidraw3",
+            "1-1",
+            "0003:000004D8:0000F372",
+            &[
+                ("manufacturer", "Microchip Technology Inc."),
+                ("product", "LUXAFOR FLAG"),
+                ("serial", "1234"),
+            ],
+        );
+
+        let api = HidApi::with_roots(&root.join("sys"), &root.join("dev")).unwrap();
+        let lights: Vec<_> = api
+            .device_list()
+            .filter(|info| info.vendor_id() == 0x04d8 && info.product_id() == 0xf372)
+            .collect();
+        assert_eq!(lights.len(), 1);
+        assert_eq!(
+            DeviceModel::from_product(lights[0].product_string().unwrap()),
+            DeviceModel::Flag
+        );
+        assert_eq!(lights[0].serial_number(), Some("1234"));
+        let path = root.join("dev/hidraw3");
+        assert_eq!(lights[0].path().to_str().unwrap(), path.to_str().unwrap());
+
+        let device = api.open_serial(0x04d8, 0xf372, "1234").unwrap();
+        assert_eq!(
+            device.get_manufacturer_string().unwrap().as_deref(),
+            Some("Microchip Technology Inc.")
+        );
+        assert_eq!(device.write(&[0, 1, 255, 255, 0, 0]).unwrap(), 6);
+        assert_eq!(fs::read(&path).unwrap(), vec![0, 1, 255, 255, 0, 0]);
+
+        let other = api.device_list().find(|info| info.vendor_id() == 0x046d);
+        assert_eq!(other.unwrap().product_string(), Some("name"));
+        assert!(matches!(
+            api.open_serial(0x04d8, 0xf372, "9999"),
+            Err(Error::DeviceNotFound)
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+/*!
+The HTTP scaffolding shared by the `server` and `receiver` modules.
+
+An [HttpServer] accepts `POST` requests only, each is routed by its path, without any query, and
+the request body is then applied. The result of applying a request is mapped to a response
+status in one place, so that both servers report errors the same way.
+
+| Result                                                   | Status  |
+|----------------------------------------------------------|---------|
+| not a `POST`                                             | `405`   |
+| no route for the path, `Error::UnsupportedCommand`       | `404`   |
+| a request, JSON, color, pattern, or device ID error      | `400`   |
+| any other error, such as a device failure                | `500`   |
+| applied, with a response body                            | `200`   |
+| applied, with nothing to report                          | `204`   |
+
+A failure reading, or responding to, a single request is logged and the server continues; only a
+failure of the underlying socket stops [HttpServer::serve].
+
+*/
+
+use crate::error::{Error, Result};
+use tiny_http::{Method, Request, Response, Server};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A minimal HTTP server, see the [module documentation](index.html).
+///
+pub(crate) struct HttpServer {
+    server: Server,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl HttpServer {
+    ///
+    /// Bind a new server to `address`, `name` describes the server in the log.
+    ///
+    pub(crate) fn bind(address: &str, name: &str, device_id: &str) -> Result<Self> {
+        let server = Server::http(address).map_err(|source| Error::Server { source })?;
+        info!(
+            "{} for device '{}' listening on {}",
+            name,
+            device_id,
+            server.server_addr()
+        );
+        Ok(Self { server })
+    }
+
+    ///
+    /// Return the base URL of the server.
+    ///
+    pub(crate) fn url(&self) -> String {
+        format!("http://{}", self.server.server_addr())
+    }
+
+    ///
+    /// Serve requests until the process is terminated, or the underlying socket fails. Each
+    /// request path is passed to `route`, and if it returns a route the request body is passed to
+    /// `apply` with it.
+    ///
+    pub(crate) fn serve<R>(
+        &self,
+        route: impl Fn(&str) -> Option<R>,
+        apply: impl Fn(R, &str) -> Result<Option<String>>,
+    ) -> Result<()> {
+        loop {
+            let request = self.server.recv()?;
+            if let Err(e) = handle(request, &route, &apply) {
+                warn!("Failed to handle request: {}", e);
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn handle<R>(
+    mut request: Request,
+    route: impl Fn(&str) -> Option<R>,
+    apply: impl Fn(R, &str) -> Result<Option<String>>,
+) -> Result<()> {
+    debug!("Received {} {}", request.method(), request.url());
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let response = if *request.method() != Method::Post {
+        Response::from_string("Method not allowed").with_status_code(405)
+    } else {
+        match route(&path) {
+            None => Response::from_string("Not found").with_status_code(404),
+            Some(route) => {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body)?;
+                match apply(route, &body) {
+                    Ok(None) => Response::from_string("").with_status_code(204),
+                    Ok(Some(body)) => Response::from_string(body).with_status_code(200),
+                    Err(e) => {
+                        let status_code = status_code(&e);
+                        Response::from_string(e.to_string()).with_status_code(status_code)
+                    }
+                }
+            }
+        }
+    };
+    request.respond(response)?;
+    Ok(())
+}
+
+fn status_code(error: &Error) -> u16 {
+    match error.root() {
+        Error::UnsupportedCommand => 404,
+        Error::InvalidColor
+        | Error::InvalidPattern
+        | Error::InvalidDeviceID
+        | Error::InvalidRequest
+        | Error::Json { .. } => 400,
+        _ => {
+            error!("Failed to apply request: {}", error);
+            500
+        }
+    }
+}
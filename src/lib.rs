@@ -43,6 +43,28 @@ fn set_do_not_disturb(device_id: &str) -> Result<()> {
 }
 ```
 
+The webhook API does not support fades or waves, or the speed of a strobe; wrapping any device in
+`emulated::Emulated` emulates these using timed sequences of solid colors, so that the same commands
+can be sent to USB and webhook lights. The `lux` command does this for webhook lights, waiting for
+an emulated sequence to finish; a sequence is cut short after a minute, and a strobe at the default
+speed uses the webhook API's own `blink`.
+
+Devices can also be opened by URI, `luxafor::open("usb:serial=1234")` or
+`luxafor::open("webhook:2a0f2c73b72")` return a boxed `Device`; other backends can add their own
+schemes with `registry::register_scheme`. Lights that can target each LED, such as `usb:` and
+`term:`, are also opened as a boxed `TargetedDevice` by `registry::open_targeted_uri`. The `lux`
+command opens each `--device` URI in this way.
+
+To share one light between threads, `shared::SharedDevice` wraps any device in an `Arc` and a
+mutex; its `apply_to` method targets an LED and applies a command while holding the lock, so that
+threads cannot interleave the target and the color. `Device` is also implemented for `&D`,
+`Box<D>`, and `Arc<D>`.
+
+Commands can also be built fluently with `Device::command`, which validates the command against the
+device's capabilities before anything is sent, for example
+`device.command().led(SpecificLED::AllFront).color(SolidColor::Red).strobe().repeat(Repeat::Forever).send()`.
+The `build` method returns the command as a value that can be stored, logged, and replayed.
+
 # CLI Examples
 
 The following shows the command line tool setting the color to red.
@@ -59,6 +81,22 @@ variable `LUX_DEVICE` to save repeating the device identifier on each call.
 ❯ lux blink green
 ```
 
+Speeds and fade durations are in units of 50 milliseconds, or may be given as a duration; repeat
+counts may be `forever`.
+
+```bash
+❯ lux strobe red --speed 500ms --repeat forever
+```
+
+Colors may also be given as six hex digits, as a color temperature in Kelvin from 1500K to 10000K,
+or as one of the white presets `warm` (2700K), `neutral` (4000K), and `daylight` (6500K), which are
+softer on the light than the pure `white`.
+
+```bash
+❯ lux solid warm
+❯ lux fade 3000K --fade-duration 2s
+```
+
 The following shows the command line tool turning the light off.
 
 ```bash
@@ -73,10 +111,162 @@ The following shows the how to set USB connected lights.
 ❯ lux -d usb solid red
 ```
 
+The following serves the Luxafor webhook API locally, applying any actions to the USB connected
+light; any webhook client can then be pointed at `http://127.0.0.1:5383/webhook/v1/actions`.
+
+```bash
+❯ lux -d usb serve --address 127.0.0.1:5383
+```
+
+The following runs a pomodoro session of four 25 minute work phases, in red, separated by 5 minute
+breaks, in green. The LEDs empty as each phase progresses and the light strobes for the last 30
+seconds of each phase; if interrupted the light is turned off, or set to the `--restore` command.
+A light, or group, that cannot target each LED stays in the phase color until the strobe.
+
+```bash
+❯ lux -d usb pomodoro --work 25m --break 5m --cycles 4
+❯ lux -d usb timer 10m --color blue --restore "solid green"
+```
+
+The following receives webhooks from CI and alerting systems; point GitHub (`workflow_run` or
+`check_suite` events) at `/github`, GitLab pipeline events at `/gitlab`, and Alertmanager at
+`/alertmanager`. Each notification is matched against the rules in the `[receive]` section of the
+configuration file, for example a failing pipeline strobes red, a critical alert shows the police
+pattern, and resolved alerts show green.
+
+```toml
+[receive]
+rules = [
+    "alertmanager firing:critical pattern police 255",
+    "* failure strobe red 10 255",
+    "* resolved green",
+]
+```
+
+```bash
+❯ lux -d usb receive --address 0.0.0.0:5384
+```
+
+The following runs a build, showing yellow on the light while it runs and then green, or red, depending
+on the exit status; after 10 seconds the light is turned off. The colors and delay can be set in the
+`[run]` section of the configuration file, or on the command line. The exit code of `lux run` is that
+of the command.
+
+```bash
+❯ lux -d usb run -- cargo build --release
+❯ lux -d usb run --running "wave yellow short 30 255" --delay 1m -- cargo test
+```
+
+The following runs a working-hours schedule, read from the `[schedule]` section of the configuration
+file, `~/.config/luxafor/config.toml` by default or as set with `--config`. While it runs, `lux override`
+replaces the scheduled command until the override expires, or is cleared.
+
+```toml
+[schedule]
+default = "off"
+rules = [
+    "mon-fri 09:00-17:30 green",
+    "mon-fri 12:00-13:00 yellow",
+]
+```
+
+```bash
+❯ lux -d usb schedule &
+❯ lux -d usb override "strobe red 10 255" --for 45m
+❯ lux -d usb override --clear
+```
+
+The following shows busy, in red, on the light while an event in an ICS calendar is in progress,
+yellow for 5 minutes before each event starts, and green otherwise. The calendar may be a file or
+an `http`, `https`, or `webcal` URL, it is reloaded every 15 minutes; all-day events, and events
+declined by the `--attendee`, are ignored. The commands can be set in the `[calendar]` section of
+the configuration file.
+
+```bash
+❯ lux -d usb calendar https://calendar.example.com/me/basic.ics --warning 10m --attendee me@example.com
+```
+
+The calendar monitor and `lux run` show the status presets `available`, `warning`, and `busy`, and
+`lux status busy` shows one directly. For those who cannot tell red from green, the `colorblind`
+palette shows the presets in blue and orange instead, and also as a steady, slowly pulsing, or
+blinking light so that each status can be told apart without color. The palette is a global
+setting, `--palette`, `LUX_PALETTE`, or `palette` in the configuration file, and
+`status::set_palette` for library clients.
+
+```bash
+❯ lux -d usb --palette colorblind calendar https://calendar.example.com/me/basic.ics
+❯ lux -d usb --palette colorblind status busy
+```
+
+The following sets two webhook lights, and the USB light, to red at the same time; every light is
+set even if another fails, and each failure is reported. A group of devices can also be named in the
+`[groups]` section of the configuration file, for example `team-room = ["usb", "2a0f2c73b72"]`, and
+used as `lux -d team-room solid red`.
+
+```bash
+❯ lux -d usb -d 2a0f2c73b72 -d 3b1e4d84c83 solid red
+```
+
+Without a light attached, `-d term` draws the six LEDs in the terminal as true-color blocks and
+animates fades, strobes, waves, and patterns, so that scripts can be developed and demonstrated
+without hardware; `terminal::TerminalDevice` provides the same preview to library clients.
+
+```bash
+❯ lux -d term strobe red --speed 500ms --repeat 5
+```
+
+With the `recorder` feature enabled, `recorder::Recorder` wraps any device and writes each command,
+with its timing, to a JSON Lines file while forwarding it. The following replays such a recording
+to the USB light, twice as fast as it was recorded; a recording may be replayed to any device,
+although one that targets LEDs can only be replayed to a light that targets LEDs.
+
+```bash
+❯ lux -d usb replay incident.jsonl --speed 2x
+```
+
+The following checks for the common reasons a light cannot be used: whether a Luxafor light is
+connected, whether its hidraw node can be opened, whether a udev rule is installed, and whether the
+webhook endpoint answers. It prints a suggested udev rule, which it can also write to a file.
+
+```bash
+❯ lux doctor
+❯ sudo lux doctor --write-udev-rule /etc/udev/rules.d/60-luxafor.rules
+```
+
+The following starts a broker that owns the USB connected light. While the broker is running any
+other `lux -d usb` command, and any `BrokerDevice` client, will send commands via the broker rather
+than opening the device directly.
+
+```bash
+❯ lux -d usb broker &
+❯ lux -d usb solid red
+```
+
+The Luxafor Mute sends input reports when its button is pressed; `USBDevice::next_event` and
+`USBDevice::events` decode these as press, long press, and release events. The following runs a
+shell command each time the button is pressed, with the event in `LUX_BUTTON_EVENT`; with
+`--long-press` holding the button runs that command instead.
+
+```bash
+❯ lux -d usb on-button --exec "pactl set-source-mute @DEFAULT_SOURCE@ toggle"
+```
+
 # Features
 
+* **broker**; provides a Unix socket broker so that multiple processes can share one light.
+* **calendar**; provides a monitor that shows busy, or available, from an ICS calendar.
 * **command-line**; provides the command line tool `lux`, it is not on by default for library clients.
+* **config**; provides the configuration file format used by the command line tool.
+* **dbus**; provides a D-Bus session service exporting a light for desktop integration.
+* **mqtt**; provides a bridge to control a light via MQTT, with Home Assistant discovery.
+* **process**; provides a wrapper that shows the status of a running process on a light.
+* **receiver**; provides a server that shows CI and alerting webhook notifications on a light.
+* **recorder**; provides a device wrapper that records commands, and replays recordings.
+* **schedule**; provides a working-hours schedule engine with expiring overrides.
+* **server**; provides a local server implementing the webhook API for any device.
+* **terminal**; provides a device that previews the light in the terminal, for working without hardware.
 * **usb**; provides access to USB connected devices.
+* **usb-hidraw**; provides access to USB connected devices on Linux via `/dev/hidraw`, without the `hidapi` C library.
 * **webhook** (default); provides access to USB, or Bluetooth, devices via webhooks.
 
 */
@@ -105,15 +295,20 @@ The following shows the how to set USB connected lights.
 #[macro_use]
 extern crate log;
 
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
+use std::num::NonZeroU8;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
 ///
-/// A color that the light can be set to.
+/// A color that the light can be set to. The text form is a preset name, six hex digits, a color
+/// temperature such as `3000K`, or one of the white presets `warm`, `neutral`, and `daylight`.
 ///
 #[derive(Clone, Debug)]
 pub enum SolidColor {
@@ -146,7 +341,7 @@ pub enum SolidColor {
 /// Waves produce a pattern that starts at the bottom of the light, fills the light and then
 /// fades out at the top.
 ///
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Wave {
     /// A short transition, completed before the next wave starts.
     Short,
@@ -161,14 +356,14 @@ pub enum Wave {
 ///
 /// A pattern the light can be set to show.
 ///
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Pattern {
     /// A preset pattern that cycles between red and blue.
     Police,
     /// A preset pattern that cycles between green,. yellow, and red.
     TrafficLights,
     /// Preset random patterns
-    Random(u8),
+    Random(RandomPattern),
     /// A preset pattern
     #[cfg(target_os = "windows")]
     Rainbow,
@@ -183,6 +378,57 @@ pub enum Pattern {
     Synthetic,
 }
 
+///
+/// One of the five preset random patterns, numbered `1..=5`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RandomPattern(u8);
+
+///
+/// The speed of a strobe or wave cycle, or the duration of a fade, in the firmware's units of
+/// approximately 50 milliseconds; the longest is `255` units, or 12.75 seconds. The text form is
+/// either a number of units, such as `10`, or a duration, such as `500ms` or `2s`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Speed(u8);
+
+///
+/// The number of times a strobe, wave, or pattern is repeated. The text form is either a number,
+/// `1..=255`, or `forever`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Repeat {
+    /// Repeat the given number of times.
+    Times(NonZeroU8),
+    /// Repeat until the light is set to something else.
+    Forever,
+}
+
+///
+/// The number of a single LED, `1..=6`; `1..=3` are on the front of the light, bottom to top,
+/// and `4..=6` are on the back.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LedIndex(u8);
+
+///
+/// The models of Luxafor light, each with its own LED layout and supported commands. The model of
+/// a USB connected light is detected from the product string of its HID descriptor.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceModel {
+    /// The Flag, with three LEDs on the front (tab) and three on the back.
+    Flag,
+    /// The Colorblind Flag, with the same layout as the Flag.
+    ColorblindFlag,
+    /// The Orb, with a single LED.
+    Orb,
+    /// The Bluetooth Pro dongle, relaying commands to a Flag-shaped light.
+    BluetoothPro,
+    /// The Mute button, with a single LED ring that supports solid colors, fades, and strobes.
+    Mute,
+}
+
 ///
 /// A trait implemented by different access methods to control a light.
 ///
@@ -205,7 +451,7 @@ pub trait Device {
     ///
     /// Set the light to fade from its current color to a new one.
     ///
-    fn set_fade_to_color(&self, color: SolidColor, fade_duration: u8) -> error::Result<()>;
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> error::Result<()>;
 
     ///
     /// Strobe the light, this will dim and brighten the same color.
@@ -213,8 +459,8 @@ pub trait Device {
     fn set_color_strobe(
         &self,
         color: SolidColor,
-        strobe_speed: u8,
-        repeat_count: u8,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
     ) -> error::Result<()>;
 
     ///
@@ -224,14 +470,49 @@ pub trait Device {
         &self,
         color: SolidColor,
         wave_pattern: Wave,
-        wave_speed: u8,
-        repeat_count: u8,
+        wave_speed: Speed,
+        repeat_count: Repeat,
     ) -> error::Result<()>;
 
     ///
     /// Set the light to repeat one of a pre-defined set of patterns.
     ///
-    fn set_pattern(&self, pattern: Pattern, repeat_count: u8) -> error::Result<()>;
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> error::Result<()>;
+
+    ///
+    /// Return the operations, patterns, and waves supported by this device. By default only
+    /// turning the light off and solid colors are assumed to be supported, devices that support
+    /// more should override this.
+    ///
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            commands: [CommandKind::TurnOff, CommandKind::Solid]
+                .into_iter()
+                .collect(),
+            led_targeting: false,
+            patterns: Vec::new(),
+            waves: Vec::new(),
+            repeat: HashSet::new(),
+            speed: HashSet::new(),
+        }
+    }
+
+    ///
+    /// Block until any sequence the device shows in the background, such as an emulated or drawn
+    /// animation, has completed or been cancelled. By default a device has none; the light itself
+    /// runs any strobe, wave, or pattern.
+    ///
+    fn wait(&self) {}
+
+    ///
+    /// Start building a command for this device, see the [builder](builder/index.html) module.
+    ///
+    fn command(&mut self) -> builder::CommandBuilder<'_, Self>
+    where
+        Self: Sized,
+    {
+        builder::CommandBuilder::new(self)
+    }
 }
 
 ///
@@ -245,8 +526,8 @@ pub enum SpecificLED {
     AllFront,
     /// Only the LEDs on the back of the light
     AllBack,
-    /// Only one specific LED (value: 1..6)
-    Number(u8),
+    /// Only one specific LED
+    Number(LedIndex),
 }
 
 ///
@@ -257,6 +538,109 @@ pub trait TargetedDevice: Device {
     fn set_specific_led(&mut self, led: SpecificLED) -> error::Result<()>;
 }
 
+///
+/// A single operation on a device, as a value; this corresponds to one method on the
+/// [Device](trait.Device.html) trait. Commands can be parsed from, and displayed as, a simple text
+/// form that mirrors the `lux` command line, for example `solid red`, `strobe 00ff00 10 255`, or
+/// `pattern traffic lights 5`. A color on its own, such as `green`, is parsed as a solid color.
+///
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Turn the light off, see `Device::turn_off`.
+    TurnOff,
+    /// See `Device::set_solid_color`.
+    Solid {
+        /// The color to set
+        color: SolidColor,
+    },
+    /// See `Device::set_fade_to_color`.
+    Fade {
+        /// The color to fade to
+        color: SolidColor,
+        /// The time taken to fade to the new color
+        fade_duration: Speed,
+    },
+    /// See `Device::set_color_strobe`.
+    Strobe {
+        /// The color to strobe
+        color: SolidColor,
+        /// The speed of each strobe cycle
+        strobe_speed: Speed,
+        /// The number of times to repeat the strobe
+        repeat_count: Repeat,
+    },
+    /// See `Device::set_color_wave`.
+    Wave {
+        /// The color of the wave
+        color: SolidColor,
+        /// The wave pattern to show
+        wave_pattern: Wave,
+        /// The speed of each wave cycle
+        wave_speed: Speed,
+        /// The number of times to repeat the wave
+        repeat_count: Repeat,
+    },
+    /// See `Device::set_pattern`.
+    Pattern {
+        /// The pattern to show
+        pattern: Pattern,
+        /// The number of times to repeat the pattern
+        repeat_count: Repeat,
+    },
+}
+
+///
+/// The kind of a [Command](enum.Command.html), without its arguments; this corresponds to one
+/// method on the [Device](trait.Device.html) trait.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommandKind {
+    /// See `Device::turn_off`.
+    TurnOff,
+    /// See `Device::set_solid_color`.
+    Solid,
+    /// See `Device::set_fade_to_color`.
+    Fade,
+    /// See `Device::set_color_strobe`.
+    Strobe,
+    /// See `Device::set_color_wave`.
+    Wave,
+    /// See `Device::set_pattern`.
+    Pattern,
+}
+
+///
+/// The operations a device supports, as returned by `Device::capabilities`. Use `check` to
+/// determine whether a command can be sent to the device before sending it.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The kinds of command supported.
+    pub commands: HashSet<CommandKind>,
+    /// Whether `TargetedDevice::set_specific_led` is honored.
+    pub led_targeting: bool,
+    /// The patterns supported by `Device::set_pattern`.
+    pub patterns: Vec<Pattern>,
+    /// The waves supported by `Device::set_color_wave`.
+    pub waves: Vec<Wave>,
+    /// The kinds of command for which the repeat count is honored.
+    pub repeat: HashSet<CommandKind>,
+    /// The kinds of command for which the speed, or fade duration, is honored.
+    pub speed: HashSet<CommandKind>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Open the device identified by `uri`, such as `usb:`, `usb:serial=1234`, or `webhook:2a0f2c73b72`;
+/// see the [registry](registry/index.html) module for the supported schemes.
+///
+pub fn open(uri: &str) -> error::Result<Box<dyn Device + Send>> {
+    registry::open_uri(&uri.parse()?)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -297,12 +681,17 @@ impl FromStr for SolidColor {
             "white" => Ok(SolidColor::White),
             "cyan" => Ok(SolidColor::Cyan),
             "magenta" => Ok(SolidColor::Magenta),
+            "warm" => SolidColor::from_kelvin(SolidColor::WARM_KELVIN),
+            "neutral" => SolidColor::from_kelvin(SolidColor::NEUTRAL_KELVIN),
+            "daylight" => SolidColor::from_kelvin(SolidColor::DAYLIGHT_KELVIN),
             _ => {
-                if s.len() == 6 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+                if let Some(kelvin) = s.strip_suffix('k') {
+                    SolidColor::from_kelvin(kelvin.parse()?)
+                } else if s.len() == 6 && s.chars().all(|c| c.is_ascii_hexdigit()) {
                     Ok(SolidColor::Custom {
-                        red: u8::from_str_radix(&s[0..1], 16)?,
-                        green: u8::from_str_radix(&s[2..3], 16)?,
-                        blue: u8::from_str_radix(&s[4..5], 16)?,
+                        red: u8::from_str_radix(&s[0..2], 16)?,
+                        green: u8::from_str_radix(&s[2..4], 16)?,
+                        blue: u8::from_str_radix(&s[4..6], 16)?,
                     })
                 } else {
                     Err(error::Error::InvalidColor)
@@ -312,6 +701,84 @@ impl FromStr for SolidColor {
     }
 }
 
+impl SolidColor {
+    ///
+    /// The lowest color temperature accepted by `from_kelvin`.
+    ///
+    pub const MIN_KELVIN: u16 = 1500;
+
+    ///
+    /// The highest color temperature accepted by `from_kelvin`.
+    ///
+    pub const MAX_KELVIN: u16 = 10000;
+
+    ///
+    /// The color temperature of the `warm` white preset, that of an incandescent bulb.
+    ///
+    pub const WARM_KELVIN: u16 = 2700;
+
+    ///
+    /// The color temperature of the `neutral` white preset.
+    ///
+    pub const NEUTRAL_KELVIN: u16 = 4000;
+
+    ///
+    /// The color temperature of the `daylight` white preset.
+    ///
+    pub const DAYLIGHT_KELVIN: u16 = 6500;
+
+    ///
+    /// Construct the custom color of a black body at the color temperature `kelvin`, which must be
+    /// in the range `MIN_KELVIN..=MAX_KELVIN`; this uses Tanner Helland's approximation of the
+    /// black body curve.
+    ///
+    pub fn from_kelvin(kelvin: u16) -> error::Result<Self> {
+        if !(Self::MIN_KELVIN..=Self::MAX_KELVIN).contains(&kelvin) {
+            return Err(error::Error::InvalidColor);
+        }
+        let temperature = f64::from(kelvin) / 100.0;
+        let channel = |value: f64| value.clamp(0.0, 255.0).round() as u8;
+        let (red, green, blue) = if temperature <= 66.0 {
+            (
+                255.0,
+                99.470_802_586_1 * temperature.ln() - 161.119_568_166_1,
+                if temperature <= 19.0 {
+                    0.0
+                } else {
+                    138.517_731_223_1 * (temperature - 10.0).ln() - 305.044_792_730_7
+                },
+            )
+        } else {
+            (
+                329.698_727_446 * (temperature - 60.0).powf(-0.133_204_759_2),
+                288.122_169_528_3 * (temperature - 60.0).powf(-0.075_514_849_2),
+                255.0,
+            )
+        };
+        Ok(SolidColor::Custom {
+            red: channel(red),
+            green: channel(green),
+            blue: channel(blue),
+        })
+    }
+
+    ///
+    /// Return the standard RGB values for this color, as a `(red, green, blue)` tuple.
+    ///
+    pub fn to_rgb(&self) -> (u8, u8, u8) {
+        match self {
+            SolidColor::Red => (255, 0, 0),
+            SolidColor::Green => (0, 255, 0),
+            SolidColor::Yellow => (255, 255, 0),
+            SolidColor::Blue => (0, 0, 255),
+            SolidColor::White => (255, 255, 255),
+            SolidColor::Cyan => (0, 255, 255),
+            SolidColor::Magenta => (255, 0, 255),
+            SolidColor::Custom { red, green, blue } => (*red, *green, *blue),
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
 impl Display for Wave {
@@ -344,6 +811,20 @@ impl FromStr for Wave {
     }
 }
 
+impl Wave {
+    ///
+    /// Return all the wave patterns.
+    ///
+    pub fn all() -> Vec<Self> {
+        vec![
+            Wave::Short,
+            Wave::Long,
+            Wave::OverlappingShort,
+            Wave::OverlappingLong,
+        ]
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
 impl Display for Pattern {
@@ -376,11 +857,7 @@ impl FromStr for Pattern {
         match s.as_str() {
             "police" => Ok(Pattern::Police),
             "traffic lights" => Ok(Pattern::TrafficLights),
-            "random 1" => Ok(Pattern::Random(1)),
-            "random 2" => Ok(Pattern::Random(2)),
-            "random 3" => Ok(Pattern::Random(3)),
-            "random 4" => Ok(Pattern::Random(4)),
-            "random 5" => Ok(Pattern::Random(5)),
+            _ if s.starts_with("random ") => Ok(Pattern::Random(s["random ".len()..].parse()?)),
             #[cfg(target_os = "windows")]
             "rainbow" => Ok(Pattern::Rainbow),
             #[cfg(target_os = "windows")]
@@ -394,106 +871,850 @@ impl FromStr for Pattern {
     }
 }
 
+impl Pattern {
+    ///
+    /// Return all the patterns supported on the current platform.
+    ///
+    pub fn all() -> Vec<Self> {
+        vec![
+            Pattern::Police,
+            Pattern::TrafficLights,
+            Pattern::Random(RandomPattern(1)),
+            Pattern::Random(RandomPattern(2)),
+            Pattern::Random(RandomPattern(3)),
+            Pattern::Random(RandomPattern(4)),
+            Pattern::Random(RandomPattern(5)),
+            #[cfg(target_os = "windows")]
+            Pattern::Rainbow,
+            #[cfg(target_os = "windows")]
+            Pattern::Sea,
+            #[cfg(target_os = "windows")]
+            Pattern::WhiteWave,
+            #[cfg(target_os = "windows")]
+            Pattern::Synthetic,
+        ]
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
-impl Display for SpecificLED {
+impl Display for RandomPattern {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                SpecificLED::All => "all".to_string(),
-                SpecificLED::AllFront => "front".to_string(),
-                SpecificLED::AllBack => "back".to_string(),
-                SpecificLED::Number(n) => n.to_string(),
-            }
-        )
+        write!(f, "{}", self.0)
     }
 }
 
-impl FromStr for SpecificLED {
+impl FromStr for RandomPattern {
     type Err = error::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.to_lowercase();
-        match s.as_str() {
-            "all" => Ok(SpecificLED::All),
-            "front" => Ok(SpecificLED::AllFront),
-            "back" => Ok(SpecificLED::AllBack),
-            "1" => Ok(SpecificLED::Number(1)),
-            "2" => Ok(SpecificLED::Number(2)),
-            "3" => Ok(SpecificLED::Number(3)),
-            "4" => Ok(SpecificLED::Number(4)),
-            "5" => Ok(SpecificLED::Number(5)),
-            "6" => Ok(SpecificLED::Number(6)),
-            _ => Err(error::Error::InvalidLED),
+        Self::new(s.parse().map_err(|_| error::Error::InvalidPattern)?)
+    }
+}
+
+impl RandomPattern {
+    ///
+    /// Construct the random pattern `number`, which must be in `1..=5`.
+    ///
+    pub fn new(number: u8) -> error::Result<Self> {
+        if (1..=5).contains(&number) {
+            Ok(Self(number))
+        } else {
+            Err(error::Error::InvalidPattern)
         }
     }
+
+    ///
+    /// Return the number of this pattern.
+    ///
+    pub fn number(&self) -> u8 {
+        self.0
+    }
 }
 
-// ------------------------------------------------------------------------------------------------
-// Modules
 // ------------------------------------------------------------------------------------------------
 
-///
-/// Provides the crate's Error and Result types as well as helper
-/// functions.
-///
-pub mod error {
-    use std::fmt::{Debug, Display};
+impl Display for Speed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
-    // ------------------------------------------------------------------------------------------------
-    // Public Types
-    // ------------------------------------------------------------------------------------------------
+impl FromStr for Speed {
+    type Err = error::Error;
 
-    ///
-    /// The Error type for this crate.
-    ///
-    #[derive(Debug)]
-    pub enum Error {
-        /// The color value supplied was not recognized
-        InvalidColor,
-        /// The pattern value supplied was not recognized
-        InvalidPattern,
-        /// The LED number is either invalid or not supported by the connected device
-        InvalidLED,
-        /// The provided device ID was incorrectly formatted
-        InvalidDeviceID,
-        /// No device was discovered, or the ID did not resolve to a device
-        DeviceNotFound,
-        /// The server indicated an invalid request
-        InvalidRequest,
-        /// An unexpected HTTP error was returned
-        UnexpectedError(u16),
-        /// The command is not supported by the current device, or connection to the device
-        UnsupportedCommand,
-        /// An error was signaled by the standard library I/O functions.
-        IoError {
-            /// The source error being wrapped.
-            source: std::io::Error,
-        },
-        /// An error was signaled by the standard library parsing functions.
-        CustomFmt {
-            /// The source error being wrapped.
-            source: std::num::ParseIntError,
-        },
-        /// An error was signaled by the `reqwest` library.
-        #[cfg(feature = "webhook")]
-        Request {
-            /// The source error being wrapped.
-            source: reqwest::Error,
-        },
-        /// An error was signaled by the standard library fmt functions.
-        Fmt {
-            /// The source error being wrapped.
-            source: std::fmt::Error,
-        },
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let number = |n: &str| n.parse::<u64>().map_err(|_| error::Error::InvalidSpeed);
+        if let Some(millis) = s.strip_suffix("ms") {
+            Self::from_duration(Duration::from_millis(number(millis)?))
+        } else if let Some(seconds) = s.strip_suffix('s') {
+            Self::from_duration(Duration::from_secs(number(seconds)?))
+        } else {
+            Ok(Self(s.parse().map_err(|_| error::Error::InvalidSpeed)?))
+        }
+    }
+}
+
+impl From<u8> for Speed {
+    fn from(units: u8) -> Self {
+        Self(units)
     }
+}
 
+impl Speed {
     ///
-    /// A Result type that specifically uses this crate's Error.
+    /// The duration of one firmware unit.
     ///
-    pub type Result<T> = std::result::Result<T, Error>;
+    pub const UNIT: Duration = Duration::from_millis(50);
+
+    ///
+    /// The default strobe speed, and the speed assumed for a device that ignores the speed of a
+    /// strobe, such as a webhook connected light.
+    ///
+    pub const DEFAULT_STROBE: Speed = Speed(10);
+
+    ///
+    /// Construct a speed of `units` firmware units.
+    ///
+    pub const fn new(units: u8) -> Self {
+        Self(units)
+    }
+
+    ///
+    /// Construct the speed closest to `duration`; this fails if the duration is longer than the
+    /// longest speed supported.
+    ///
+    pub fn from_duration(duration: Duration) -> error::Result<Self> {
+        let unit = Self::UNIT.as_millis();
+        let units = (duration.as_millis() + unit / 2) / unit;
+        u8::try_from(units)
+            .map(Self)
+            .map_err(|_| error::Error::InvalidSpeed)
+    }
+
+    ///
+    /// Return the number of firmware units.
+    ///
+    pub fn units(&self) -> u8 {
+        self.0
+    }
+
+    ///
+    /// Return the approximate duration of this speed.
+    ///
+    pub fn as_duration(&self) -> Duration {
+        Self::UNIT * u32::from(self.0)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Repeat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repeat::Times(n) => write!(f, "{}", n),
+            Repeat::Forever => write!(f, "forever"),
+        }
+    }
+}
+
+impl FromStr for Repeat {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("forever") {
+            Ok(Repeat::Forever)
+        } else {
+            s.parse::<NonZeroU8>()
+                .map(Repeat::Times)
+                .map_err(|_| error::Error::InvalidRepeat)
+        }
+    }
+}
+
+impl Repeat {
+    ///
+    /// Construct a repeat of `count` times, which must not be zero.
+    ///
+    pub fn times(count: u8) -> error::Result<Self> {
+        NonZeroU8::new(count)
+            .map(Repeat::Times)
+            .ok_or(error::Error::InvalidRepeat)
+    }
+
+    ///
+    /// Construct a repeat from the firmware's value, where zero repeats forever.
+    ///
+    pub fn from_firmware(value: u8) -> Self {
+        NonZeroU8::new(value).map_or(Repeat::Forever, Repeat::Times)
+    }
+
+    ///
+    /// Return the firmware's value for this repeat, where zero repeats forever.
+    ///
+    pub fn to_firmware(&self) -> u8 {
+        match self {
+            Repeat::Times(n) => n.get(),
+            Repeat::Forever => 0,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for LedIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for LedIndex {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s.parse().map_err(|_| error::Error::InvalidLED)?)
+    }
+}
+
+impl LedIndex {
+    ///
+    /// The number of individually addressable LEDs.
+    ///
+    pub const COUNT: u8 = 6;
+
+    ///
+    /// Construct the index of LED `number`, which must be in `1..=6`.
+    ///
+    pub fn new(number: u8) -> error::Result<Self> {
+        if (1..=Self::COUNT).contains(&number) {
+            Ok(Self(number))
+        } else {
+            Err(error::Error::InvalidLED)
+        }
+    }
+
+    ///
+    /// Return the number of this LED.
+    ///
+    pub fn number(&self) -> u8 {
+        self.0
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for DeviceModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DeviceModel::Flag => "flag",
+                DeviceModel::ColorblindFlag => "colorblind flag",
+                DeviceModel::Orb => "orb",
+                DeviceModel::BluetoothPro => "bluetooth pro",
+                DeviceModel::Mute => "mute",
+            }
+        )
+    }
+}
+
+impl FromStr for DeviceModel {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Hyphens are accepted in place of spaces, as in `usb:model=colorblind-flag`.
+        let s = s.trim().to_lowercase().replace('-', " ");
+        DeviceModel::all()
+            .into_iter()
+            .find(|model| model.to_string() == s)
+            .ok_or(error::Error::InvalidDeviceModel)
+    }
+}
+
+impl DeviceModel {
+    ///
+    /// Return all the known models.
+    ///
+    pub fn all() -> Vec<Self> {
+        vec![
+            DeviceModel::Flag,
+            DeviceModel::ColorblindFlag,
+            DeviceModel::Orb,
+            DeviceModel::BluetoothPro,
+            DeviceModel::Mute,
+        ]
+    }
+
+    ///
+    /// Return the model described by the product string of a HID descriptor, such as
+    /// `LUXAFOR FLAG` or `LUXAFOR ORB`; an unrecognized product is assumed to be a Flag.
+    ///
+    pub fn from_product(product: &str) -> Self {
+        let product = product.to_lowercase();
+        let words: Vec<&str> = product.split_whitespace().collect();
+        let has = |names: &[&str]| words.iter().any(|word| names.contains(word));
+        if has(&["orb"]) {
+            DeviceModel::Orb
+        } else if has(&["mute"]) {
+            DeviceModel::Mute
+        } else if has(&["bt", "bluetooth"]) {
+            DeviceModel::BluetoothPro
+        } else if has(&["cb", "colorblind"]) {
+            DeviceModel::ColorblindFlag
+        } else {
+            if !has(&["flag"]) {
+                warn!("Unrecognized product '{}', assuming a Flag", product);
+            }
+            DeviceModel::Flag
+        }
+    }
+
+    ///
+    /// Return the number of individually addressable LEDs.
+    ///
+    pub fn led_count(&self) -> u8 {
+        match self {
+            DeviceModel::Orb | DeviceModel::Mute => 1,
+            _ => LedIndex::COUNT,
+        }
+    }
+
+    ///
+    /// Return `true` if the LEDs are split between the front and back of the light.
+    ///
+    pub fn has_front_and_back(&self) -> bool {
+        self.led_count() == LedIndex::COUNT
+    }
+
+    ///
+    /// Return an error if `led` cannot be targeted on this model.
+    ///
+    pub fn check_led(&self, led: &SpecificLED) -> error::Result<()> {
+        let supported = match led {
+            SpecificLED::All => true,
+            SpecificLED::AllFront | SpecificLED::AllBack => self.has_front_and_back(),
+            SpecificLED::Number(n) => n.number() <= self.led_count(),
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(error::Error::InvalidLED)
+        }
+    }
+
+    ///
+    /// Return the capabilities of this model.
+    ///
+    pub fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities {
+            led_targeting: self.led_count() > 1,
+            ..Capabilities::all()
+        };
+        if let DeviceModel::Mute = self {
+            capabilities
+                .commands
+                .retain(|kind| !matches!(kind, CommandKind::Wave | CommandKind::Pattern));
+            capabilities.patterns.clear();
+            capabilities.waves.clear();
+            capabilities
+                .repeat
+                .retain(|kind| *kind == CommandKind::Strobe);
+        }
+        capabilities
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for SpecificLED {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SpecificLED::All => "all".to_string(),
+                SpecificLED::AllFront => "front".to_string(),
+                SpecificLED::AllBack => "back".to_string(),
+                SpecificLED::Number(n) => n.to_string(),
+            }
+        )
+    }
+}
+
+impl FromStr for SpecificLED {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        match s.as_str() {
+            "all" => Ok(SpecificLED::All),
+            "front" => Ok(SpecificLED::AllFront),
+            "back" => Ok(SpecificLED::AllBack),
+            _ => Ok(SpecificLED::Number(s.parse()?)),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::TurnOff => write!(f, "off"),
+            Command::Solid { color } => write!(f, "solid {}", color),
+            Command::Fade {
+                color,
+                fade_duration,
+            } => write!(f, "fade {} {}", color, fade_duration),
+            Command::Strobe {
+                color,
+                strobe_speed,
+                repeat_count,
+            } => write!(f, "strobe {} {} {}", color, strobe_speed, repeat_count),
+            Command::Wave {
+                color,
+                wave_pattern,
+                wave_speed,
+                repeat_count,
+            } => write!(
+                f,
+                "wave {} {} {} {}",
+                color, wave_pattern, wave_speed, repeat_count
+            ),
+            Command::Pattern {
+                pattern,
+                repeat_count,
+            } => write!(f, "pattern {} {}", pattern, repeat_count),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        // Wave and pattern names may contain spaces, so they are taken as whatever lies between
+        // the fixed leading and trailing arguments.
+        match words.as_slice() {
+            ["off"] => Ok(Command::TurnOff),
+            [color] => Ok(Command::Solid {
+                color: color.parse().map_err(|_| error::Error::InvalidCommand)?,
+            }),
+            ["solid", color] => Ok(Command::Solid {
+                color: color.parse()?,
+            }),
+            ["fade", color, fade_duration] => Ok(Command::Fade {
+                color: color.parse()?,
+                fade_duration: fade_duration.parse()?,
+            }),
+            ["strobe", color, strobe_speed, repeat_count] => Ok(Command::Strobe {
+                color: color.parse()?,
+                strobe_speed: strobe_speed.parse()?,
+                repeat_count: repeat_count.parse()?,
+            }),
+            ["wave", color, wave_pattern @ .., wave_speed, repeat_count]
+                if !wave_pattern.is_empty() =>
+            {
+                Ok(Command::Wave {
+                    color: color.parse()?,
+                    wave_pattern: wave_pattern.join(" ").parse()?,
+                    wave_speed: wave_speed.parse()?,
+                    repeat_count: repeat_count.parse()?,
+                })
+            }
+            ["pattern", pattern @ .., repeat_count] if !pattern.is_empty() => {
+                Ok(Command::Pattern {
+                    pattern: pattern.join(" ").parse()?,
+                    repeat_count: repeat_count.parse()?,
+                })
+            }
+            _ => Err(error::Error::InvalidCommand),
+        }
+    }
+}
+
+impl Command {
+    ///
+    /// Return the kind of this command.
+    ///
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            Command::TurnOff => CommandKind::TurnOff,
+            Command::Solid { .. } => CommandKind::Solid,
+            Command::Fade { .. } => CommandKind::Fade,
+            Command::Strobe { .. } => CommandKind::Strobe,
+            Command::Wave { .. } => CommandKind::Wave,
+            Command::Pattern { .. } => CommandKind::Pattern,
+        }
+    }
+
+    ///
+    /// Apply this command to the provided device, calling the corresponding `Device` method.
+    ///
+    pub fn apply<D: Device + ?Sized>(self, device: &D) -> error::Result<()> {
+        match self {
+            Command::TurnOff => device.turn_off(),
+            Command::Solid { color } => device.set_solid_color(color),
+            Command::Fade {
+                color,
+                fade_duration,
+            } => device.set_fade_to_color(color, fade_duration),
+            Command::Strobe {
+                color,
+                strobe_speed,
+                repeat_count,
+            } => device.set_color_strobe(color, strobe_speed, repeat_count),
+            Command::Wave {
+                color,
+                wave_pattern,
+                wave_speed,
+                repeat_count,
+            } => device.set_color_wave(color, wave_pattern, wave_speed, repeat_count),
+            Command::Pattern {
+                pattern,
+                repeat_count,
+            } => device.set_pattern(pattern, repeat_count),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for CommandKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                CommandKind::TurnOff => "off",
+                CommandKind::Solid => "solid",
+                CommandKind::Fade => "fade",
+                CommandKind::Strobe => "strobe",
+                CommandKind::Wave => "wave",
+                CommandKind::Pattern => "pattern",
+            }
+        )
+    }
+}
+
+impl CommandKind {
+    ///
+    /// Return all the kinds of command.
+    ///
+    pub fn all() -> Vec<Self> {
+        vec![
+            CommandKind::TurnOff,
+            CommandKind::Solid,
+            CommandKind::Fade,
+            CommandKind::Strobe,
+            CommandKind::Wave,
+            CommandKind::Pattern,
+        ]
+    }
+
+    ///
+    /// Return `true` if commands of this kind have a repeat count.
+    ///
+    pub fn has_repeat(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::Strobe | CommandKind::Wave | CommandKind::Pattern
+        )
+    }
+
+    ///
+    /// Return `true` if commands of this kind have a speed, or fade duration.
+    ///
+    pub fn has_speed(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::Fade | CommandKind::Strobe | CommandKind::Wave
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Capabilities {
+    ///
+    /// Return the capabilities of a device that supports every operation, pattern, and wave, as
+    /// well as LED targeting.
+    ///
+    pub fn all() -> Self {
+        let commands: HashSet<CommandKind> = CommandKind::all().into_iter().collect();
+        Self {
+            led_targeting: true,
+            patterns: Pattern::all(),
+            waves: Wave::all(),
+            repeat: commands
+                .iter()
+                .filter(|k| k.has_repeat())
+                .copied()
+                .collect(),
+            speed: commands.iter().filter(|k| k.has_speed()).copied().collect(),
+            commands,
+        }
+    }
+
+    ///
+    /// Return `true` if the device supports `command`, including its pattern or wave.
+    ///
+    pub fn supports(&self, command: &Command) -> bool {
+        self.commands.contains(&command.kind())
+            && match command {
+                Command::Wave { wave_pattern, .. } => self.waves.contains(wave_pattern),
+                Command::Pattern { pattern, .. } => self.patterns.contains(pattern),
+                _ => true,
+            }
+    }
+
+    ///
+    /// Return an error if the device does not support `command`. A warning is logged if the
+    /// device supports the command but will ignore its repeat count, or speed.
+    ///
+    pub fn check(&self, command: &Command) -> error::Result<()> {
+        if !self.supports(command) {
+            return Err(error::Error::UnsupportedCommand);
+        }
+        let kind = command.kind();
+        if kind.has_repeat() && !self.repeat.contains(&kind) {
+            warn!(
+                "The repeat count of '{}' will be ignored by the device",
+                command
+            );
+        }
+        if kind.has_speed() && !self.speed.contains(&kind) {
+            warn!("The speed of '{}' will be ignored by the device", command);
+        }
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+macro_rules! forward_device {
+    ($($pointer:ty),*) => {
+        $(
+            impl<D: Device + ?Sized> Device for $pointer {
+                fn id(&self) -> String {
+                    (**self).id()
+                }
+
+                fn turn_off(&self) -> error::Result<()> {
+                    (**self).turn_off()
+                }
+
+                fn set_solid_color(&self, color: SolidColor) -> error::Result<()> {
+                    (**self).set_solid_color(color)
+                }
+
+                fn set_fade_to_color(
+                    &self,
+                    color: SolidColor,
+                    fade_duration: Speed,
+                ) -> error::Result<()> {
+                    (**self).set_fade_to_color(color, fade_duration)
+                }
+
+                fn set_color_strobe(
+                    &self,
+                    color: SolidColor,
+                    strobe_speed: Speed,
+                    repeat_count: Repeat,
+                ) -> error::Result<()> {
+                    (**self).set_color_strobe(color, strobe_speed, repeat_count)
+                }
+
+                fn set_color_wave(
+                    &self,
+                    color: SolidColor,
+                    wave_pattern: Wave,
+                    wave_speed: Speed,
+                    repeat_count: Repeat,
+                ) -> error::Result<()> {
+                    (**self).set_color_wave(color, wave_pattern, wave_speed, repeat_count)
+                }
+
+                fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> error::Result<()> {
+                    (**self).set_pattern(pattern, repeat_count)
+                }
+
+                fn capabilities(&self) -> Capabilities {
+                    (**self).capabilities()
+                }
+
+                fn wait(&self) {
+                    (**self).wait()
+                }
+            }
+        )*
+    };
+}
+
+forward_device!(&D, Box<D>, Arc<D>);
+
+impl<D: TargetedDevice + ?Sized> TargetedDevice for Box<D> {
+    fn set_specific_led(&mut self, led: SpecificLED) -> error::Result<()> {
+        (**self).set_specific_led(led)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Modules
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Provides the crate's Error and Result types as well as helper
+/// functions.
+///
+pub mod error {
+    use std::fmt::{Debug, Display};
+
+    // ------------------------------------------------------------------------------------------------
+    // Public Types
+    // ------------------------------------------------------------------------------------------------
+
+    ///
+    /// The Error type for this crate.
+    ///
+    #[derive(Debug)]
+    pub enum Error {
+        /// The color value supplied was not recognized
+        InvalidColor,
+        /// The pattern value supplied was not recognized
+        InvalidPattern,
+        /// The command supplied was not recognized
+        InvalidCommand,
+        /// The speed, or duration, supplied was not recognized or is too long
+        InvalidSpeed,
+        /// The repeat count supplied was not recognized, it must be `1..=255` or `forever`
+        InvalidRepeat,
+        /// The schedule rule, or override, supplied was not recognized
+        InvalidSchedule,
+        /// The event rule supplied was not recognized
+        InvalidEventRule,
+        /// The calendar, or calendar source, supplied was not recognized
+        InvalidCalendar,
+        /// The status preset, or palette, supplied was not recognized
+        InvalidStatus,
+        /// The LED number is either invalid or not supported by the connected device
+        InvalidLED,
+        /// The provided device ID was incorrectly formatted
+        InvalidDeviceID,
+        /// The provided device URI was incorrectly formatted
+        InvalidDeviceURI,
+        /// The device model supplied was not recognized
+        InvalidDeviceModel,
+        /// A line of a recording was not a recorded command
+        InvalidRecording {
+            /// The line number, from 1, in the recording.
+            line: usize,
+        },
+        /// No device backend is registered for the scheme of a device URI
+        UnknownScheme {
+            /// The scheme of the URI.
+            scheme: String,
+        },
+        /// No device was discovered, or the ID did not resolve to a device
+        DeviceNotFound,
+        /// The server indicated an invalid request
+        InvalidRequest,
+        /// An HTTP request returned an unsuccessful status
+        Http {
+            /// The HTTP status code returned.
+            status: u16,
+            /// The body of the response, which may describe the error.
+            body: String,
+        },
+        /// The command is not supported by the current device, or connection to the device
+        UnsupportedCommand,
+        /// An operation on a device failed; this wraps the error with the device and command.
+        Device {
+            /// The identifier of the device.
+            device_id: String,
+            /// The command being applied, in the text form of a `Command`, if any.
+            command: Option<String>,
+            /// The error returned by the device.
+            source: Box<Error>,
+        },
+        /// One or more members of a device group failed, each failure has the member's context.
+        Group {
+            /// The number of members in the group.
+            members: usize,
+            /// The errors returned by the members that failed.
+            failures: Vec<Error>,
+        },
+        /// An error was signaled by the standard library I/O functions.
+        IoError {
+            /// The source error being wrapped.
+            source: std::io::Error,
+        },
+        /// An error was signaled by the standard library parsing functions.
+        CustomFmt {
+            /// The source error being wrapped.
+            source: std::num::ParseIntError,
+        },
+        /// An error was signaled by the `hidapi` library.
+        #[cfg(feature = "usb")]
+        Hid {
+            /// The source error being wrapped.
+            source: hidapi::HidError,
+        },
+        /// An error was signaled by the `reqwest` library.
+        #[cfg(any(feature = "calendar", feature = "webhook"))]
+        Request {
+            /// The source error being wrapped.
+            source: reqwest::Error,
+        },
+        /// An error was signaled by the standard library fmt functions.
+        Fmt {
+            /// The source error being wrapped.
+            source: std::fmt::Error,
+        },
+        /// An error was signaled by the `zbus` library.
+        #[cfg(feature = "dbus")]
+        Dbus {
+            /// The source error being wrapped.
+            source: zbus::Error,
+        },
+        /// An error was signaled by the `rumqttc` library.
+        #[cfg(feature = "mqtt")]
+        Mqtt {
+            /// The source error being wrapped.
+            source: rumqttc::ClientError,
+        },
+        /// An error was signaled by the `serde_json` library.
+        #[cfg(any(
+            feature = "mqtt",
+            feature = "receiver",
+            feature = "recorder",
+            feature = "server"
+        ))]
+        Json {
+            /// The source error being wrapped.
+            source: serde_json::Error,
+        },
+        /// An error was signaled by the `toml` library.
+        #[cfg(feature = "config")]
+        Toml {
+            /// The source error being wrapped.
+            source: toml::de::Error,
+        },
+        /// An error was signaled by the `tiny_http` library.
+        #[cfg(any(feature = "receiver", feature = "server"))]
+        Server {
+            /// The source error being wrapped.
+            source: Box<dyn std::error::Error + Send + Sync + 'static>,
+        },
+    }
+
+    ///
+    /// A Result type that specifically uses this crate's Error.
+    ///
+    pub type Result<T> = std::result::Result<T, Error>;
 
     // ------------------------------------------------------------------------------------------------
     // Implementations
@@ -507,17 +1728,53 @@ pub mod error {
                 match self {
                     Self::InvalidColor => "The color value supplied was not recognized".to_string(),
                     Self::InvalidPattern => "The pattern value supplied was not recognized".to_string(),
+                    Self::InvalidCommand => "The command supplied was not recognized".to_string(),
+                    Self::InvalidSpeed => "The speed, or duration, supplied was not recognized or is too long".to_string(),
+                    Self::InvalidRepeat => "The repeat count supplied was not recognized, it must be 1..=255 or forever".to_string(),
+                    Self::InvalidSchedule => "The schedule rule, or override, supplied was not recognized".to_string(),
+                    Self::InvalidEventRule => "The event rule supplied was not recognized".to_string(),
+                    Self::InvalidCalendar => "The calendar, or calendar source, supplied was not recognized".to_string(),
+                    Self::InvalidStatus => "The status preset, or palette, supplied was not recognized".to_string(),
                     Self::InvalidLED => "The LED number is either invalid or not supported by the connected device".to_string(),
                     Self::InvalidDeviceID => "The provided device ID was incorrectly formatted".to_string(),
-                    Self::DeviceNotFound => "No device was discovered, or // TODO: he ID did not resolve to a device".to_string(),
+                    Self::InvalidDeviceURI => "The provided device URI was incorrectly formatted".to_string(),
+                    Self::InvalidDeviceModel => "The device model supplied was not recognized".to_string(),
+                    Self::InvalidRecording { line } => format!("Line {line} of the recording was not a recorded command"),
+                    Self::UnknownScheme { scheme } => format!("No device backend is registered for the URI scheme '{scheme}'"),
+                    Self::DeviceNotFound => "No device was discovered, or the ID did not resolve to a device".to_string(),
                     Self::InvalidRequest => "The server indicated an invalid request".to_string(),
-                    Self::UnexpectedError(status_code)=>format!("An unexpected HTTP error was returned: {status_code}"),
+                    Self::Http { status, body } if body.trim().is_empty() => format!("The server returned HTTP status {status}"),
+                    Self::Http { status, body } => format!("The server returned HTTP status {status}: {}", body.trim()),
                     Self::UnsupportedCommand => "The command is not supported by the current device, or connection to the device".to_string(),
+                    Self::Device { device_id, command: Some(command), source } => format!("Could not apply '{command}' to device '{device_id}'; source: {source}"),
+                    Self::Device { device_id, command: None, source } => format!("Device '{device_id}' failed; source: {source}"),
+                    Self::Group { members, failures } => format!(
+                        "{} of {members} devices in the group failed; sources: {}",
+                        failures.len(),
+                        failures.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+                    ),
                     Self::IoError{source} => format!("An I/O error occurred; source: {source}"),
                     Self::CustomFmt { source } => format!("A parsing error occurred; source: {source}"),
-                    #[cfg(feature = "webhook")]
+                    #[cfg(feature = "usb")]
+                    Self::Hid { source } => format!("A USB HID error occurred; source: {source}"),
+                    #[cfg(any(feature = "calendar", feature = "webhook"))]
                     Self::Request { source } => format!("An HTTP request error occurred; source: {source}"),
                     Self::Fmt { source } => format!("An formatting error occurred; source: {source}"),
+                    #[cfg(feature = "dbus")]
+                    Self::Dbus { source } => format!("A D-Bus error occurred; source: {source}"),
+                    #[cfg(feature = "mqtt")]
+                    Self::Mqtt { source } => format!("An MQTT client error occurred; source: {source}"),
+                    #[cfg(any(
+                        feature = "mqtt",
+                        feature = "receiver",
+                        feature = "recorder",
+                        feature = "server"
+                    ))]
+                    Self::Json { source } => format!("A JSON error occurred; source: {source}"),
+                    #[cfg(feature = "config")]
+                    Self::Toml { source } => format!("A configuration file error occurred; source: {source}"),
+                    #[cfg(any(feature = "receiver", feature = "server"))]
+                    Self::Server { source } => format!("An HTTP server error occurred; source: {source}"),
                 }
             )
         }
@@ -526,14 +1783,79 @@ pub mod error {
     impl std::error::Error for Error {
         fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
             match self {
+                Error::Device { source, .. } => Some(source.as_ref()),
                 Error::IoError { source } => Some(source),
+                #[cfg(feature = "usb")]
+                Error::Hid { source } => Some(source),
                 Error::CustomFmt { source } => Some(source),
-                #[cfg(feature = "webhook")]
+                #[cfg(any(feature = "calendar", feature = "webhook"))]
                 Error::Request { source } => Some(source),
                 Error::Fmt { source } => Some(source),
+                #[cfg(feature = "dbus")]
+                Error::Dbus { source } => Some(source),
+                #[cfg(feature = "mqtt")]
+                Error::Mqtt { source } => Some(source),
+                #[cfg(any(
+                    feature = "mqtt",
+                    feature = "receiver",
+                    feature = "recorder",
+                    feature = "server"
+                ))]
+                Error::Json { source } => Some(source),
+                #[cfg(feature = "config")]
+                Error::Toml { source } => Some(source),
+                #[cfg(any(feature = "receiver", feature = "server"))]
+                Error::Server { source } => Some(source.as_ref()),
+                _ => None,
+            }
+        }
+    }
+
+    impl Error {
+        ///
+        /// Wrap this error with the identifier of the device, and the command being applied, that
+        /// caused it. An error that already has a device context is returned unchanged.
+        ///
+        pub fn with_context(self, device_id: &str, command: Option<&str>) -> Self {
+            match self {
+                Self::Device { .. } => self,
+                source => Self::Device {
+                    device_id: device_id.to_string(),
+                    command: command.map(str::to_string),
+                    source: Box::new(source),
+                },
+            }
+        }
+
+        ///
+        /// Return the identifier of the device that caused this error, if known.
+        ///
+        pub fn device_id(&self) -> Option<&str> {
+            match self {
+                Self::Device { device_id, .. } => Some(device_id),
                 _ => None,
             }
         }
+
+        ///
+        /// Return the command being applied when this error occurred, if known.
+        ///
+        pub fn command(&self) -> Option<&str> {
+            match self {
+                Self::Device { command, .. } => command.as_deref(),
+                _ => None,
+            }
+        }
+
+        ///
+        /// Return the underlying error, without any device context.
+        ///
+        pub fn root(&self) -> &Self {
+            match self {
+                Self::Device { source, .. } => source.root(),
+                _ => self,
+            }
+        }
     }
 
     impl From<std::io::Error> for Error {
@@ -548,22 +1870,295 @@ pub mod error {
         }
     }
 
-    #[cfg(feature = "webhook")]
+    #[cfg(any(feature = "calendar", feature = "webhook"))]
     impl From<reqwest::Error> for Error {
         fn from(source: reqwest::Error) -> Self {
             Self::Request { source }
         }
     }
 
+    #[cfg(feature = "usb")]
+    impl From<hidapi::HidError> for Error {
+        fn from(source: hidapi::HidError) -> Self {
+            Self::Hid { source }
+        }
+    }
+
     impl From<std::fmt::Error> for Error {
         fn from(source: std::fmt::Error) -> Self {
             Self::Fmt { source }
         }
     }
+
+    #[cfg(feature = "dbus")]
+    impl From<zbus::Error> for Error {
+        fn from(source: zbus::Error) -> Self {
+            Self::Dbus { source }
+        }
+    }
+
+    #[cfg(feature = "mqtt")]
+    impl From<rumqttc::ClientError> for Error {
+        fn from(source: rumqttc::ClientError) -> Self {
+            Self::Mqtt { source }
+        }
+    }
+
+    #[cfg(any(
+        feature = "mqtt",
+        feature = "receiver",
+        feature = "recorder",
+        feature = "server"
+    ))]
+    impl From<serde_json::Error> for Error {
+        fn from(source: serde_json::Error) -> Self {
+            Self::Json { source }
+        }
+    }
+
+    #[cfg(feature = "config")]
+    impl From<toml::de::Error> for Error {
+        fn from(source: toml::de::Error) -> Self {
+            Self::Toml { source }
+        }
+    }
 }
 
-#[cfg(feature = "usb")]
+#[cfg(all(unix, feature = "broker"))]
+pub mod broker;
+
+pub mod builder;
+
+pub mod button;
+
+#[cfg(feature = "calendar")]
+pub mod calendar;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "dbus")]
+pub mod dbus;
+
+pub mod doctor;
+
+pub mod emulated;
+
+pub mod group;
+
+mod hid;
+
+#[cfg(any(feature = "receiver", feature = "server"))]
+mod http;
+
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+
+#[cfg(feature = "process")]
+pub mod process;
+
+#[cfg(feature = "receiver")]
+pub mod receiver;
+
+#[cfg(feature = "recorder")]
+pub mod recorder;
+
+pub mod registry;
+
+#[cfg(feature = "schedule")]
+pub mod schedule;
+
+mod sequencer;
+
+#[cfg(feature = "server")]
+pub mod server;
+
+pub mod shared;
+
+pub mod status;
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(feature = "terminal")]
+pub mod terminal;
+
+pub mod timer;
+
+#[cfg(any(feature = "usb", all(target_os = "linux", feature = "usb-hidraw")))]
 pub mod usb_hid;
 
+#[cfg(all(target_os = "linux", feature = "usb-hidraw"))]
+mod hidraw;
+
 #[cfg(feature = "webhook")]
 pub mod webhook;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::MockDevice;
+    use crate::{
+        Capabilities, Command, CommandKind, DeviceModel, LedIndex, Pattern, RandomPattern, Repeat,
+        SolidColor, SpecificLED, Speed,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_custom_color_from_str() {
+        let color: SolidColor = "FF8001".parse().unwrap();
+        assert_eq!(color.to_string(), "ff8001");
+    }
+
+    #[test]
+    fn test_custom_color_channels() {
+        // Each channel is parsed from its own two digits; every channel once used only its first.
+        let color: SolidColor = "1a2b3c".parse().unwrap();
+        assert_eq!(color.to_rgb(), (0x1a, 0x2b, 0x3c));
+        assert!("1a2b3".parse::<SolidColor>().is_err());
+        assert!("1a2b3g".parse::<SolidColor>().is_err());
+    }
+
+    #[test]
+    fn test_color_temperature() {
+        let kelvin = |text: &str| text.parse::<SolidColor>().unwrap().to_rgb();
+        assert_eq!(kelvin("warm"), (255, 167, 87));
+        assert_eq!(kelvin("warm"), kelvin("2700k"));
+        assert_eq!(kelvin("Daylight"), kelvin("6500K"));
+        let (red, green, blue) = kelvin("1500K");
+        assert!(red == 255 && green < 120 && blue == 0);
+        let (red, green, blue) = kelvin("10000K");
+        assert!(red < green && green < blue && blue == 255);
+        let (red, green, blue) = kelvin("neutral");
+        assert!(red == 255 && green > 200 && blue > 150 && blue < green);
+        assert!("1000K".parse::<SolidColor>().is_err());
+        assert!("hotK".parse::<SolidColor>().is_err());
+        assert!(SolidColor::from_kelvin(10001).is_err());
+    }
+
+    #[test]
+    fn test_command_round_trip() {
+        for text in &[
+            "off",
+            "solid red",
+            "fade 00ff80 60",
+            "strobe blue 10 255",
+            "wave green overlapping short 30 5",
+            "pattern traffic lights 3",
+        ] {
+            let command: Command = text.parse().unwrap();
+            assert_eq!(&command.to_string(), text);
+        }
+        assert!("wave green 30 5".parse::<Command>().is_err());
+        assert!("pattern 3".parse::<Command>().is_err());
+        assert!("solid".parse::<Command>().is_err());
+        assert_eq!(
+            "green".parse::<Command>().unwrap().to_string(),
+            "solid green"
+        );
+    }
+
+    #[test]
+    fn test_device_models() {
+        assert_eq!(DeviceModel::from_product("LUXAFOR FLAG"), DeviceModel::Flag);
+        assert_eq!(DeviceModel::from_product("Luxafor Orb"), DeviceModel::Orb);
+        assert_eq!(
+            DeviceModel::from_product("LUXAFOR BT"),
+            DeviceModel::BluetoothPro
+        );
+        assert_eq!(DeviceModel::from_product("LUXAFOR MUTE"), DeviceModel::Mute);
+        assert_eq!(
+            DeviceModel::from_product("LUXAFOR CB"),
+            DeviceModel::ColorblindFlag
+        );
+        assert_eq!(DeviceModel::from_product(""), DeviceModel::Flag);
+        assert_eq!(
+            "colorblind-flag".parse::<DeviceModel>().unwrap(),
+            DeviceModel::ColorblindFlag
+        );
+        assert!("lamp".parse::<DeviceModel>().is_err());
+
+        let six = SpecificLED::Number(LedIndex::new(6).unwrap());
+        assert!(DeviceModel::Flag.check_led(&six).is_ok());
+        assert!(DeviceModel::Flag.check_led(&SpecificLED::AllBack).is_ok());
+        assert!(DeviceModel::Orb.check_led(&six).is_err());
+        assert!(DeviceModel::Orb.check_led(&SpecificLED::AllFront).is_err());
+        assert!(DeviceModel::Orb.check_led(&SpecificLED::All).is_ok());
+
+        assert!(DeviceModel::Flag.capabilities().led_targeting);
+        assert!(!DeviceModel::Orb.capabilities().led_targeting);
+        let mute = DeviceModel::Mute.capabilities();
+        assert!(mute.check(&"strobe red 10 5".parse().unwrap()).is_ok());
+        assert!(mute.check(&"pattern police 5".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_validated_values() {
+        assert_eq!("10".parse::<Speed>().unwrap(), Speed::new(10));
+        assert_eq!("500ms".parse::<Speed>().unwrap(), Speed::new(10));
+        assert_eq!("2s".parse::<Speed>().unwrap(), Speed::new(40));
+        assert_eq!(Speed::new(3).as_duration(), Duration::from_millis(150));
+        assert!("13s".parse::<Speed>().is_err());
+        assert!("fast".parse::<Speed>().is_err());
+
+        assert_eq!("forever".parse::<Repeat>().unwrap(), Repeat::Forever);
+        assert_eq!(Repeat::from_firmware(0), Repeat::Forever);
+        assert_eq!(Repeat::Forever.to_firmware(), 0);
+        assert_eq!(Repeat::times(5).unwrap().to_firmware(), 5);
+        assert!("0".parse::<Repeat>().is_err());
+
+        assert_eq!(RandomPattern::new(5).unwrap().number(), 5);
+        assert!(RandomPattern::new(0).is_err());
+        assert!("random 6".parse::<Pattern>().is_err());
+        assert_eq!(LedIndex::new(6).unwrap().number(), 6);
+        assert!(LedIndex::new(0).is_err());
+        assert!("7".parse::<LedIndex>().is_err());
+
+        assert_eq!(
+            "strobe blue 500ms forever"
+                .parse::<Command>()
+                .unwrap()
+                .to_string(),
+            "strobe blue 10 forever"
+        );
+    }
+
+    #[test]
+    fn test_command_apply() {
+        let device = MockDevice::default();
+        "pattern random 2 5"
+            .parse::<Command>()
+            .unwrap()
+            .apply(&device)
+            .unwrap();
+        Command::TurnOff.apply(&device).unwrap();
+        assert_eq!(device.calls(), vec!["pattern random 2 5", "off"]);
+    }
+
+    #[test]
+    fn test_capabilities_check() {
+        let all = Capabilities::all();
+        for text in [
+            "off",
+            "fade red 60",
+            "wave blue long 30 5",
+            "pattern police 5",
+        ] {
+            let command: Command = text.parse().unwrap();
+            assert!(all.supports(&command));
+            assert!(all.check(&command).is_ok());
+        }
+
+        let mut limited = Capabilities::all();
+        let _ = limited.commands.remove(&CommandKind::Fade);
+        limited.patterns.retain(|p| p.to_string() == "police");
+        assert!(limited.check(&"fade red 60".parse().unwrap()).is_err());
+        assert!(limited.check(&"pattern police 5".parse().unwrap()).is_ok());
+        assert!(limited
+            .check(&"pattern traffic lights 5".parse().unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,136 @@
+/*!
+A device implementation, used only by tests, that records each call made to it, and a writer
+that records everything written to it.
+
+*/
+
+use crate::{
+    Capabilities, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice, Wave,
+};
+#[cfg(any(feature = "recorder", feature = "terminal"))]
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Records each call as a string in the same form as `Command`'s display, clones share the
+/// same record.
+///
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MockDevice {
+    calls: Arc<Mutex<Vec<String>>>,
+    capabilities: Option<Capabilities>,
+}
+
+///
+/// Records the bytes written to it, clones share the same record.
+///
+#[cfg(any(feature = "recorder", feature = "terminal"))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MockWriter(Arc<Mutex<Vec<u8>>>);
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl MockDevice {
+    pub(crate) fn with_capabilities(capabilities: Capabilities) -> Self {
+        Self {
+            capabilities: Some(capabilities),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: String) -> crate::error::Result<()> {
+        self.calls.lock().unwrap().push(call);
+        Ok(())
+    }
+}
+
+impl Device for MockDevice {
+    fn id(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn turn_off(&self) -> crate::error::Result<()> {
+        self.record("off".to_string())
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> crate::error::Result<()> {
+        self.record(format!("solid {}", color))
+    }
+
+    fn set_fade_to_color(
+        &self,
+        color: SolidColor,
+        fade_duration: Speed,
+    ) -> crate::error::Result<()> {
+        self.record(format!("fade {} {}", color, fade_duration))
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> crate::error::Result<()> {
+        self.record(format!(
+            "strobe {} {} {}",
+            color, strobe_speed, repeat_count
+        ))
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> crate::error::Result<()> {
+        self.record(format!(
+            "wave {} {} {} {}",
+            color, wave_pattern, wave_speed, repeat_count
+        ))
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> crate::error::Result<()> {
+        self.record(format!("pattern {} {}", pattern, repeat_count))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone().unwrap_or_else(Capabilities::all)
+    }
+}
+
+impl TargetedDevice for MockDevice {
+    fn set_specific_led(&mut self, led: SpecificLED) -> crate::error::Result<()> {
+        self.record(format!("led {}", led))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(any(feature = "recorder", feature = "terminal"))]
+impl Write for MockWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "recorder", feature = "terminal"))]
+impl MockWriter {
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
@@ -0,0 +1,641 @@
+/*!
+A bridge that allows a [Device] to be controlled via MQTT, including support for
+[Home Assistant MQTT discovery](https://www.home-assistant.io/integrations/light.mqtt/).
+
+# Topics
+
+All topics are relative to a base topic, by default `luxafor`, and a node identifier derived from
+the device identifier.
+
+| Topic                                       | Direction | Content                          |
+|---------------------------------------------|-----------|----------------------------------|
+| `<base>/<node>/set`                         | Subscribe | A JSON command, see below        |
+| `<base>/<node>/state`                       | Publish   | The JSON state, retained         |
+| `<base>/<node>/availability`                | Publish   | `online` or `offline`, retained  |
+| `<discovery prefix>/light/<node>/config`    | Publish   | Home Assistant discovery config  |
+
+# Commands
+
+The command topic accepts the Home Assistant JSON schema, where `state` is either `ON` or `OFF`,
+`color` is an object with `r`, `g`, and `b` values, `effect` is one of the effect names, and
+`transition` is a fade duration. The effect names are taken from the [Pattern] and [Wave] enums,
+the latter prefixed with `wave`, as well as `strobe`.
+
+```json
+{ "state": "ON", "color": { "r": 255, "g": 128, "b": 0 }, "effect": "wave short" }
+```
+
+The command topic also accepts an explicit form in which `command` is one of `off`, `solid`,
+`fade`, `strobe`, `wave`, or `pattern`; the remaining fields are optional and have the same defaults
+as the `lux` command line. The `color` may be a name, hex string, or an RGB object.
+
+```json
+{ "command": "strobe", "color": "red", "speed": 10, "repeat": 5 }
+{ "command": "fade", "color": "00ff00", "duration": 60 }
+{ "command": "wave", "color": "blue", "wave": "overlapping long", "speed": 30, "repeat": 255 }
+{ "command": "pattern", "pattern": "police", "repeat": 3 }
+```
+
+# Example
+
+```rust,ignore
+use luxafor::mqtt::{MqttBridge, MqttConfig};
+use luxafor::webhook::new_device_for;
+use luxafor::error::Result;
+
+fn bridge(device_id: &str) -> Result<()> {
+    let mut bridge = MqttBridge::new(new_device_for(device_id)?, MqttConfig::new("localhost", 1883));
+    bridge.run()
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{Command, Device, Pattern, Repeat, SolidColor, Speed, Wave};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Value};
+use std::num::NonZeroU8;
+use std::str::FromStr;
+use std::time::Duration;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The configuration for the MQTT bridge.
+///
+#[derive(Clone, Debug)]
+pub struct MqttConfig {
+    /// The host name of the MQTT broker.
+    pub host: String,
+    /// The port of the MQTT broker.
+    pub port: u16,
+    /// The client identifier to connect with.
+    pub client_id: String,
+    /// An optional user name and password to connect with.
+    pub credentials: Option<(String, String)>,
+    /// The base topic, under which the command, state and availability topics are created.
+    pub base_topic: String,
+    /// The Home Assistant discovery prefix, if `None` no discovery config is published.
+    pub discovery_prefix: Option<String>,
+}
+
+///
+/// The bridge between an MQTT broker and a device.
+///
+#[allow(missing_debug_implementations)]
+pub struct MqttBridge<D: Device> {
+    device: D,
+    config: MqttConfig,
+    node_id: String,
+    color: SolidColor,
+    last_command: Option<Command>,
+    effect: Option<String>,
+}
+
+///
+/// The default MQTT port.
+///
+pub const DEFAULT_PORT: u16 = 1883;
+
+///
+/// The default base topic.
+///
+pub const DEFAULT_BASE_TOPIC: &str = "luxafor";
+
+///
+/// The default Home Assistant discovery prefix.
+///
+pub const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const AVAILABILITY_ONLINE: &str = "online";
+const AVAILABILITY_OFFLINE: &str = "offline";
+
+const STATE_ON: &str = "ON";
+const STATE_OFF: &str = "OFF";
+
+const EFFECT_STROBE: &str = "strobe";
+const EFFECT_WAVE_PREFIX: &str = "wave ";
+
+const DEFAULT_FADE_DURATION: Speed = Speed::new(60);
+const DEFAULT_STROBE_SPEED: Speed = Speed::DEFAULT_STROBE;
+const DEFAULT_WAVE_SPEED: Speed = Speed::new(30);
+const DEFAULT_REPEAT_COUNT: Repeat = Repeat::Times(NonZeroU8::MAX);
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const REQUEST_CAPACITY: usize = 16;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the list of effect names supported by the bridge, these are the names used in the Home
+/// Assistant discovery config.
+///
+pub fn effect_list() -> Vec<String> {
+    Pattern::all()
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .chain(std::iter::once(EFFECT_STROBE.to_string()))
+        .chain(
+            Wave::all()
+                .iter()
+                .map(|wave| format!("{}{}", EFFECT_WAVE_PREFIX, wave)),
+        )
+        .collect()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl MqttConfig {
+    ///
+    /// Construct a new configuration for the broker at `host` and `port`, all other values take
+    /// their defaults.
+    ///
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            client_id: format!("lux-{}", std::process::id()),
+            credentials: None,
+            base_topic: DEFAULT_BASE_TOPIC.to_string(),
+            discovery_prefix: Some(DEFAULT_DISCOVERY_PREFIX.to_string()),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<D: Device> MqttBridge<D> {
+    ///
+    /// Construct a new bridge for `device`, this does not connect to the broker until `run` is
+    /// called.
+    ///
+    pub fn new(device: D, config: MqttConfig) -> Self {
+        let node_id = device
+            .id()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Self {
+            device,
+            config,
+            node_id,
+            color: SolidColor::White,
+            last_command: None,
+            effect: None,
+        }
+    }
+
+    ///
+    /// The topic on which commands are received.
+    ///
+    pub fn command_topic(&self) -> String {
+        format!("{}/{}/set", self.config.base_topic, self.node_id)
+    }
+
+    ///
+    /// The topic on which the device state is published.
+    ///
+    pub fn state_topic(&self) -> String {
+        format!("{}/{}/state", self.config.base_topic, self.node_id)
+    }
+
+    ///
+    /// The topic on which the bridge availability is published.
+    ///
+    pub fn availability_topic(&self) -> String {
+        format!("{}/{}/availability", self.config.base_topic, self.node_id)
+    }
+
+    ///
+    /// The topic on which the Home Assistant discovery config is published, if enabled.
+    ///
+    pub fn discovery_topic(&self) -> Option<String> {
+        self.config
+            .discovery_prefix
+            .as_ref()
+            .map(|prefix| format!("{}/light/{}/config", prefix, self.node_id))
+    }
+
+    ///
+    /// Return the Home Assistant discovery config for the device as a JSON value.
+    ///
+    pub fn discovery_config(&self) -> Value {
+        json!({
+            "name": format!("Luxafor {}", self.device.id()),
+            "unique_id": format!("luxafor_{}", self.node_id),
+            "schema": "json",
+            "command_topic": self.command_topic(),
+            "state_topic": self.state_topic(),
+            "availability_topic": self.availability_topic(),
+            "payload_available": AVAILABILITY_ONLINE,
+            "payload_not_available": AVAILABILITY_OFFLINE,
+            "brightness": false,
+            "supported_color_modes": ["rgb"],
+            "effect": true,
+            "effect_list": effect_list(),
+            "device": {
+                "identifiers": [format!("luxafor_{}", self.node_id)],
+                "name": format!("Luxafor {}", self.device.id()),
+                "manufacturer": "Luxafor",
+            },
+        })
+    }
+
+    ///
+    /// Connect to the broker and process commands until the connection is closed. Connection
+    /// errors are logged, and the bridge will attempt to reconnect.
+    ///
+    pub fn run(&mut self) -> Result<()> {
+        let mut options =
+            MqttOptions::new(&self.config.client_id, &self.config.host, self.config.port);
+        let _ = options
+            .set_keep_alive(KEEP_ALIVE)
+            .set_last_will(LastWill::new(
+                self.availability_topic(),
+                AVAILABILITY_OFFLINE,
+                QoS::AtLeastOnce,
+                true,
+            ));
+        if let Some((user_name, password)) = &self.config.credentials {
+            let _ = options.set_credentials(user_name, password);
+        }
+        info!(
+            "Connecting device '{}' to MQTT broker {}:{}",
+            self.device.id(),
+            self.config.host,
+            self.config.port
+        );
+        let command_topic = self.command_topic();
+        let (client, mut connection) = Client::new(options, REQUEST_CAPACITY);
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => self.on_connect(&client)?,
+                Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == command_topic => {
+                    self.on_command(&client, &publish.payload)?
+                }
+                Ok(event) => trace!("MQTT event: {:?}", event),
+                Err(e) => {
+                    error!("MQTT connection error: {}", e);
+                    std::thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_connect(&self, client: &Client) -> Result<()> {
+        info!("Connected, subscribing to {}", self.command_topic());
+        client.subscribe(self.command_topic(), QoS::AtLeastOnce)?;
+        if let Some(topic) = self.discovery_topic() {
+            client.publish(
+                topic,
+                QoS::AtLeastOnce,
+                true,
+                self.discovery_config().to_string(),
+            )?;
+        }
+        client.publish(
+            self.availability_topic(),
+            QoS::AtLeastOnce,
+            true,
+            AVAILABILITY_ONLINE,
+        )?;
+        self.publish_state(client)
+    }
+
+    fn on_command(&mut self, client: &Client, payload: &[u8]) -> Result<()> {
+        match parse_command(payload, &self.color) {
+            Ok((command, effect)) => {
+                debug!("Received command '{}'", command);
+                match command.clone().apply(&self.device) {
+                    Ok(()) => {
+                        if let Some(color) = command_color(&command) {
+                            self.color = color;
+                        }
+                        self.last_command = Some(command);
+                        self.effect = effect;
+                    }
+                    Err(e) => error!("Device failed to apply command '{}': {}", command, e),
+                }
+            }
+            Err(e) => warn!(
+                "Ignoring invalid command {:?}: {}",
+                String::from_utf8_lossy(payload),
+                e
+            ),
+        }
+        self.publish_state(client)
+    }
+
+    fn publish_state(&self, client: &Client) -> Result<()> {
+        let state = match &self.last_command {
+            None | Some(Command::TurnOff) => json!({ "state": STATE_OFF }),
+            Some(command) => {
+                let (r, g, b) = self.color.to_rgb();
+                json!({
+                    "state": STATE_ON,
+                    "color_mode": "rgb",
+                    "color": { "r": r, "g": g, "b": b },
+                    "effect": self.effect,
+                    "command": command.to_string(),
+                })
+            }
+        };
+        client.publish(
+            self.state_topic(),
+            QoS::AtLeastOnce,
+            true,
+            state.to_string(),
+        )?;
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn parse_command(payload: &[u8], current: &SolidColor) -> Result<(Command, Option<String>)> {
+    let payload: Value = serde_json::from_slice(payload)?;
+    let color = match payload.get("color") {
+        None => current.clone(),
+        Some(color) => parse_color(color)?,
+    };
+    let number = |name: &str| -> Result<Option<u8>> {
+        match payload.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .and_then(|v| u8::try_from(v).ok())
+                .map(Some)
+                .ok_or(Error::InvalidCommand),
+        }
+    };
+    let speed = |name: &str, default: Speed| -> Result<Speed> {
+        Ok(number(name)?.map(Speed::new).unwrap_or(default))
+    };
+    let repeat = |name: &str| -> Result<Repeat> {
+        Ok(number(name)?
+            .map(Repeat::from_firmware)
+            .unwrap_or(DEFAULT_REPEAT_COUNT))
+    };
+    let string = |name: &str| payload.get(name).and_then(Value::as_str);
+
+    if let Some(command) = string("command") {
+        let command = match command {
+            "off" => Command::TurnOff,
+            "solid" => Command::Solid { color },
+            "fade" => Command::Fade {
+                color,
+                fade_duration: speed("duration", DEFAULT_FADE_DURATION)?,
+            },
+            "strobe" => Command::Strobe {
+                color,
+                strobe_speed: speed("speed", DEFAULT_STROBE_SPEED)?,
+                repeat_count: repeat("repeat")?,
+            },
+            "wave" => Command::Wave {
+                color,
+                wave_pattern: Wave::from_str(string("wave").unwrap_or("short"))?,
+                wave_speed: speed("speed", DEFAULT_WAVE_SPEED)?,
+                repeat_count: repeat("repeat")?,
+            },
+            "pattern" => Command::Pattern {
+                pattern: Pattern::from_str(string("pattern").ok_or(Error::InvalidPattern)?)?,
+                repeat_count: repeat("repeat")?,
+            },
+            _ => return Err(Error::InvalidCommand),
+        };
+        Ok((command, None))
+    } else {
+        match string("state") {
+            Some(STATE_OFF) => Ok((Command::TurnOff, None)),
+            Some(STATE_ON) => match string("effect") {
+                None => match payload.get("transition") {
+                    None => Ok((Command::Solid { color }, None)),
+                    Some(_) => Ok((
+                        Command::Fade {
+                            color,
+                            fade_duration: speed("transition", DEFAULT_FADE_DURATION)?,
+                        },
+                        None,
+                    )),
+                },
+                Some(effect) => {
+                    let command = if effect == EFFECT_STROBE {
+                        Command::Strobe {
+                            color,
+                            strobe_speed: DEFAULT_STROBE_SPEED,
+                            repeat_count: DEFAULT_REPEAT_COUNT,
+                        }
+                    } else if let Some(wave) = effect.strip_prefix(EFFECT_WAVE_PREFIX) {
+                        Command::Wave {
+                            color,
+                            wave_pattern: Wave::from_str(wave)?,
+                            wave_speed: DEFAULT_WAVE_SPEED,
+                            repeat_count: DEFAULT_REPEAT_COUNT,
+                        }
+                    } else {
+                        Command::Pattern {
+                            pattern: Pattern::from_str(effect)?,
+                            repeat_count: DEFAULT_REPEAT_COUNT,
+                        }
+                    };
+                    Ok((command, Some(effect.to_string())))
+                }
+            },
+            _ => Err(Error::InvalidCommand),
+        }
+    }
+}
+
+fn parse_color(color: &Value) -> Result<SolidColor> {
+    match color {
+        Value::String(color) => SolidColor::from_str(color),
+        Value::Object(_) => {
+            let channel = |name: &str| {
+                color
+                    .get(name)
+                    .and_then(Value::as_u64)
+                    .and_then(|v| u8::try_from(v).ok())
+                    .ok_or(Error::InvalidColor)
+            };
+            Ok(SolidColor::Custom {
+                red: channel("r")?,
+                green: channel("g")?,
+                blue: channel("b")?,
+            })
+        }
+        _ => Err(Error::InvalidColor),
+    }
+}
+
+fn command_color(command: &Command) -> Option<SolidColor> {
+    match command {
+        Command::Solid { color }
+        | Command::Fade { color, .. }
+        | Command::Strobe { color, .. }
+        | Command::Wave { color, .. } => Some(color.clone()),
+        _ => None,
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{effect_list, parse_command, MqttBridge, MqttConfig};
+    use crate::mock::MockDevice;
+    use crate::SolidColor;
+
+    fn parse(payload: &str) -> String {
+        parse_command(payload.as_bytes(), &SolidColor::Green)
+            .unwrap()
+            .0
+            .to_string()
+    }
+
+    #[test]
+    fn test_parse_home_assistant_commands() {
+        assert_eq!(parse(r#"{"state":"OFF"}"#), "off");
+        assert_eq!(parse(r#"{"state":"ON"}"#), "solid green");
+        assert_eq!(
+            parse(r#"{"state":"ON","color":{"r":255,"g":128,"b":0}}"#),
+            "solid ff8000"
+        );
+        assert_eq!(
+            parse(r#"{"state":"ON","color":{"r":0,"g":0,"b":255},"transition":5}"#),
+            "fade 0000ff 5"
+        );
+        assert_eq!(
+            parse(r#"{"state":"ON","effect":"traffic lights"}"#),
+            "pattern traffic lights 255"
+        );
+        assert_eq!(
+            parse(r#"{"state":"ON","effect":"wave overlapping long"}"#),
+            "wave green overlapping long 30 255"
+        );
+        assert_eq!(
+            parse(r#"{"state":"ON","effect":"strobe"}"#),
+            "strobe green 10 255"
+        );
+        assert!(parse_command(br#"{"state":"ON","effect":"disco"}"#, &SolidColor::Red).is_err());
+    }
+
+    #[test]
+    fn test_parse_explicit_commands() {
+        assert_eq!(parse(r#"{"command":"off"}"#), "off");
+        assert_eq!(parse(r#"{"command":"solid","color":"red"}"#), "solid red");
+        assert_eq!(
+            parse(r#"{"command":"fade","color":"00ff00","duration":20}"#),
+            "fade 00ff00 20"
+        );
+        assert_eq!(
+            parse(r#"{"command":"strobe","color":"red","speed":5,"repeat":3}"#),
+            "strobe red 5 3"
+        );
+        assert_eq!(
+            parse(r#"{"command":"wave","color":"blue","wave":"long"}"#),
+            "wave blue long 30 255"
+        );
+        assert_eq!(
+            parse(r#"{"command":"pattern","pattern":"police","repeat":2}"#),
+            "pattern police 2"
+        );
+        assert!(parse_command(br#"{"command":"pattern"}"#, &SolidColor::Red).is_err());
+        assert!(
+            parse_command(br#"{"command":"solid","color":"mauve"}"#, &SolidColor::Red).is_err()
+        );
+    }
+
+    #[test]
+    fn test_discovery_config() {
+        let bridge = MqttBridge::new(MockDevice::default(), MqttConfig::new("localhost", 1883));
+        assert_eq!(
+            bridge.discovery_topic().unwrap(),
+            "homeassistant/light/mock/config"
+        );
+        let config = bridge.discovery_config();
+        assert_eq!(config["command_topic"], "luxafor/mock/set");
+        assert_eq!(config["state_topic"], "luxafor/mock/state");
+        assert_eq!(
+            config["effect_list"].as_array().unwrap().len(),
+            effect_list().len()
+        );
+        assert!(effect_list().contains(&"wave overlapping short".to_string()));
+    }
+}
+
+///
+/// These tests require an MQTT broker, by default on `localhost:1883` although this may be changed
+/// with the environment variable `LUX_MQTT_TEST_BROKER`. Run with
+/// `RUSTFLAGS="--cfg mqtt_test" cargo test --features mqtt`.
+///
+#[cfg(all(test, mqtt_test))]
+mod broker_tests {
+    use super::{MqttBridge, MqttConfig};
+    use crate::mock::MockDevice;
+    use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+    use std::time::Duration;
+
+    #[test]
+    fn test_bridge_against_broker() {
+        let address =
+            std::env::var("LUX_MQTT_TEST_BROKER").unwrap_or_else(|_| "localhost:1883".to_string());
+        let (host, port) = address.split_once(':').unwrap();
+        let port: u16 = port.parse().unwrap();
+
+        let mock = MockDevice::default();
+        let mut config = MqttConfig::new(host, port);
+        config.base_topic = format!("luxafor-test-{}", std::process::id());
+        let mut bridge = MqttBridge::new(mock.clone(), config);
+        let command_topic = bridge.command_topic();
+        let state_topic = bridge.state_topic();
+        let _ = std::thread::spawn(move || bridge.run());
+
+        let (client, mut connection) =
+            Client::new(MqttOptions::new("lux-test-client", host, port), 10);
+        client.subscribe(&state_topic, QoS::AtLeastOnce).unwrap();
+        let mut sent = false;
+        for event in connection.iter() {
+            if let Ok(Event::Incoming(Packet::Publish(publish))) = event {
+                let state = String::from_utf8_lossy(&publish.payload).to_string();
+                if !sent {
+                    client
+                        .publish(
+                            &command_topic,
+                            QoS::AtLeastOnce,
+                            false,
+                            r#"{"state":"ON","effect":"police"}"#,
+                        )
+                        .unwrap();
+                    sent = true;
+                } else if state.contains("police") {
+                    break;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(mock.calls(), vec!["pattern police 255"]);
+    }
+}
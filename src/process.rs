@@ -0,0 +1,309 @@
+/*!
+Run a command, such as a build or test run, and show its status on a light.
+
+While the command runs the light shows the `running` command; when it exits the light shows the
+`success` or `failure` command, depending on the exit status, and after a delay the `restore`
+command is applied. As a light cannot report its current state, `restore` is usually the state the
+light is normally left in, by default `off`. By default `running`, `success`, and `failure` are
+the `warning`, `available`, and `busy` [status presets](../status/index.html) in the current
+palette.
+
+The command shares the standard input, output, and error streams of the current process. On Unix,
+signals such as `SIGTERM` sent to the current process are forwarded to the command; signals sent by
+the terminal, such as `^C`, are received by the command directly. A signal received during the
+restore delay restores the light immediately.
+
+# Example
+
+```rust,ignore
+use luxafor::process::{run_with_status, StatusCommands};
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::error::Result;
+
+fn cargo_test() -> Result<i32> {
+    let discovery = USBDeviceDiscovery::new()?;
+    let device = discovery.device()?;
+    let args = vec!["cargo".into(), "test".into()];
+    run_with_status(&device, &args, &StatusCommands::default())
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::status::StatusPreset;
+use crate::{Command, Device};
+use std::ffi::OsString;
+use std::process::ExitStatus;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+#[cfg(unix)]
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+#[cfg(unix)]
+use signal_hook::iterator::{exfiltrator::WithOrigin, SignalsInfo};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::Arc;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The commands applied to the light over the life of a process.
+///
+#[derive(Clone, Debug)]
+pub struct StatusCommands {
+    /// Applied while the process is running.
+    pub running: Command,
+    /// Applied when the process exits successfully.
+    pub success: Command,
+    /// Applied when the process fails, or cannot be started.
+    pub failure: Command,
+    /// Applied once `restore_delay` has passed after the process exits.
+    pub restore: Command,
+    /// How long the success or failure status is shown.
+    pub restore_delay: Duration,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(unix)]
+struct SignalForwarder {
+    handle: signal_hook::iterator::Handle,
+    child_running: Arc<AtomicBool>,
+}
+
+#[cfg(not(unix))]
+struct SignalForwarder;
+
+const DEFAULT_RESTORE_DELAY: Duration = Duration::from_secs(10);
+
+// The exit code used by shells for a process terminated by a signal is this plus the signal.
+#[cfg(unix)]
+const SIGNAL_EXIT_BASE: i32 = 128;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Run the program named by the first element of `command_line`, with the remaining elements as
+/// arguments, showing its status on `device`. Returns the exit code of the program.
+///
+/// Failures to set the light are logged, they do not affect the program or the exit code returned.
+///
+pub fn run_with_status<D: Device + ?Sized>(
+    device: &D,
+    command_line: &[OsString],
+    status: &StatusCommands,
+) -> Result<i32> {
+    let (program, args) = command_line.split_first().ok_or(Error::InvalidCommand)?;
+    let (signal_sender, signals) = channel();
+
+    show(device, &status.running);
+    let result = match std::process::Command::new(program).args(args).spawn() {
+        Ok(mut child) => {
+            info!("Started {:?} with process ID {}", program, child.id());
+            let forwarder = SignalForwarder::start(child.id(), signal_sender)?;
+            let exit_status = child.wait();
+            forwarder.child_exited();
+            let result = exit_status.map(|exit_status| {
+                info!("Process {:?} exited with {}", program, exit_status);
+                show(
+                    device,
+                    if exit_status.success() {
+                        &status.success
+                    } else {
+                        &status.failure
+                    },
+                );
+                exit_code(&exit_status)
+            });
+            wait_for_restore(&signals, status.restore_delay);
+            forwarder.close();
+            result
+        }
+        Err(e) => {
+            error!("Could not start {:?}: {}", program, e);
+            show(device, &status.failure);
+            wait_for_restore(&signals, status.restore_delay);
+            Err(e)
+        }
+    };
+    show(device, &status.restore);
+    Ok(result?)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for StatusCommands {
+    fn default() -> Self {
+        Self {
+            running: StatusPreset::Warning.command(),
+            success: StatusPreset::Available.command(),
+            failure: StatusPreset::Busy.command(),
+            restore: Command::TurnOff,
+            restore_delay: DEFAULT_RESTORE_DELAY,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(unix)]
+impl SignalForwarder {
+    fn start(child_id: u32, received: Sender<()>) -> Result<Self> {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+
+        let mut signals = SignalsInfo::<WithOrigin>::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM])?;
+        let handle = signals.handle();
+        let child_running = Arc::new(AtomicBool::new(true));
+        let forwarding = child_running.clone();
+        let _ = std::thread::spawn(move || {
+            for origin in signals.forever() {
+                // Signals from the terminal are sent to the whole process group, only those sent
+                // to this process alone need to be forwarded.
+                if origin.process.is_some() && forwarding.load(Ordering::SeqCst) {
+                    debug!(
+                        "Forwarding signal {} to process {}",
+                        origin.signal, child_id
+                    );
+                    if let Ok(signal) = Signal::try_from(origin.signal) {
+                        if let Err(e) = kill(Pid::from_raw(child_id as i32), signal) {
+                            warn!("Could not forward signal {}: {}", signal, e);
+                        }
+                    }
+                }
+                let _ = received.send(());
+            }
+        });
+        Ok(Self {
+            handle,
+            child_running,
+        })
+    }
+
+    fn child_exited(&self) {
+        self.child_running.store(false, Ordering::SeqCst);
+    }
+
+    fn close(self) {
+        self.handle.close();
+    }
+}
+
+#[cfg(not(unix))]
+impl SignalForwarder {
+    fn start(_: u32, _: Sender<()>) -> Result<Self> {
+        Ok(Self)
+    }
+
+    fn child_exited(&self) {}
+
+    fn close(self) {}
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn show<D: Device + ?Sized>(device: &D, command: &Command) {
+    if let Err(e) = command.clone().apply(device) {
+        warn!(
+            "Could not set device '{}' to '{}': {}",
+            device.id(),
+            command,
+            e
+        );
+    }
+}
+
+fn wait_for_restore(signals: &Receiver<()>, delay: Duration) {
+    // Discard any signals received while the process was running.
+    while signals.try_recv().is_ok() {}
+    if signals.recv_timeout(delay).is_ok() {
+        debug!("Signal received, restoring immediately");
+    }
+}
+
+#[cfg(unix)]
+fn exit_code(status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| SIGNAL_EXIT_BASE + signal))
+        .unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: &ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{run_with_status, StatusCommands};
+    use crate::mock::MockDevice;
+    use std::ffi::OsString;
+    use std::time::Duration;
+
+    fn shell(script: &str) -> Vec<OsString> {
+        vec!["sh".into(), "-c".into(), script.into()]
+    }
+
+    fn quick() -> StatusCommands {
+        StatusCommands {
+            restore_delay: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_success() {
+        let device = MockDevice::default();
+        assert_eq!(
+            run_with_status(&device, &shell("exit 0"), &quick()).unwrap(),
+            0
+        );
+        assert_eq!(device.calls(), vec!["solid yellow", "solid green", "off"]);
+    }
+
+    #[test]
+    fn test_failure() {
+        let device = MockDevice::default();
+        let status = StatusCommands {
+            failure: "strobe red 10 255".parse().unwrap(),
+            restore: "solid blue".parse().unwrap(),
+            ..quick()
+        };
+        assert_eq!(
+            run_with_status(&device, &shell("exit 3"), &status).unwrap(),
+            3
+        );
+        assert_eq!(
+            device.calls(),
+            vec!["solid yellow", "strobe red 10 255", "solid blue"]
+        );
+    }
+
+    #[test]
+    fn test_not_found() {
+        let device = MockDevice::default();
+        let command_line = vec![OsString::from("/no/such/program")];
+        assert!(run_with_status(&device, &command_line, &quick()).is_err());
+        assert!(run_with_status(&device, &[], &quick()).is_err());
+        assert_eq!(device.calls(), vec!["solid yellow", "solid red", "off"]);
+    }
+}
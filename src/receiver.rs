@@ -0,0 +1,643 @@
+/*!
+An HTTP server that receives webhook notifications from CI and alerting systems and shows them on
+a light.
+
+Each notification format is handled by an [Adapter], which turns a JSON payload into an [Event]
+with a normalized [Status]. The event is then matched against a list of [EventRule]s, the first
+rule that matches supplies the [Command](../enum.Command.html) to apply. The following adapters
+are provided, each served at its own path.
+
+| Path            | Adapter          | Payloads                                       |
+|-----------------|------------------|------------------------------------------------|
+| `/github`       | [GitHub]         | `workflow_run` and `check_suite` events        |
+| `/gitlab`       | [GitLab]         | Pipeline events                                |
+| `/alertmanager` | [Alertmanager]   | Prometheus Alertmanager webhook notifications  |
+
+Payloads an adapter does not handle, such as the GitHub `ping` event, are acknowledged and
+otherwise ignored. Additional adapters may be added with
+[`add_adapter`](struct.ReceiverServer.html#method.add_adapter).
+
+# Rules
+
+The text form of a rule is the adapter name, the status and optionally a severity, and then the
+command; either the adapter or status may be `*` to match any value. For example:
+
+```text
+alertmanager firing:critical pattern police 255
+* failure strobe red 10 255
+* resolved green
+```
+
+# Example
+
+```rust,ignore
+use luxafor::receiver::{default_rules, ReceiverServer};
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::error::Result;
+
+fn receive_notifications() -> Result<()> {
+    let discovery = USBDeviceDiscovery::new()?;
+    let server = ReceiverServer::bind("127.0.0.1:5384", discovery.device()?, default_rules())?;
+    println!("Receiving notifications at {}", server.url());
+    server.serve()
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::http::HttpServer;
+use crate::{Command, Device};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The normalized status of a notification.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// A pipeline, or workflow, is waiting to run.
+    Pending,
+    /// A pipeline, or workflow, is running.
+    Running,
+    /// A pipeline, or workflow, completed successfully.
+    Success,
+    /// A pipeline, or workflow, failed.
+    Failure,
+    /// A pipeline, or workflow, was cancelled or skipped.
+    Cancelled,
+    /// An alert is firing.
+    Firing,
+    /// An alert has been resolved.
+    Resolved,
+}
+
+///
+/// A notification, as produced by an [Adapter].
+///
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// The name of the adapter that produced the event.
+    pub source: String,
+    /// The name of the pipeline, workflow, or alert, used only for logging.
+    pub name: String,
+    /// The normalized status.
+    pub status: Status,
+    /// The severity, if the notification has one.
+    pub severity: Option<String>,
+}
+
+///
+/// Turns the JSON payload of a notification into an [Event].
+///
+pub trait Adapter: Send {
+    /// The name of the adapter, which is also the path it is served at.
+    fn name(&self) -> &str;
+
+    /// Return the event for `payload`, or `None` if the payload is not one this adapter handles.
+    fn event(&self, payload: &Value) -> Result<Option<Event>>;
+}
+
+///
+/// A rule that maps matching events to a command.
+///
+#[derive(Clone, Debug)]
+pub struct EventRule {
+    source: Option<String>,
+    status: Option<Status>,
+    severity: Option<String>,
+    command: Command,
+}
+
+///
+/// Handles GitHub `workflow_run` and `check_suite` webhook events.
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitHub;
+
+///
+/// Handles GitLab pipeline webhook events.
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitLab;
+
+///
+/// Handles Prometheus Alertmanager webhook notifications; the severity of a notification is the
+/// most severe of its firing alerts.
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Alertmanager;
+
+///
+/// A server that receives notifications and applies the matching rule to a single device.
+///
+#[allow(missing_debug_implementations)]
+pub struct ReceiverServer<D: Device> {
+    server: HttpServer,
+    device: D,
+    adapters: Vec<Box<dyn Adapter>>,
+    rules: Vec<EventRule>,
+}
+
+///
+/// The default address used by the `lux receive` command.
+///
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:5384";
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const ANY: &str = "*";
+
+// Severities in decreasing order, any other severity is ranked below these.
+const SEVERITIES: &[&str] = &["critical", "error", "warning", "info"];
+
+const DEFAULT_RULES: &[&str] = &[
+    "alertmanager firing:critical pattern police 255",
+    "* firing strobe red 10 255",
+    "* failure strobe red 10 255",
+    "* running yellow",
+    "* success green",
+    "* resolved green",
+];
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the rules used when none are configured.
+///
+pub fn default_rules() -> Vec<EventRule> {
+    DEFAULT_RULES
+        .iter()
+        .filter_map(|rule| rule.parse().ok())
+        .collect()
+}
+
+///
+/// Return the command of the first rule in `rules` that matches `event`, if any.
+///
+pub fn matching_command<'a>(rules: &'a [EventRule], event: &Event) -> Option<&'a Command> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(event))
+        .map(|rule| &rule.command)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Status::Pending => "pending",
+                Status::Running => "running",
+                Status::Success => "success",
+                Status::Failure => "failure",
+                Status::Cancelled => "cancelled",
+                Status::Firing => "firing",
+                Status::Resolved => "resolved",
+            }
+        )
+    }
+}
+
+impl FromStr for Status {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Status::Pending),
+            "running" => Ok(Status::Running),
+            "success" => Ok(Status::Success),
+            "failure" => Ok(Status::Failure),
+            "cancelled" => Ok(Status::Cancelled),
+            "firing" => Ok(Status::Firing),
+            "resolved" => Ok(Status::Resolved),
+            _ => Err(Error::InvalidEventRule),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for EventRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let status = self
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or_else(|| ANY.to_string());
+        write!(f, "{} {}", self.source.as_deref().unwrap_or(ANY), status)?;
+        if let Some(severity) = &self.severity {
+            write!(f, ":{}", severity)?;
+        }
+        write!(f, " {}", self.command)
+    }
+}
+
+impl FromStr for EventRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let source = words.next().ok_or(Error::InvalidEventRule)?;
+        let status = words.next().ok_or(Error::InvalidEventRule)?;
+        let (status, severity) = match status.split_once(':') {
+            Some((status, severity)) => (status, Some(severity.to_string())),
+            None => (status, None),
+        };
+        let command = words.collect::<Vec<&str>>().join(" ");
+        Ok(Self {
+            source: if source == ANY {
+                None
+            } else {
+                Some(source.to_string())
+            },
+            status: if status == ANY {
+                None
+            } else {
+                Some(status.parse()?)
+            },
+            severity,
+            command: command.parse()?,
+        })
+    }
+}
+
+impl EventRule {
+    ///
+    /// Construct a new rule; `None` for any of `source`, `status`, or `severity` matches any value.
+    ///
+    pub fn new(
+        source: Option<&str>,
+        status: Option<Status>,
+        severity: Option<&str>,
+        command: Command,
+    ) -> Self {
+        Self {
+            source: source.map(str::to_string),
+            status,
+            severity: severity.map(str::to_string),
+            command,
+        }
+    }
+
+    ///
+    /// Return the command applied for matching events.
+    ///
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    ///
+    /// Returns `true` if this rule matches `event`.
+    ///
+    pub fn matches(&self, event: &Event) -> bool {
+        self.source
+            .as_ref()
+            .is_none_or(|source| *source == event.source)
+            && self.status.is_none_or(|status| status == event.status)
+            && self
+                .severity
+                .as_ref()
+                .is_none_or(|severity| Some(severity) == event.severity.as_ref())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Adapter for GitHub {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn event(&self, payload: &Value) -> Result<Option<Event>> {
+        let (object, name) = if let Some(run) = payload.get("workflow_run") {
+            (run, string_field(run, "name")?)
+        } else if let Some(suite) = payload.get("check_suite") {
+            (suite, string_field(&suite["app"], "name")?)
+        } else {
+            return Ok(None);
+        };
+        let status = match string_field(object, "status")? {
+            "completed" => match string_field(object, "conclusion")? {
+                "success" | "neutral" | "skipped" => Status::Success,
+                "cancelled" | "stale" => Status::Cancelled,
+                _ => Status::Failure,
+            },
+            "in_progress" => Status::Running,
+            _ => Status::Pending,
+        };
+        Ok(Some(Event::new(self.name(), name, status, None)))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Adapter for GitLab {
+    fn name(&self) -> &str {
+        "gitlab"
+    }
+
+    fn event(&self, payload: &Value) -> Result<Option<Event>> {
+        if payload.get("object_kind").and_then(Value::as_str) != Some("pipeline") {
+            return Ok(None);
+        }
+        let name = string_field(&payload["project"], "path_with_namespace")?;
+        let status = match string_field(&payload["object_attributes"], "status")? {
+            "running" => Status::Running,
+            "success" => Status::Success,
+            "failed" => Status::Failure,
+            "canceled" | "skipped" => Status::Cancelled,
+            _ => Status::Pending,
+        };
+        Ok(Some(Event::new(self.name(), name, status, None)))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Adapter for Alertmanager {
+    fn name(&self) -> &str {
+        "alertmanager"
+    }
+
+    fn event(&self, payload: &Value) -> Result<Option<Event>> {
+        let status = match string_field(payload, "status")? {
+            "firing" => Status::Firing,
+            "resolved" => Status::Resolved,
+            _ => return Err(Error::InvalidRequest),
+        };
+        let name = payload["groupLabels"]["alertname"]
+            .as_str()
+            .unwrap_or_default();
+        let alerts = payload["alerts"].as_array().ok_or(Error::InvalidRequest)?;
+        let severity = alerts
+            .iter()
+            .filter(|alert| {
+                status == Status::Resolved || alert["status"].as_str() == Some("firing")
+            })
+            .filter_map(|alert| alert["labels"]["severity"].as_str())
+            .min_by_key(|severity| severity_rank(severity))
+            .or_else(|| payload["commonLabels"]["severity"].as_str());
+        Ok(Some(Event::new(self.name(), name, status, severity)))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Event {
+    ///
+    /// Construct a new event.
+    ///
+    pub fn new(source: &str, name: &str, status: Status, severity: Option<&str>) -> Self {
+        Self {
+            source: source.to_string(),
+            name: name.to_string(),
+            status,
+            severity: severity.map(str::to_string),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<D: Device> ReceiverServer<D> {
+    ///
+    /// Bind a new server to `address` with the [GitHub], [GitLab], and [Alertmanager] adapters;
+    /// events are matched against `rules` and applied to `device`. Binding to port `0` will
+    /// allocate a free port, use [`url`](#method.url) to determine the actual address.
+    ///
+    pub fn bind(address: &str, device: D, rules: Vec<EventRule>) -> Result<Self> {
+        let server = HttpServer::bind(address, "Receiver", &device.id())?;
+        Ok(Self {
+            server,
+            device,
+            adapters: vec![Box::new(GitHub), Box::new(GitLab), Box::new(Alertmanager)],
+            rules,
+        })
+    }
+
+    ///
+    /// Add an adapter, served at the path `/` followed by its name. An adapter with the same name
+    /// as an existing one replaces it.
+    ///
+    pub fn add_adapter(&mut self, adapter: Box<dyn Adapter>) {
+        self.adapters
+            .retain(|existing| existing.name() != adapter.name());
+        self.adapters.push(adapter);
+    }
+
+    ///
+    /// Return the base URL of the server; each adapter is served at this URL followed by `/` and
+    /// its name.
+    ///
+    pub fn url(&self) -> String {
+        self.server.url()
+    }
+
+    ///
+    /// Serve requests until the process is terminated, or the underlying socket fails. A failure
+    /// reading, or responding to, a single request is logged and does not stop the server.
+    ///
+    pub fn serve(&self) -> Result<()> {
+        self.server.serve(
+            |path| {
+                self.adapters
+                    .iter()
+                    .find(|adapter| path.trim_matches('/') == adapter.name())
+            },
+            |adapter, body| self.apply(adapter.as_ref(), body),
+        )
+    }
+
+    fn apply(&self, adapter: &dyn Adapter, body: &str) -> Result<Option<String>> {
+        let payload: Value = serde_json::from_str(body)?;
+        let event = match adapter.event(&payload)? {
+            None => {
+                debug!("Ignoring payload not handled by adapter {}", adapter.name());
+                return Ok(None);
+            }
+            Some(event) => event,
+        };
+        match matching_command(&self.rules, &event) {
+            None => {
+                info!(
+                    "No rule for {} '{}' {}",
+                    event.source, event.name, event.status
+                );
+                Ok(None)
+            }
+            Some(command) => {
+                info!(
+                    "Applying '{}' for {} '{}' {}",
+                    command, event.source, event.name, event.status
+                );
+                command.clone().apply(&self.device)?;
+                Ok(Some(command.to_string()))
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn string_field<'a>(object: &'a Value, field: &str) -> Result<&'a str> {
+    object
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or(Error::InvalidRequest)
+}
+
+fn severity_rank(severity: &str) -> usize {
+    SEVERITIES
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(severity))
+        .unwrap_or(SEVERITIES.len())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "webhook"))]
+mod tests {
+    use super::{default_rules, Adapter, Event, EventRule, ReceiverServer, Status};
+    use crate::error::Result;
+    use crate::mock::MockDevice;
+    use serde_json::Value;
+
+    const GITHUB_WORKFLOW_RUN: &str = include_str!("../tests/fixtures/github_workflow_run.json");
+    const GITHUB_CHECK_SUITE: &str = include_str!("../tests/fixtures/github_check_suite.json");
+    const GITHUB_PING: &str = include_str!("../tests/fixtures/github_ping.json");
+    const GITLAB_PIPELINE: &str = include_str!("../tests/fixtures/gitlab_pipeline.json");
+    const ALERTMANAGER_FIRING: &str = include_str!("../tests/fixtures/alertmanager_firing.json");
+    const ALERTMANAGER_RESOLVED: &str =
+        include_str!("../tests/fixtures/alertmanager_resolved.json");
+
+    struct Deploy;
+
+    impl Adapter for Deploy {
+        fn name(&self) -> &str {
+            "deploy"
+        }
+
+        fn event(&self, payload: &Value) -> Result<Option<Event>> {
+            Ok(payload["ok"].as_bool().map(|ok| {
+                let status = if ok { Status::Success } else { Status::Failure };
+                Event::new(self.name(), "deploy", status, None)
+            }))
+        }
+    }
+
+    fn start(mock: &MockDevice, rules: Vec<EventRule>) -> String {
+        let mut server = ReceiverServer::bind("127.0.0.1:0", mock.clone(), rules).unwrap();
+        server.add_adapter(Box::new(Deploy));
+        let url = server.url();
+        let _ = std::thread::spawn(move || server.serve());
+        url
+    }
+
+    fn post(url: &str, path: &str, body: &str) -> (u16, String) {
+        let response = reqwest::blocking::Client::new()
+            .post(format!("{}/{}", url, path))
+            .body(body.to_string())
+            .send()
+            .unwrap();
+        (response.status().as_u16(), response.text().unwrap())
+    }
+
+    #[test]
+    fn test_rule_round_trip() {
+        for text in &[
+            "alertmanager firing:critical pattern police 255",
+            "* failure strobe red 10 255",
+            "gitlab * solid green",
+        ] {
+            let rule: EventRule = text.parse().unwrap();
+            assert_eq!(&rule.to_string(), text);
+        }
+        assert!("* broken red".parse::<EventRule>().is_err());
+        assert!("* failure".parse::<EventRule>().is_err());
+        assert_eq!(default_rules().len(), 6);
+    }
+
+    #[test]
+    fn test_fixtures_with_default_rules() {
+        let mock = MockDevice::default();
+        let url = start(&mock, default_rules());
+
+        assert_eq!(
+            post(&url, "github", GITHUB_WORKFLOW_RUN),
+            (200, "strobe red 10 255".to_string())
+        );
+        assert_eq!(
+            post(&url, "github", GITHUB_CHECK_SUITE),
+            (200, "solid green".to_string())
+        );
+        assert_eq!(post(&url, "github", GITHUB_PING).0, 204);
+        assert_eq!(
+            post(&url, "gitlab", GITLAB_PIPELINE),
+            (200, "solid yellow".to_string())
+        );
+        assert_eq!(
+            post(&url, "alertmanager", ALERTMANAGER_FIRING),
+            (200, "pattern police 255".to_string())
+        );
+        assert_eq!(
+            post(&url, "alertmanager", ALERTMANAGER_RESOLVED),
+            (200, "solid green".to_string())
+        );
+        assert_eq!(
+            post(&url, "deploy", r#"{"ok": false}"#),
+            (200, "strobe red 10 255".to_string())
+        );
+
+        assert_eq!(
+            mock.calls(),
+            vec![
+                "strobe red 10 255",
+                "solid green",
+                "solid yellow",
+                "pattern police 255",
+                "solid green",
+                "strobe red 10 255",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_configured_rules_and_bad_requests() {
+        let mock = MockDevice::default();
+        let rules = vec![
+            "github failure pattern traffic lights 3".parse().unwrap(),
+            "alertmanager firing:warning blue".parse().unwrap(),
+        ];
+        let url = start(&mock, rules);
+
+        assert_eq!(
+            post(&url, "github", GITHUB_WORKFLOW_RUN),
+            (200, "pattern traffic lights 3".to_string())
+        );
+        // The most severe firing alert is critical, which has no rule.
+        assert_eq!(post(&url, "alertmanager", ALERTMANAGER_FIRING).0, 204);
+        assert_eq!(post(&url, "gitlab", "not json").0, 400);
+        assert_eq!(post(&url, "alertmanager", r#"{"status": "firing"}"#).0, 400);
+        assert_eq!(post(&url, "jenkins", "{}").0, 404);
+
+        assert_eq!(mock.calls(), vec!["pattern traffic lights 3"]);
+    }
+}
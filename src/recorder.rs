@@ -0,0 +1,395 @@
+/*!
+Recording the commands sent to a device, and replaying them with their original timing.
+
+A [Recorder] wraps any device, forwarding each call to it and writing the command to a JSON Lines
+file, one object per line. Each object has the time since recording started, `at_ms`, the wall
+clock time, `time_ms`, in milliseconds since the Unix epoch, the device identifier, and the
+command in its text form, prefixed by the targeted LED if any; a command that failed also has the
+error.
+
+```json
+{"at_ms":0,"time_ms":1760781600000,"device":"terminal","command":"solid red"}
+{"at_ms":1500,"time_ms":1760781601500,"device":"terminal","command":"led front strobe red 10 255"}
+```
+
+A recording is read with [read_recording], and [replay] re-issues its commands to any device,
+with the same intervals between them divided by the replay rate; [replay_targeted] also targets
+the LED recorded with each command.
+
+# Example
+
+```rust,ignore
+use luxafor::recorder::{read_recording, replay_targeted, Recorder};
+use luxafor::terminal::TerminalDevice;
+use luxafor::{Device, SolidColor};
+use luxafor::error::Result;
+use std::fs::File;
+use std::io::BufReader;
+
+fn record_and_replay() -> Result<()> {
+    let device = Recorder::create(TerminalDevice::new(), "incident.jsonl".as_ref())?;
+    device.set_solid_color(SolidColor::Red)?;
+    let entries = read_recording(BufReader::new(File::open("incident.jsonl")?))?;
+    let _ = replay_targeted(&entries, &mut TerminalDevice::new(), 2.0)?;
+    Ok(())
+}
+```
+
+*/
+
+use crate::builder::TargetedCommand;
+use crate::error::{Error, Result};
+use crate::{
+    Capabilities, Command, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice,
+    Wave,
+};
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufRead, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A device that records each command sent to the device it wraps, see the
+/// [module documentation](index.html).
+///
+#[allow(missing_debug_implementations)]
+pub struct Recorder<D> {
+    device: D,
+    writer: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+    led: Option<SpecificLED>,
+}
+
+///
+/// One command read from a recording.
+///
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// The time since recording started.
+    pub at: Duration,
+    /// The wall clock time the command was sent.
+    pub time: SystemTime,
+    /// The identifier of the device the command was sent to.
+    pub device: String,
+    /// The command, and the LED it targeted if any.
+    pub command: TargetedCommand,
+    /// The error returned by the device, if the command failed.
+    pub error: Option<String>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Read the entries of a recording, returning an error with the line number of the first line
+/// that is not a recorded command.
+///
+pub fn read_recording(reader: impl BufRead) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)?;
+        entries.push(parse_entry(&value).ok_or(Error::InvalidRecording { line: index + 1 })?);
+    }
+    Ok(entries)
+}
+
+///
+/// Re-issue the commands in `entries` to `device`, keeping the original intervals between them
+/// divided by `rate`, so that a rate of `2.0` replays twice as fast. Returns the number of
+/// commands replayed. A recording that targets LEDs cannot be replayed to a device that is not
+/// targeted, this fails with `Error::InvalidLED` before any command is replayed; use
+/// [replay_targeted] instead.
+///
+pub fn replay<D: Device + ?Sized>(entries: &[Entry], device: &D, rate: f64) -> Result<usize> {
+    if let Some(entry) = entries.iter().find(|entry| entry.command.led.is_some()) {
+        error!(
+            "The recording targets LEDs, which device '{}' cannot, starting with '{}'",
+            device.id(),
+            entry.command
+        );
+        return Err(Error::InvalidLED.with_context(&device.id(), Some(&entry.command.to_string())));
+    }
+    play(entries, rate, &device.id(), |entry| {
+        entry.command.command.clone().apply(device)
+    })
+}
+
+///
+/// Re-issue the commands in `entries` to `device`, as [replay], targeting the LED recorded with
+/// each command.
+///
+pub fn replay_targeted<D: TargetedDevice + ?Sized>(
+    entries: &[Entry],
+    device: &mut D,
+    rate: f64,
+) -> Result<usize> {
+    let device_id = device.id();
+    play(entries, rate, &device_id, |entry| {
+        entry.command.clone().apply(device)
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<D: Device> Recorder<D> {
+    ///
+    /// Wrap `device`, recording to a new file at `path`; any existing file is replaced.
+    ///
+    pub fn create(device: D, path: &Path) -> Result<Self> {
+        info!("Recording device '{}' to {:?}", device.id(), path);
+        Ok(Self::with_writer(
+            device,
+            LineWriter::new(File::create(path)?),
+        ))
+    }
+
+    ///
+    /// Wrap `device`, recording to `writer`.
+    ///
+    pub fn with_writer(device: D, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            device,
+            writer: Mutex::new(Box::new(writer)),
+            started: Instant::now(),
+            led: None,
+        }
+    }
+
+    ///
+    /// Return the wrapped device.
+    ///
+    pub fn inner(&self) -> &D {
+        &self.device
+    }
+
+    fn record(&self, command: Command) -> Result<()> {
+        let at = self.started.elapsed();
+        let time = SystemTime::now();
+        let command = TargetedCommand {
+            led: self.led.clone(),
+            command,
+        };
+        let result = command.command.clone().apply(&self.device);
+        let mut entry = json!({
+            "at_ms": at.as_millis() as u64,
+            "time_ms": time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            "device": self.device.id(),
+            "command": command.to_string(),
+        });
+        if let Err(e) = &result {
+            entry["error"] = Value::String(e.to_string());
+        }
+        let written = writeln!(self.writer.lock().unwrap(), "{}", entry);
+        result?;
+        Ok(written?)
+    }
+}
+
+impl<D: Device> Device for Recorder<D> {
+    fn id(&self) -> String {
+        self.device.id()
+    }
+
+    fn turn_off(&self) -> Result<()> {
+        self.record(Command::TurnOff)
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
+        self.record(Command::Solid { color })
+    }
+
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
+        self.record(Command::Fade {
+            color,
+            fade_duration,
+        })
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.record(Command::Strobe {
+            color,
+            strobe_speed,
+            repeat_count,
+        })
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.record(Command::Wave {
+            color,
+            wave_pattern,
+            wave_speed,
+            repeat_count,
+        })
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
+        self.record(Command::Pattern {
+            pattern,
+            repeat_count,
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.device.capabilities()
+    }
+
+    fn wait(&self) {
+        self.device.wait()
+    }
+}
+
+impl<D: TargetedDevice> TargetedDevice for Recorder<D> {
+    fn set_specific_led(&mut self, led: SpecificLED) -> Result<()> {
+        self.device.set_specific_led(led.clone())?;
+        self.led = Some(led);
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn play(
+    entries: &[Entry],
+    rate: f64,
+    device_id: &str,
+    mut apply: impl FnMut(&Entry) -> Result<()>,
+) -> Result<usize> {
+    if !(rate.is_finite() && rate > 0.0) {
+        return Err(Error::InvalidSpeed);
+    }
+    let first = match entries.first() {
+        Some(first) => first.at,
+        None => return Ok(0),
+    };
+    info!(
+        "Replaying {} commands to device '{}' at {}x",
+        entries.len(),
+        device_id,
+        rate
+    );
+    let started = Instant::now();
+    for entry in entries {
+        let due = started + entry.at.saturating_sub(first).div_f64(rate);
+        let wait = due.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+        debug!("Replaying '{}'", entry.command);
+        apply(entry).map_err(|e| e.with_context(device_id, Some(&entry.command.to_string())))?;
+    }
+    Ok(entries.len())
+}
+
+fn parse_entry(value: &Value) -> Option<Entry> {
+    Some(Entry {
+        at: Duration::from_millis(value["at_ms"].as_u64()?),
+        time: UNIX_EPOCH + Duration::from_millis(value["time_ms"].as_u64().unwrap_or_default()),
+        device: value["device"].as_str().unwrap_or_default().to_string(),
+        command: value["command"].as_str()?.parse().ok()?,
+        error: value["error"].as_str().map(str::to_string),
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{read_recording, replay, replay_targeted, Recorder};
+    use crate::error::Error;
+    use crate::mock::{MockDevice, MockWriter};
+    use crate::{Device, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_record_and_replay() {
+        let buffer = MockWriter::default();
+        let mock = MockDevice::default();
+        let mut device = Recorder::with_writer(mock.clone(), buffer.clone());
+        device.set_solid_color(SolidColor::Red).unwrap();
+        std::thread::sleep(Duration::from_millis(40));
+        device.set_specific_led(SpecificLED::AllFront).unwrap();
+        device
+            .set_color_strobe(SolidColor::Blue, Speed::new(10), Repeat::Forever)
+            .unwrap();
+        assert_eq!(
+            mock.calls(),
+            vec!["solid red", "led front", "strobe blue 10 forever"]
+        );
+
+        let recording = buffer.text();
+        let entries = read_recording(recording.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].device, "mock");
+        assert_eq!(
+            entries[1].command.to_string(),
+            "led front strobe blue 10 forever"
+        );
+        assert!(entries[1].at >= Duration::from_millis(40));
+
+        let mut target = MockDevice::default();
+        let started = Instant::now();
+        assert_eq!(replay_targeted(&entries, &mut target, 2.0).unwrap(), 2);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(target.calls(), mock.calls());
+
+        let untargeted = MockDevice::default();
+        let error = replay(&entries, &untargeted, 2.0).unwrap_err();
+        assert!(matches!(error.root(), Error::InvalidLED));
+        assert_eq!(error.command(), Some("led front strobe blue 10 forever"));
+        assert!(untargeted.calls().is_empty());
+    }
+
+    #[test]
+    fn test_replay_to_device() {
+        let recording = "{\"at_ms\":0,\"command\":\"solid red\"}\n\
+                         {\"at_ms\":5,\"command\":\"fade blue 10\"}\n";
+        let entries = read_recording(recording.as_bytes()).unwrap();
+        let device = MockDevice::default();
+        let target: &dyn Device = &device;
+        assert_eq!(replay(&entries, target, 1.0).unwrap(), 2);
+        assert_eq!(device.calls(), vec!["solid red", "fade blue 10"]);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let recording =
+            "{\"at_ms\":0,\"command\":\"solid red\"}\n\n{\"at_ms\":5,\"command\":\"dance\"}\n";
+        assert!(matches!(
+            read_recording(recording.as_bytes()),
+            Err(Error::InvalidRecording { line: 3 })
+        ));
+        assert!(read_recording("not json".as_bytes()).is_err());
+        assert!(matches!(
+            replay(&[], &MockDevice::default(), 0.0),
+            Err(Error::InvalidSpeed)
+        ));
+    }
+}
@@ -0,0 +1,340 @@
+/*!
+Opening devices by URI, with a registry of the schemes that can be opened.
+
+A device URI has the form `scheme:target?name=value&...`, the meaning of the target and options
+depends on the scheme. The schemes provided by this crate, when their features are enabled, are:
+
+* `usb:` opens the first USB connected light, `usb:serial=1234` the light with that serial number,
+  and `usb:path=/dev/hidraw3` the light at that HID path.
+* `webhook:2a0f2c73b72` opens the webhook connected light with that device ID, the option
+  `endpoint` sends actions to another server, as `webhook::new_device_with_endpoint`.
+* `term:` opens a `terminal::TerminalDevice`, drawing the light on standard output.
+
+Other backends can add their own schemes with [register_scheme], after which [open](../fn.open.html)
+will open their URIs. A backend whose lights can target each LED registers with
+[register_targeted_scheme] instead, so that [open_targeted_uri] can return them as a
+[TargetedDevice](../trait.TargetedDevice.html); the `usb` and `term` schemes are registered in this
+way.
+
+# Example
+
+```rust,ignore
+use luxafor::registry::{register_scheme, DeviceUri};
+use luxafor::error::Result;
+use luxafor::Device;
+
+fn main() -> Result<()> {
+    register_scheme("my-light", |uri: &DeviceUri| {
+        Ok(Box::new(my_light::connect(uri.target())?))
+    });
+    let device = luxafor::open("my-light:desk")?;
+    device.turn_off()
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{Device, TargetedDevice};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{OnceLock, RwLock};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A parsed device URI, of the form `scheme:target?name=value&...`.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceUri {
+    scheme: String,
+    target: String,
+    options: Vec<(String, String)>,
+}
+
+///
+/// A function that opens the device for a URI of the scheme it is registered for.
+///
+pub type Opener = Box<dyn Fn(&DeviceUri) -> Result<Box<dyn Device + Send>> + Send + Sync>;
+
+///
+/// A function that opens the targeted device for a URI of the scheme it is registered for.
+///
+pub type TargetedOpener =
+    Box<dyn Fn(&DeviceUri) -> Result<Box<dyn TargetedDevice + Send>> + Send + Sync>;
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+enum Registered {
+    Device(Opener),
+    Targeted(TargetedOpener),
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Register `opener` for URIs with `scheme`, replacing any existing opener for the scheme.
+///
+pub fn register_scheme<F>(scheme: &str, opener: F)
+where
+    F: Fn(&DeviceUri) -> Result<Box<dyn Device + Send>> + Send + Sync + 'static,
+{
+    debug!("Registering device URI scheme '{}'", scheme);
+    let _ = registry()
+        .write()
+        .unwrap()
+        .insert(scheme.to_lowercase(), Registered::Device(Box::new(opener)));
+}
+
+///
+/// Register `opener` for URIs with `scheme`, whose devices can target each LED, replacing any
+/// existing opener for the scheme.
+///
+pub fn register_targeted_scheme<F>(scheme: &str, opener: F)
+where
+    F: Fn(&DeviceUri) -> Result<Box<dyn TargetedDevice + Send>> + Send + Sync + 'static,
+{
+    debug!("Registering targeted device URI scheme '{}'", scheme);
+    let _ = registry().write().unwrap().insert(
+        scheme.to_lowercase(),
+        Registered::Targeted(Box::new(opener)),
+    );
+}
+
+///
+/// Return the registered schemes, in sorted order.
+///
+pub fn schemes() -> Vec<String> {
+    let mut schemes: Vec<String> = registry().read().unwrap().keys().cloned().collect();
+    schemes.sort();
+    schemes
+}
+
+///
+/// Open the device identified by `uri`, using the opener registered for its scheme.
+///
+pub fn open_uri(uri: &DeviceUri) -> Result<Box<dyn Device + Send>> {
+    with_opener(uri, |registered| match registered {
+        Registered::Device(opener) => opener(uri),
+        Registered::Targeted(opener) => Ok(opener(uri)?),
+    })
+}
+
+///
+/// Open the device identified by `uri` as a targeted device, returning `None` if its scheme was
+/// registered with [register_scheme] and so its devices cannot target LEDs.
+///
+pub fn open_targeted_uri(uri: &DeviceUri) -> Result<Option<Box<dyn TargetedDevice + Send>>> {
+    with_opener(uri, |registered| match registered {
+        Registered::Device(_) => Ok(None),
+        Registered::Targeted(opener) => opener(uri).map(Some),
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for DeviceUri {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.target)?;
+        for (i, (name, value)) in self.options.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DeviceUri {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (scheme, rest) = s.trim().split_once(':').ok_or(Error::InvalidDeviceURI)?;
+        if scheme.is_empty()
+            || !scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '.')
+        {
+            return Err(Error::InvalidDeviceURI);
+        }
+        let (target, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let options = query
+            .split('&')
+            .filter(|option| !option.is_empty())
+            .map(|option| {
+                option
+                    .split_once('=')
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .ok_or(Error::InvalidDeviceURI)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            scheme: scheme.to_lowercase(),
+            target: target.to_string(),
+            options,
+        })
+    }
+}
+
+impl DeviceUri {
+    ///
+    /// Return the scheme, in lower case.
+    ///
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    ///
+    /// Return the target, the part between the scheme and any options.
+    ///
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    ///
+    /// Return the value of the option `name`; a target of the form `name=value`, as in
+    /// `usb:serial=1234`, is also treated as an option.
+    ///
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+            .or_else(|| match self.target.split_once('=') {
+                Some((option, value)) if option == name => Some(value),
+                _ => None,
+            })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn registry() -> &'static RwLock<HashMap<String, Registered>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Registered>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        #[allow(unused_mut)]
+        let mut openers: HashMap<String, Registered> = HashMap::new();
+        #[cfg(any(feature = "usb", all(target_os = "linux", feature = "usb-hidraw")))]
+        let _ = openers.insert("usb".to_string(), Registered::Targeted(Box::new(open_usb)));
+        #[cfg(feature = "webhook")]
+        let _ = openers.insert(
+            "webhook".to_string(),
+            Registered::Device(Box::new(open_webhook)),
+        );
+        #[cfg(feature = "terminal")]
+        let _ = openers.insert(
+            "term".to_string(),
+            Registered::Targeted(Box::new(open_terminal)),
+        );
+        RwLock::new(openers)
+    })
+}
+
+// The registry stays locked while the device is opened, the opener is only borrowed from it.
+fn with_opener<T>(uri: &DeviceUri, open: impl FnOnce(&Registered) -> Result<T>) -> Result<T> {
+    let registry = registry().read().unwrap();
+    match registry.get(&uri.scheme) {
+        Some(registered) => {
+            info!("Opening device '{}'", uri);
+            open(registered)
+        }
+        None => Err(Error::UnknownScheme {
+            scheme: uri.scheme.clone(),
+        }),
+    }
+}
+
+#[cfg(any(feature = "usb", all(target_os = "linux", feature = "usb-hidraw")))]
+fn open_usb(uri: &DeviceUri) -> Result<Box<dyn TargetedDevice + Send>> {
+    Ok(Box::new(crate::usb_hid::open(uri)?))
+}
+
+#[cfg(feature = "webhook")]
+fn open_webhook(uri: &DeviceUri) -> Result<Box<dyn Device + Send>> {
+    Ok(Box::new(crate::webhook::open(uri)?))
+}
+
+#[cfg(feature = "terminal")]
+fn open_terminal(_: &DeviceUri) -> Result<Box<dyn TargetedDevice + Send>> {
+    Ok(Box::new(crate::terminal::TerminalDevice::new()))
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        open_targeted_uri, open_uri, register_scheme, register_targeted_scheme, schemes, DeviceUri,
+    };
+    use crate::error::Error;
+    use crate::mock::MockDevice;
+    use crate::SpecificLED;
+
+    #[test]
+    fn test_parse_uri() {
+        let uri: DeviceUri = "webhook:2a0f2c73b72?endpoint=http://127.0.0.1:5383/actions"
+            .parse()
+            .unwrap();
+        assert_eq!(uri.scheme(), "webhook");
+        assert_eq!(uri.target(), "2a0f2c73b72");
+        assert_eq!(
+            uri.option("endpoint"),
+            Some("http://127.0.0.1:5383/actions")
+        );
+        assert_eq!(
+            uri.to_string(),
+            "webhook:2a0f2c73b72?endpoint=http://127.0.0.1:5383/actions"
+        );
+
+        let uri: DeviceUri = "USB:serial=1234".parse().unwrap();
+        assert_eq!(uri.scheme(), "usb");
+        assert_eq!(uri.option("serial"), Some("1234"));
+        assert_eq!(uri.option("path"), None);
+        assert_eq!("usb:".parse::<DeviceUri>().unwrap().target(), "");
+
+        assert!("2a0f2c73b72".parse::<DeviceUri>().is_err());
+        assert!(":target".parse::<DeviceUri>().is_err());
+        assert!("usb:?serial".parse::<DeviceUri>().is_err());
+    }
+
+    #[test]
+    fn test_open() {
+        register_scheme("mock", |_| Ok(Box::new(MockDevice::default())));
+        assert!(schemes().contains(&"mock".to_string()));
+        assert_eq!(crate::open("mock:anything").unwrap().id(), "mock");
+        assert!(matches!(
+            crate::open("unknown:device"),
+            Err(Error::UnknownScheme { scheme }) if scheme == "unknown"
+        ));
+        assert!(open_targeted_uri(&"mock:anything".parse().unwrap())
+            .unwrap()
+            .is_none());
+
+        register_targeted_scheme("mock-targeted", |_| Ok(Box::new(MockDevice::default())));
+        let uri: DeviceUri = "mock-targeted:anything".parse().unwrap();
+        let mut device = open_targeted_uri(&uri).unwrap().unwrap();
+        device.set_specific_led(SpecificLED::AllBack).unwrap();
+        assert_eq!(open_uri(&uri).unwrap().id(), "mock");
+
+        #[cfg(feature = "webhook")]
+        {
+            let device = crate::open("webhook:2a0f2c73b72?endpoint=http://127.0.0.1:1").unwrap();
+            assert_eq!(device.id(), "2a0f2c73b72");
+            assert!(matches!(
+                crate::open("webhook:not-hex"),
+                Err(Error::InvalidDeviceID)
+            ));
+        }
+    }
+}
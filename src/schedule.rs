@@ -0,0 +1,779 @@
+/*!
+A working-hours schedule that sets the light according to the day of the week and time of day.
+
+A [Schedule] is a list of [Rule]s, each mapping a set of weekdays and a time range to a
+[Command](../enum.Command.html), and a default command used when no rule is active. Where rules
+overlap the last one listed wins, so general rules should be listed before more specific ones. The
+text form of a rule is the days, the time range, and then the command.
+
+```text
+mon-fri 09:00-17:30 green
+mon-fri 12:00-13:00 yellow
+sat,sun 22:00-02:00 pattern police 5
+```
+
+Days are a single day, a range such as `mon-fri`, a comma separated list of either, or `daily`. A
+time range whose end is not after its start, such as `22:00-02:00`, runs over midnight into the
+following day.
+
+An [Engine] applies a schedule to a device, sleeping until the next boundary, where a rule starts
+or ends, and then applying whichever command is active. Rules are evaluated against the local
+wall-clock time, so a rule starts at 09:00 on either side of a daylight saving change; a boundary
+that falls in the hour skipped when the clocks go forward takes effect at the end of that hour. A
+manual [Override] replaces the scheduled command until it expires, after which the schedule
+resumes.
+
+The engine reads the time from a [Clock]; [SystemClock] uses the local time zone of the host while
+[SimulatedClock] allows time to be set directly, for testing.
+
+# Example
+
+```rust,ignore
+use luxafor::schedule::{Engine, Schedule, SystemClock};
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::error::Result;
+
+fn working_hours() -> Result<()> {
+    let schedule = Schedule::new(
+        vec![
+            "mon-fri 09:00-17:30 green".parse()?,
+            "mon-fri 12:00-13:00 yellow".parse()?,
+        ],
+        "off".parse()?,
+    );
+    let discovery = USBDeviceDiscovery::new()?;
+    let mut engine = Engine::new(schedule, SystemClock);
+    engine.run(&discovery.device()?, None)
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{Command, Device};
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+    Weekday,
+};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A single schedule rule; the command is active on the given days between the start and end
+/// times.
+///
+#[derive(Clone, Debug)]
+pub struct Rule {
+    days: [bool; 7],
+    start: NaiveTime,
+    end: NaiveTime,
+    command: Command,
+}
+
+///
+/// A set of rules, and the default command used when no rule is active.
+///
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    rules: Vec<Rule>,
+    default: Command,
+}
+
+///
+/// A manual command that replaces the scheduled one until it expires.
+///
+#[derive(Clone, Debug)]
+pub struct Override {
+    /// The command to apply in place of the schedule.
+    pub command: Command,
+    /// The time at which the schedule resumes.
+    pub until: DateTime<Utc>,
+}
+
+///
+/// The source of time for an [Engine].
+///
+pub trait Clock {
+    /// The time zone in which schedule rules are evaluated.
+    type TimeZone: TimeZone;
+
+    /// Return the current time.
+    fn now(&self) -> DateTime<Self::TimeZone>;
+
+    /// Block until the time `until` has been reached.
+    fn sleep_until(&self, until: &DateTime<Self::TimeZone>);
+}
+
+///
+/// A clock that uses the system time, in the local time zone.
+///
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+///
+/// A clock whose time only changes when set, or when the engine sleeps; this allows a schedule
+/// to be tested without waiting.
+///
+#[derive(Debug)]
+pub struct SimulatedClock<Tz: TimeZone> {
+    now: Mutex<DateTime<Tz>>,
+}
+
+///
+/// Applies a schedule, and any override, to a device as time passes.
+///
+#[derive(Debug)]
+pub struct Engine<C: Clock> {
+    schedule: Schedule,
+    clock: C,
+    manual: Option<Override>,
+    applied: Option<String>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+// The longest time the engine sleeps before checking for overrides, or changes to the clock.
+const POLL_SECONDS: i64 = 5;
+
+const OVERRIDE_FILE_NAME: &str = "luxafor-override";
+
+const TIME_FORMAT: &str = "%H:%M";
+
+const ALL_DAYS: &str = "daily";
+
+// The longest gap in local time caused by a daylight saving change.
+const MAX_GAP_MINUTES: i64 = 180;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the override file path used when none is specified; this is `luxafor-override` in the
+/// directory named by `XDG_RUNTIME_DIR` if set, otherwise in the system temporary directory.
+///
+pub fn default_override_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join(OVERRIDE_FILE_NAME),
+        _ => std::env::temp_dir().join(OVERRIDE_FILE_NAME),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}-{} {}",
+            days_to_string(&self.days),
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT),
+            self.command
+        )
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let days = words.next().ok_or(Error::InvalidSchedule)?;
+        let (start, end) = words
+            .next()
+            .and_then(|times| times.split_once('-'))
+            .ok_or(Error::InvalidSchedule)?;
+        let command = words.collect::<Vec<&str>>().join(" ");
+        Ok(Self {
+            days: parse_days(days)?,
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+            command: command.parse()?,
+        })
+    }
+}
+
+impl Rule {
+    ///
+    /// Construct a new rule that applies `command` on each of `days` from `start` until `end`.
+    ///
+    pub fn new(days: &[Weekday], start: NaiveTime, end: NaiveTime, command: Command) -> Self {
+        let mut day_set = [false; 7];
+        for day in days {
+            day_set[day_index(*day)] = true;
+        }
+        Self {
+            days: day_set,
+            start,
+            end,
+            command,
+        }
+    }
+
+    ///
+    /// Return the command applied while this rule is active.
+    ///
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    ///
+    /// Returns `true` if this rule is active at the local time `at`.
+    ///
+    pub fn is_active(&self, at: &NaiveDateTime) -> bool {
+        let time = at.time();
+        let today = self.days[day_index(at.weekday())];
+        if self.start < self.end {
+            today && time >= self.start && time < self.end
+        } else {
+            let yesterday = self.days[day_index(at.weekday().pred())];
+            (today && time >= self.start) || (yesterday && time < self.end)
+        }
+    }
+
+    fn boundaries(&self, date: NaiveDate) -> Vec<NaiveDateTime> {
+        if self.days[day_index(date.weekday())] {
+            let end_date = if self.start < self.end {
+                date
+            } else {
+                date.succ_opt().unwrap_or(date)
+            };
+            vec![date.and_time(self.start), end_date.and_time(self.end)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Schedule {
+    ///
+    /// Construct a new schedule; `default` is applied whenever none of `rules` is active.
+    ///
+    pub fn new(rules: Vec<Rule>, default: Command) -> Self {
+        Self { rules, default }
+    }
+
+    ///
+    /// Return the rules in this schedule.
+    ///
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    ///
+    /// Return the rule active at `at`, if any; where more than one rule is active the last wins.
+    ///
+    pub fn active_rule<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> Option<&Rule> {
+        let local = at.naive_local();
+        self.rules.iter().rev().find(|rule| rule.is_active(&local))
+    }
+
+    ///
+    /// Return the command that should be applied at `at`.
+    ///
+    pub fn command_at<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> &Command {
+        self.active_rule(at)
+            .map(Rule::command)
+            .unwrap_or(&self.default)
+    }
+
+    ///
+    /// Return the first time after `after` at which a rule starts or ends, or `None` if the
+    /// schedule has no rules.
+    ///
+    pub fn next_boundary<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = after.timezone();
+        let today = after.naive_local().date();
+        // Starting from yesterday catches the end of rules that run over midnight, and every rule
+        // recurs within a week.
+        let first = today.pred_opt().unwrap_or(today);
+        first
+            .iter_days()
+            .take(9)
+            .flat_map(|date| {
+                self.rules
+                    .iter()
+                    .flat_map(move |rule| rule.boundaries(date))
+            })
+            .filter_map(|local| resolve_local(&timezone, local))
+            .filter(|at| at > after)
+            .min()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Override {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.until.to_rfc3339(), self.command)
+    }
+}
+
+impl FromStr for Override {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (until, command) = s.trim().split_once(' ').ok_or(Error::InvalidSchedule)?;
+        Ok(Self {
+            command: command.parse()?,
+            until: DateTime::parse_from_rfc3339(until)
+                .map_err(|_| Error::InvalidSchedule)?
+                .with_timezone(&Utc),
+        })
+    }
+}
+
+impl Override {
+    ///
+    /// Construct an override that applies `command` for `duration` from now.
+    ///
+    pub fn new(command: Command, duration: Duration) -> Result<Self> {
+        let duration = TimeDelta::from_std(duration).map_err(|_| Error::InvalidSchedule)?;
+        Ok(Self {
+            command,
+            until: Utc::now() + duration,
+        })
+    }
+
+    ///
+    /// Load an override from the file at `path`, returning `None` if the file does not exist.
+    ///
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Some(text.parse()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    ///
+    /// Save this override to the file at `path`, where a running engine will pick it up.
+    ///
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, format!("{}\n", self))?;
+        Ok(())
+    }
+
+    ///
+    /// Remove any override saved to the file at `path`.
+    ///
+    pub fn remove(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Clock for SystemClock {
+    type TimeZone = Local;
+
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep_until(&self, until: &DateTime<Local>) {
+        if let Ok(wait) = (*until - Local::now()).to_std() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<Tz: TimeZone> SimulatedClock<Tz> {
+    ///
+    /// Construct a new clock starting at `now`.
+    ///
+    pub fn new(now: DateTime<Tz>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    ///
+    /// Set the current time.
+    ///
+    pub fn set(&self, now: DateTime<Tz>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    ///
+    /// Move the current time forward by `by`.
+    ///
+    pub fn advance(&self, by: TimeDelta) {
+        let mut now = self.now.lock().unwrap();
+        *now = now.clone() + by;
+    }
+}
+
+impl<Tz: TimeZone> Clock for SimulatedClock<Tz> {
+    type TimeZone = Tz;
+
+    fn now(&self) -> DateTime<Tz> {
+        self.now.lock().unwrap().clone()
+    }
+
+    fn sleep_until(&self, until: &DateTime<Tz>) {
+        let mut now = self.now.lock().unwrap();
+        if *until > *now {
+            *now = until.clone();
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl<C: Clock> Engine<C> {
+    ///
+    /// Construct a new engine for `schedule`, reading the time from `clock`.
+    ///
+    pub fn new(schedule: Schedule, clock: C) -> Self {
+        Self {
+            schedule,
+            clock,
+            manual: None,
+            applied: None,
+        }
+    }
+
+    ///
+    /// Return the clock used by this engine.
+    ///
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    ///
+    /// Replace the scheduled command until the override expires.
+    ///
+    pub fn set_override(&mut self, manual: Override) {
+        info!(
+            "Overriding schedule with '{}' until {}",
+            manual.command, manual.until
+        );
+        self.manual = Some(manual);
+    }
+
+    ///
+    /// Remove any override, so that the schedule resumes at the next step.
+    ///
+    pub fn clear_override(&mut self) {
+        if self.manual.take().is_some() {
+            info!("Override cleared");
+        }
+    }
+
+    ///
+    /// Apply the command active now, if it differs from the last command applied, and return the
+    /// time at which the active command may next change.
+    ///
+    pub fn step<D: Device + ?Sized>(
+        &mut self,
+        device: &D,
+    ) -> Result<Option<DateTime<C::TimeZone>>> {
+        let now = self.clock.now();
+        if matches!(&self.manual, Some(manual) if manual.until <= now) {
+            info!("Override expired, resuming schedule");
+            self.manual = None;
+        }
+
+        let command = match &self.manual {
+            Some(manual) => &manual.command,
+            None => self.schedule.command_at(&now),
+        };
+        let text = command.to_string();
+        if self.applied.as_ref() != Some(&text) {
+            info!("Applying '{}' at {}", text, now.naive_local());
+            command.clone().apply(device)?;
+            self.applied = Some(text);
+        }
+
+        let next = self.schedule.next_boundary(&now);
+        let expiry = self
+            .manual
+            .as_ref()
+            .map(|manual| manual.until.with_timezone(&now.timezone()));
+        Ok(match (next, expiry) {
+            (Some(next), Some(expiry)) => Some(next.min(expiry)),
+            (next, expiry) => next.or(expiry),
+        })
+    }
+
+    ///
+    /// Run the schedule until the process is terminated, or the device fails. If `override_path`
+    /// is provided the override saved there, if any, is loaded before each step.
+    ///
+    pub fn run<D: Device + ?Sized>(
+        &mut self,
+        device: &D,
+        override_path: Option<&Path>,
+    ) -> Result<()> {
+        loop {
+            if let Some(path) = override_path {
+                match Override::load(path) {
+                    Ok(Some(manual)) if manual.until > self.clock.now() => {
+                        if self.manual.as_ref().map(|current| current.to_string())
+                            != Some(manual.to_string())
+                        {
+                            self.set_override(manual)
+                        }
+                    }
+                    Ok(Some(_)) => {
+                        Override::remove(path)?;
+                        self.clear_override();
+                    }
+                    Ok(None) => self.clear_override(),
+                    Err(e) => warn!("Ignoring override file {:?}: {}", path, e),
+                }
+            }
+            let next = self.step(device)?;
+            let poll_at = self.clock.now() + TimeDelta::seconds(POLL_SECONDS);
+            let wake_at = match next {
+                Some(next) if next < poll_at => next,
+                _ => poll_at,
+            };
+            self.clock.sleep_until(&wake_at);
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn day_index(day: Weekday) -> usize {
+    day.num_days_from_monday() as usize
+}
+
+fn parse_days(s: &str) -> Result<[bool; 7]> {
+    let mut days = [false; 7];
+    if s.eq_ignore_ascii_case(ALL_DAYS) {
+        return Ok([true; 7]);
+    }
+    for part in s.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let first = Weekday::from_str(first).map_err(|_| Error::InvalidSchedule)?;
+        let last = Weekday::from_str(last).map_err(|_| Error::InvalidSchedule)?;
+        let mut day = first;
+        days[day_index(day)] = true;
+        while day != last {
+            day = day.succ();
+            days[day_index(day)] = true;
+        }
+    }
+    Ok(days)
+}
+
+fn days_to_string(days: &[bool; 7]) -> String {
+    if days.iter().all(|day| *day) {
+        return ALL_DAYS.to_string();
+    }
+    let name = |index: usize| {
+        Weekday::try_from(index as u8)
+            .map(|day| day.to_string().to_lowercase())
+            .unwrap_or_default()
+    };
+    let mut parts = Vec::new();
+    let mut index = 0;
+    while index < days.len() {
+        if days[index] {
+            let first = index;
+            while index + 1 < days.len() && days[index + 1] {
+                index += 1;
+            }
+            if index == first {
+                parts.push(name(first));
+            } else {
+                parts.push(format!("{}-{}", name(first), name(index)));
+            }
+        }
+        index += 1;
+    }
+    parts.join(",")
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, TIME_FORMAT).map_err(|_| Error::InvalidSchedule)
+}
+
+fn resolve_local<Tz: TimeZone>(timezone: &Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    // A local time skipped when the clocks go forward does not exist, so the first minute after
+    // the gap is used instead; a repeated local time uses its first occurrence.
+    (0..=MAX_GAP_MINUTES)
+        .map(|minutes| local + TimeDelta::minutes(minutes))
+        .find_map(|local| timezone.from_local_datetime(&local).earliest())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Engine, Override, Rule, Schedule, SimulatedClock};
+    use crate::mock::MockDevice;
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+    use chrono_tz::Europe::London;
+    use chrono_tz::Tz;
+
+    fn working_hours() -> Schedule {
+        Schedule::new(
+            vec![
+                "mon-fri 09:00-17:30 green".parse().unwrap(),
+                "mon-fri 12:00-13:00 yellow".parse().unwrap(),
+                "sat 22:00-02:00 pattern police 5".parse().unwrap(),
+            ],
+            "off".parse().unwrap(),
+        )
+    }
+
+    fn london(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Tz> {
+        London
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rule_round_trip() {
+        for text in &[
+            "mon-fri 09:00-17:30 solid green",
+            "mon,wed,fri-sun 22:00-02:00 pattern police 5",
+            "daily 00:00-00:00 off",
+        ] {
+            let rule: Rule = text.parse().unwrap();
+            assert_eq!(&rule.to_string(), text);
+        }
+        assert_eq!(
+            "sat-mon 08:00-09:00 red"
+                .parse::<Rule>()
+                .unwrap()
+                .to_string(),
+            "mon,sat-sun 08:00-09:00 solid red"
+        );
+        assert!("mon-fri 09:00 green".parse::<Rule>().is_err());
+        assert!("someday 09:00-10:00 green".parse::<Rule>().is_err());
+        assert!("mon 09:00-25:00 green".parse::<Rule>().is_err());
+        assert!("mon 09:00-10:00".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_command_at() {
+        let schedule = working_hours();
+        let command_at = |at| schedule.command_at(&at).to_string();
+        // Wednesday 2026-06-17
+        assert_eq!(command_at(london(2026, 6, 17, 8, 59)), "off");
+        assert_eq!(command_at(london(2026, 6, 17, 9, 0)), "solid green");
+        assert_eq!(command_at(london(2026, 6, 17, 12, 30)), "solid yellow");
+        assert_eq!(command_at(london(2026, 6, 17, 13, 0)), "solid green");
+        assert_eq!(command_at(london(2026, 6, 17, 17, 30)), "off");
+        // Saturday night into Sunday morning
+        assert_eq!(command_at(london(2026, 6, 20, 12, 0)), "off");
+        assert_eq!(command_at(london(2026, 6, 20, 23, 0)), "pattern police 5");
+        assert_eq!(command_at(london(2026, 6, 21, 1, 59)), "pattern police 5");
+        assert_eq!(command_at(london(2026, 6, 21, 2, 0)), "off");
+    }
+
+    #[test]
+    fn test_next_boundary_over_weekend_and_dst() {
+        let schedule = working_hours();
+        // Friday evening, the clocks go forward early on Sunday 2026-03-29.
+        let next = schedule.next_boundary(&london(2026, 3, 27, 18, 0)).unwrap();
+        assert_eq!(next, london(2026, 3, 28, 22, 0));
+        let next = schedule.next_boundary(&next).unwrap();
+        assert_eq!(next, london(2026, 3, 29, 2, 0));
+        let next = schedule.next_boundary(&next).unwrap();
+        assert_eq!(next, london(2026, 3, 30, 9, 0));
+        assert_eq!(
+            next.with_timezone(&Utc),
+            Utc.with_ymd_and_hms(2026, 3, 30, 8, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_boundary_in_dst_gap() {
+        let schedule = Schedule::new(
+            vec!["daily 01:30-03:00 red".parse().unwrap()],
+            "off".parse().unwrap(),
+        );
+        // 01:30 does not exist on 2026-03-29, the rule starts when the clocks reach 02:00.
+        let next = schedule.next_boundary(&london(2026, 3, 29, 0, 30)).unwrap();
+        assert_eq!(
+            next.with_timezone(&Utc),
+            Utc.with_ymd_and_hms(2026, 3, 29, 1, 0, 0).unwrap()
+        );
+        assert_eq!(schedule.command_at(&next).to_string(), "solid red");
+    }
+
+    #[test]
+    fn test_engine_with_simulated_clock() {
+        let device = MockDevice::default();
+        let clock = SimulatedClock::new(london(2026, 6, 17, 8, 0));
+        let mut engine = Engine::new(working_hours(), clock);
+
+        let mut wake_times = Vec::new();
+        while engine.clock().now() < london(2026, 6, 18, 0, 0) {
+            let next = engine.step(&device).unwrap().unwrap();
+            wake_times.push(next.format("%H:%M").to_string());
+            engine.clock().sleep_until(&next);
+        }
+        assert_eq!(
+            wake_times,
+            vec!["09:00", "12:00", "13:00", "17:30", "09:00"]
+        );
+        assert_eq!(
+            device.calls(),
+            vec!["off", "solid green", "solid yellow", "solid green", "off"]
+        );
+    }
+
+    #[test]
+    fn test_engine_override_expires() {
+        let device = MockDevice::default();
+        let clock = SimulatedClock::new(london(2026, 6, 17, 10, 0));
+        let mut engine = Engine::new(working_hours(), clock);
+        let _ = engine.step(&device).unwrap();
+
+        engine.set_override(Override {
+            command: "strobe red 10 255".parse().unwrap(),
+            until: london(2026, 6, 17, 10, 30).with_timezone(&Utc),
+        });
+        let next = engine.step(&device).unwrap().unwrap();
+        assert_eq!(next, london(2026, 6, 17, 10, 30));
+
+        engine.clock().advance(TimeDelta::minutes(10));
+        let _ = engine.step(&device).unwrap();
+        engine.clock().sleep_until(&next);
+        let next = engine.step(&device).unwrap().unwrap();
+        assert_eq!(next, london(2026, 6, 17, 12, 0));
+        assert_eq!(
+            device.calls(),
+            vec!["solid green", "strobe red 10 255", "solid green"]
+        );
+    }
+
+    #[test]
+    fn test_override_round_trip() {
+        let text = "2026-06-17T10:30:00+00:00 strobe red 10 255";
+        let manual: Override = text.parse().unwrap();
+        assert_eq!(manual.to_string(), text);
+        assert!("tomorrow solid red".parse::<Override>().is_err());
+    }
+}
@@ -0,0 +1,238 @@
+/*!
+Timed sequences of values, played in the background, used by the devices that animate commands
+themselves, such as `emulated::Emulated` and `terminal::TerminalDevice`.
+
+A [Sequencer] runs at most one sequence at a time. Each call to [Sequencer::show] or
+[Sequencer::play] cancels the running sequence, and every value, including those shown by a
+sequence, is shown while holding the sequencer's generation lock; a sequence checks that it has not
+been cancelled within the same lock, so a stale value can never overwrite one shown by a later
+call.
+
+*/
+
+use crate::error::Result;
+use crate::Repeat;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Runs one sequence at a time in the background, see the [module documentation](index.html).
+///
+#[derive(Debug, Default)]
+pub(crate) struct Sequencer {
+    generation: Arc<Generation>,
+    running: Mutex<Option<JoinHandle<()>>>,
+}
+
+///
+/// One value in a sequence, and how long it is shown before the next.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Step<T> {
+    pub(crate) value: T,
+    pub(crate) hold: Duration,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+struct Generation {
+    current: Mutex<u64>,
+    changed: Condvar,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the color `fraction` of the way from `from` to `to`.
+///
+pub(crate) fn blend(from: (u8, u8, u8), to: (u8, u8, u8), fraction: f64) -> (u8, u8, u8) {
+    let channel = |from: u8, to: u8| {
+        (f64::from(from) + (f64::from(to) - f64::from(from)) * fraction).round() as u8
+    };
+    (
+        channel(from.0, to.0),
+        channel(from.1, to.1),
+        channel(from.2, to.2),
+    )
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Sequencer {
+    ///
+    /// Cancel any running sequence, and call `show` before any other value can be shown.
+    ///
+    pub(crate) fn show<R>(&self, show: impl FnOnce() -> R) -> R {
+        let mut current = self.generation.current.lock().unwrap();
+        *current += 1;
+        self.generation.changed.notify_all();
+        show()
+    }
+
+    ///
+    /// Cancel any running sequence, and play `cycle` `repeat_count` times in the background,
+    /// followed by `last` if the sequence completes. The first value is shown before this
+    /// returns, any error showing a later value stops the sequence and is logged against `name`.
+    ///
+    pub(crate) fn play<T: Send + Sync + 'static>(
+        &self,
+        name: String,
+        cycle: Vec<Step<T>>,
+        repeat_count: Repeat,
+        last: Option<T>,
+        show: impl Fn(&T) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let mut current = self.generation.current.lock().unwrap();
+        *current += 1;
+        self.generation.changed.notify_all();
+        let generation = *current;
+        let first = match cycle.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        show(&first.value)?;
+        drop(current);
+
+        let shared = self.generation.clone();
+        let handle = std::thread::spawn(move || {
+            let play = |steps: &[Step<T>]| shared.play(generation, &name, steps, &show);
+            if !shared.sleep(generation, cycle[0].hold) || !play(&cycle[1..]) {
+                return;
+            }
+            let mut remaining = match repeat_count {
+                Repeat::Times(count) => Some(count.get() - 1),
+                Repeat::Forever => None,
+            };
+            while remaining.is_none_or(|count| count > 0) {
+                if !play(&cycle) {
+                    return;
+                }
+                remaining = remaining.map(|count| count - 1);
+            }
+            if let Some(value) = last {
+                let _ = play(&[Step {
+                    value,
+                    hold: Duration::ZERO,
+                }]);
+            }
+        });
+        let _ = self.running.lock().unwrap().replace(handle);
+        Ok(())
+    }
+
+    ///
+    /// Block until any running sequence has completed, or been cancelled.
+    ///
+    pub(crate) fn wait(&self) {
+        let handle = self.running.lock().unwrap().take();
+        if let Some(handle) = handle {
+            let _ = handle.join();
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Generation {
+    fn sleep(&self, generation: u64, duration: Duration) -> bool {
+        let current = self.current.lock().unwrap();
+        let (current, _) = self
+            .changed
+            .wait_timeout_while(current, duration, |current| *current == generation)
+            .unwrap();
+        *current == generation
+    }
+
+    fn play<T>(
+        &self,
+        generation: u64,
+        name: &str,
+        steps: &[Step<T>],
+        show: &impl Fn(&T) -> Result<()>,
+    ) -> bool {
+        for step in steps {
+            {
+                let current = self.current.lock().unwrap();
+                if *current != generation {
+                    return false;
+                }
+                if let Err(e) = show(&step.value) {
+                    warn!("Stopping sequence on device '{}': {}", name, e);
+                    return false;
+                }
+            }
+            if !self.sleep(generation, step.hold) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{Sequencer, Step};
+    use crate::Repeat;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn test_play_and_cancel() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let record = |shown: &Arc<Mutex<Vec<u8>>>| {
+            let shown = shown.clone();
+            move |value: &u8| {
+                shown.lock().unwrap().push(*value);
+                Ok(())
+            }
+        };
+        let step = |value| Step {
+            value,
+            hold: Duration::from_millis(1),
+        };
+        let sequencer = Sequencer::default();
+        sequencer
+            .play(
+                "test".to_string(),
+                vec![step(1), step(2)],
+                Repeat::times(2).unwrap(),
+                Some(3),
+                record(&shown),
+            )
+            .unwrap();
+        sequencer.wait();
+        assert_eq!(*shown.lock().unwrap(), vec![1, 2, 1, 2, 3]);
+
+        shown.lock().unwrap().clear();
+        sequencer
+            .play(
+                "test".to_string(),
+                vec![step(4), step(5)],
+                Repeat::Forever,
+                None,
+                record(&shown),
+            )
+            .unwrap();
+        sequencer.show(|| shown.lock().unwrap().push(6));
+        sequencer.wait();
+        let shown = shown.lock().unwrap();
+        assert_eq!(shown.first(), Some(&4));
+        assert_eq!(shown.last(), Some(&6));
+    }
+}
@@ -0,0 +1,294 @@
+/*!
+A local HTTP server that accepts the Luxafor webhook API and applies each action to a [Device].
+
+The server accepts the same endpoints and JSON payloads as the Luxafor cloud API, so that any tool
+that can call a Luxafor webhook can instead target a locally attached light, without the need for
+an internet connection.
+
+# Endpoints
+
+All endpoints accept a `POST` request with a JSON body of the form shown below, relative to the
+base path `/webhook/v1/actions`.
+
+| Endpoint       | Action Fields                                    | Device Method       |
+|----------------|--------------------------------------------------|---------------------|
+| `/solid_color` | `color`, `custom_color`                          | `set_solid_color`   |
+| `/blink`       | `color`, `custom_color`, `repeat`                | `set_color_strobe`  |
+| `/pattern`     | `pattern`                                        | `set_pattern`       |
+
+```json
+{
+  "userId": "2a0f2c73b72",
+  "actionFields": {
+    "color": "custom",
+    "custom_color": "ff8000"
+  }
+}
+```
+
+The `color` field is either one of the named colors, or `custom` in which case `custom_color`
+holds the six digit hex value. The `repeat` field is a count, `1..=255`, or `0` to repeat
+forever. The `userId` field is required, but is only used for logging as the server drives a
+single device.
+
+# Example
+
+```rust,ignore
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::server::WebhookServer;
+use luxafor::error::Result;
+
+fn serve_local_light() -> Result<()> {
+    let discovery = USBDeviceDiscovery::new()?;
+    let server = WebhookServer::bind("127.0.0.1:5383", discovery.device()?)?;
+    println!("Serving webhooks at {}", server.endpoint());
+    server.serve()
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::http::HttpServer;
+use crate::{Device, Pattern, Repeat, SolidColor, Speed};
+use serde_json::Value;
+use std::num::NonZeroU8;
+use std::str::FromStr;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A server that implements the Luxafor webhook API for a single device.
+///
+#[allow(missing_debug_implementations)]
+pub struct WebhookServer<D: Device> {
+    server: HttpServer,
+    device: D,
+}
+
+///
+/// The default address used by the `lux serve` command.
+///
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:5383";
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const API_V1_PATH: &str = "/webhook/v1/actions";
+
+const ACTION_SOLID_COLOR: &str = "solid_color";
+const ACTION_BLINK: &str = "blink";
+const ACTION_PATTERN: &str = "pattern";
+
+const DEFAULT_STROBE_SPEED: Speed = Speed::DEFAULT_STROBE;
+const DEFAULT_REPEAT_COUNT: Repeat = Repeat::Times(NonZeroU8::MAX);
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<D: Device> WebhookServer<D> {
+    ///
+    /// Bind a new server to `address`, any actions received will be applied to `device`. Binding
+    /// to port `0` will allocate a free port, use [`endpoint`](#method.endpoint) to determine the
+    /// actual address.
+    ///
+    pub fn bind(address: &str, device: D) -> Result<Self> {
+        let server = HttpServer::bind(address, "Webhook server", &device.id())?;
+        Ok(Self { server, device })
+    }
+
+    ///
+    /// Return the base URL of the webhook API served, suitable for use with
+    /// `webhook::new_device_with_endpoint`.
+    ///
+    pub fn endpoint(&self) -> String {
+        format!("{}{}", self.server.url(), API_V1_PATH)
+    }
+
+    ///
+    /// Serve requests until the process is terminated, or the underlying socket fails. A failure
+    /// reading, or responding to, a single request is logged and does not stop the server.
+    ///
+    pub fn serve(&self) -> Result<()> {
+        self.server.serve(
+            |path| action_name(path).map(str::to_string),
+            |action, body| self.apply(&action, body).map(|()| Some(String::new())),
+        )
+    }
+
+    fn apply(&self, action: &str, body: &str) -> Result<()> {
+        let body: Value = serde_json::from_str(body)?;
+        let user_id = body
+            .get("userId")
+            .and_then(Value::as_str)
+            .ok_or(Error::InvalidDeviceID)?;
+        let fields = body.get("actionFields").ok_or(Error::InvalidRequest)?;
+        info!("Applying action {} for user '{}'", action, user_id);
+        match action {
+            ACTION_SOLID_COLOR => self.device.set_solid_color(color_field(fields)?),
+            ACTION_BLINK => {
+                let repeat_count = match fields.get("repeat") {
+                    None => DEFAULT_REPEAT_COUNT,
+                    Some(repeat) => repeat
+                        .as_u64()
+                        .and_then(|v| u8::try_from(v).ok())
+                        .map(Repeat::from_firmware)
+                        .ok_or(Error::InvalidRequest)?,
+                };
+                self.device.set_color_strobe(
+                    color_field(fields)?,
+                    DEFAULT_STROBE_SPEED,
+                    repeat_count,
+                )
+            }
+            ACTION_PATTERN => {
+                let pattern = fields
+                    .get("pattern")
+                    .and_then(Value::as_str)
+                    .ok_or(Error::InvalidPattern)?;
+                self.device
+                    .set_pattern(Pattern::from_str(pattern)?, DEFAULT_REPEAT_COUNT)
+            }
+            _ => Err(Error::UnsupportedCommand),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn action_name(path: &str) -> Option<&str> {
+    path.strip_prefix(API_V1_PATH)
+        .and_then(|action| action.strip_prefix('/'))
+        .map(|action| action.trim_end_matches('/'))
+}
+
+fn color_field(fields: &Value) -> Result<SolidColor> {
+    match fields.get("color").and_then(Value::as_str) {
+        Some("custom") => fields
+            .get("custom_color")
+            .and_then(Value::as_str)
+            .ok_or(Error::InvalidColor)
+            .and_then(SolidColor::from_str),
+        Some(color) => SolidColor::from_str(color),
+        None => Err(Error::InvalidColor),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "webhook"))]
+mod tests {
+    use super::WebhookServer;
+    use crate::mock::MockDevice;
+    use crate::webhook::new_device_with_endpoint;
+    use crate::{Device, Pattern, Repeat, SolidColor, Speed};
+
+    #[test]
+    fn test_webhook_device_against_server() {
+        let mock = MockDevice::default();
+        let server = WebhookServer::bind("127.0.0.1:0", mock.clone()).unwrap();
+        let endpoint = server.endpoint();
+        let _ = std::thread::spawn(move || server.serve());
+
+        let device = new_device_with_endpoint("2a0f2c73b72", &endpoint).unwrap();
+        device.set_solid_color(SolidColor::Red).unwrap();
+        device
+            .set_solid_color(SolidColor::Custom {
+                red: 0xff,
+                green: 0x80,
+                blue: 0x01,
+            })
+            .unwrap();
+        device
+            .set_color_strobe(SolidColor::Blue, Speed::new(20), Repeat::times(5).unwrap())
+            .unwrap();
+        device
+            .set_pattern(Pattern::Police, Repeat::times(3).unwrap())
+            .unwrap();
+        device.turn_off().unwrap();
+
+        assert_eq!(
+            mock.calls(),
+            vec![
+                "solid red",
+                "solid ff8001",
+                "strobe blue 10 5",
+                "pattern police 255",
+                "solid 000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strobe_forever_against_server() {
+        let mock = MockDevice::default();
+        let server = WebhookServer::bind("127.0.0.1:0", mock.clone()).unwrap();
+        let endpoint = server.endpoint();
+        let _ = std::thread::spawn(move || server.serve());
+
+        let device = new_device_with_endpoint("2a0f2c73b72", &endpoint).unwrap();
+        device
+            .set_color_strobe(SolidColor::Red, Speed::new(10), Repeat::Forever)
+            .unwrap();
+        device
+            .set_color_strobe(
+                SolidColor::Custom {
+                    red: 0xff,
+                    green: 0x80,
+                    blue: 0x01,
+                },
+                Speed::new(10),
+                Repeat::Forever,
+            )
+            .unwrap();
+        assert_eq!(
+            mock.calls(),
+            vec!["strobe red 10 forever", "strobe ff8001 10 forever"]
+        );
+    }
+
+    #[test]
+    fn test_server_rejects_bad_requests() {
+        let server = WebhookServer::bind("127.0.0.1:0", MockDevice::default()).unwrap();
+        let endpoint = server.endpoint();
+        let _ = std::thread::spawn(move || server.serve());
+
+        let client = reqwest::blocking::Client::new();
+        let post = |action: &str, body: &str| {
+            client
+                .post(format!("{}/{}", endpoint, action))
+                .body(body.to_string())
+                .send()
+                .unwrap()
+                .status()
+                .as_u16()
+        };
+        assert_eq!(post("solid_color", "not json"), 400);
+        assert_eq!(
+            post("solid_color", r#"{"actionFields":{"color":"red"}}"#),
+            400
+        );
+        assert_eq!(
+            post(
+                "solid_color",
+                r#"{"userId":"1","actionFields":{"color":"mauve"}}"#
+            ),
+            400
+        );
+        assert_eq!(
+            post(
+                "rainbow",
+                r#"{"userId":"1","actionFields":{"color":"red"}}"#
+            ),
+            404
+        );
+    }
+}
@@ -0,0 +1,216 @@
+/*!
+A device handle that can be shared, and used concurrently, across threads.
+
+A [SharedDevice] wraps any device in an `Arc` and a mutex; clones share the same device and each
+call holds the lock for its duration. For devices that implement
+[TargetedDevice](../trait.TargetedDevice.html) the methods [SharedDevice::apply_to] and
+[SharedDevice::apply] target an LED and apply a command while holding the lock, so that another
+thread cannot change the target between the two. The targeted LED remains the target for later
+calls, as it would for the wrapped device.
+
+# Example
+
+```rust,ignore
+use luxafor::shared::SharedDevice;
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::{Command, SolidColor, SpecificLED};
+use luxafor::error::Result;
+
+fn front_and_back() -> Result<()> {
+    let device = SharedDevice::new(USBDeviceDiscovery::new()?.device()?);
+    let other = device.clone();
+    let handle = std::thread::spawn(move || {
+        other.apply_to(SpecificLED::AllBack, Command::Solid { color: SolidColor::Red })
+    });
+    device.apply_to(SpecificLED::AllFront, Command::Solid { color: SolidColor::Green })?;
+    handle.join().unwrap()
+}
+```
+
+*/
+
+use crate::builder::TargetedCommand;
+use crate::error::Result;
+use crate::{
+    Capabilities, Command, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice,
+    Wave,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A thread-safe handle to a device, see the [module documentation](index.html).
+///
+#[allow(missing_debug_implementations)]
+pub struct SharedDevice<D> {
+    device: Arc<Mutex<D>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<D> Clone for SharedDevice<D> {
+    fn clone(&self) -> Self {
+        Self {
+            device: self.device.clone(),
+        }
+    }
+}
+
+impl<D: Device> SharedDevice<D> {
+    ///
+    /// Construct a new shared handle to `device`.
+    ///
+    pub fn new(device: D) -> Self {
+        Self {
+            device: Arc::new(Mutex::new(device)),
+        }
+    }
+
+    ///
+    /// Lock the device, for a sequence of calls that must not be interleaved with other threads.
+    ///
+    pub fn lock(&self) -> MutexGuard<'_, D> {
+        self.device.lock().unwrap()
+    }
+}
+
+impl<D: TargetedDevice> SharedDevice<D> {
+    ///
+    /// Target `led` and apply `command` to it, without another thread changing the target
+    /// between the two.
+    ///
+    pub fn apply_to(&self, led: SpecificLED, command: Command) -> Result<()> {
+        self.apply(TargetedCommand {
+            led: Some(led),
+            command,
+        })
+    }
+
+    ///
+    /// Apply `command`, targeting its LED first if it has one, without another thread changing
+    /// the target between the two.
+    ///
+    pub fn apply(&self, command: TargetedCommand) -> Result<()> {
+        command.apply(&mut *self.lock())
+    }
+}
+
+impl<D: Device> Device for SharedDevice<D> {
+    fn id(&self) -> String {
+        self.lock().id()
+    }
+
+    fn turn_off(&self) -> Result<()> {
+        self.lock().turn_off()
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
+        self.lock().set_solid_color(color)
+    }
+
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
+        self.lock().set_fade_to_color(color, fade_duration)
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.lock()
+            .set_color_strobe(color, strobe_speed, repeat_count)
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.lock()
+            .set_color_wave(color, wave_pattern, wave_speed, repeat_count)
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
+        self.lock().set_pattern(pattern, repeat_count)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.lock().capabilities()
+    }
+
+    fn wait(&self) {
+        self.lock().wait()
+    }
+}
+
+impl<D: TargetedDevice> TargetedDevice for SharedDevice<D> {
+    fn set_specific_led(&mut self, led: SpecificLED) -> Result<()> {
+        self.lock().set_specific_led(led)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::SharedDevice;
+    use crate::mock::MockDevice;
+    use crate::{Command, Device, SolidColor, SpecificLED};
+    use std::sync::Arc;
+
+    fn use_device(device: impl Device) -> String {
+        device.set_solid_color(SolidColor::Blue).unwrap();
+        device.id()
+    }
+
+    #[test]
+    fn test_apply_to_is_atomic() {
+        let mock = MockDevice::default();
+        let device = SharedDevice::new(mock.clone());
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let device = device.clone();
+                std::thread::spawn(move || {
+                    let (led, color) = if i % 2 == 0 {
+                        (SpecificLED::AllFront, SolidColor::Green)
+                    } else {
+                        (SpecificLED::AllBack, SolidColor::Red)
+                    };
+                    device.apply_to(led, Command::Solid { color }).unwrap();
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        let calls = mock.calls();
+        assert_eq!(calls.len(), 16);
+        for pair in calls.chunks(2) {
+            match pair[0].as_str() {
+                "led front" => assert_eq!(pair[1], "solid green"),
+                _ => assert_eq!(pair, ["led back", "solid red"]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_forwarding_impls() {
+        let mock = MockDevice::default();
+        assert_eq!(use_device(&mock), "mock");
+        assert_eq!(use_device(Box::new(mock.clone())), "mock");
+        assert_eq!(use_device(Arc::new(mock.clone())), "mock");
+        let boxed: Box<dyn Device + Send> = Box::new(mock.clone());
+        assert_eq!(use_device(boxed), "mock");
+        assert_eq!(use_device(SharedDevice::new(mock.clone())), "mock");
+        assert_eq!(mock.calls().len(), 5);
+    }
+}
@@ -0,0 +1,371 @@
+/*!
+Status presets, and the palette they are shown in.
+
+The calendar monitor and `process::run_with_status` show a status, available, warning, or busy,
+on the light; by default these are green, yellow, and red. The difference between red and green
+cannot be seen by many people with color vision deficiency, so the palette used for the presets is
+a global setting. In the [Palette::Colorblind] palette the presets use blue and orange, from the
+Okabe-Ito palette, and each status also has a distinct temporal signature so that it can be told
+apart without seeing color at all:
+
+| Status      | Standard     | Colorblind                                |
+|-------------|--------------|-------------------------------------------|
+| `available` | solid green  | steady blue                               |
+| `warning`   | solid yellow | a slow orange pulse, a long wave          |
+| `busy`      | solid red    | a blinking orange, a strobe every second  |
+
+The palette is read when a preset's command is created, so [set_palette] should be called before
+any default options, such as `CalendarOptions::default()`, are created.
+
+Not every device can show every signature: [StatusPreset::command_for] checks the preset against
+the device's capabilities, a wave falls back to a slow strobe, as on the Luxafor Mute, and a strobe
+to a solid color. So that the three presets stay distinct on a device that can only show a solid
+color, the colorblind `busy` preset falls back to vermillion rather than orange. The blinking `busy` preset is a strobe at `Speed::DEFAULT_STROBE`, so a webhook
+light shows it with the webhook API's own `blink`; a pulse emulated by `emulated::Emulated` is cut
+short after `emulated::MAX_SEQUENCE`, leaving the light a steady orange, rather than calling the
+webhook API forever.
+
+# Example
+
+```rust,ignore
+use luxafor::status::{set_palette, Palette, StatusPreset};
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::error::Result;
+
+fn show_busy() -> Result<()> {
+    set_palette(Palette::Colorblind);
+    let discovery = USBDeviceDiscovery::new()?;
+    let device = discovery.device()?;
+    StatusPreset::Busy.command_for(&device.capabilities()).apply(&device)
+}
+```
+
+*/
+
+use crate::error::{Error, Result};
+use crate::{Capabilities, Command, Repeat, SolidColor, Speed, Wave};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A status that may be shown on the light. The text form is `available`, `warning`, or `busy`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusPreset {
+    /// Free to be interrupted.
+    Available,
+    /// About to become busy, or working on something that may need attention.
+    Warning,
+    /// Not to be interrupted.
+    Busy,
+}
+
+///
+/// The colors, and signatures, used for the status presets. The text form is `standard` or
+/// `colorblind`.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Palette {
+    /// Green, yellow, and red, each shown as a solid color.
+    #[default]
+    Standard,
+    /// Blue and orange, with each status shown as a distinct steady, pulsing, or blinking light.
+    Colorblind,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+static COLORBLIND: AtomicBool = AtomicBool::new(false);
+
+const COLORBLIND_BLUE: SolidColor = SolidColor::Custom {
+    red: 0,
+    green: 114,
+    blue: 178,
+};
+
+const COLORBLIND_ORANGE: SolidColor = SolidColor::Custom {
+    red: 230,
+    green: 159,
+    blue: 0,
+};
+
+const COLORBLIND_VERMILLION: SolidColor = SolidColor::Custom {
+    red: 213,
+    green: 94,
+    blue: 0,
+};
+
+const PULSE_SPEED: Speed = Speed::new(40);
+
+const BLINK_SPEED: Speed = Speed::DEFAULT_STROBE;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Set the palette used for the status presets by this process.
+///
+pub fn set_palette(palette: Palette) {
+    info!("Using the {} status palette", palette);
+    COLORBLIND.store(palette == Palette::Colorblind, Ordering::Relaxed);
+}
+
+///
+/// Return the palette used for the status presets by this process, by default
+/// [Palette::Standard].
+///
+pub fn palette() -> Palette {
+    if COLORBLIND.load(Ordering::Relaxed) {
+        Palette::Colorblind
+    } else {
+        Palette::Standard
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for StatusPreset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                StatusPreset::Available => "available",
+                StatusPreset::Warning => "warning",
+                StatusPreset::Busy => "busy",
+            }
+        )
+    }
+}
+
+impl FromStr for StatusPreset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "available" => Ok(StatusPreset::Available),
+            "warning" => Ok(StatusPreset::Warning),
+            "busy" => Ok(StatusPreset::Busy),
+            _ => Err(Error::InvalidStatus),
+        }
+    }
+}
+
+impl StatusPreset {
+    ///
+    /// Return the command that shows this status in the current [palette].
+    ///
+    pub fn command(&self) -> Command {
+        self.command_in(palette())
+    }
+
+    ///
+    /// Return the command that shows this status in the current [palette] on a device with
+    /// `capabilities`, falling back to a simpler signature if the device does not support it.
+    ///
+    pub fn command_for(&self, capabilities: &Capabilities) -> Command {
+        self.command_for_in(palette(), capabilities)
+    }
+
+    ///
+    /// Return the command that shows this status in `palette`.
+    ///
+    pub fn command_in(&self, palette: Palette) -> Command {
+        match (palette, self) {
+            (Palette::Standard, StatusPreset::Available) => Command::Solid {
+                color: SolidColor::Green,
+            },
+            (Palette::Standard, StatusPreset::Warning) => Command::Solid {
+                color: SolidColor::Yellow,
+            },
+            (Palette::Standard, StatusPreset::Busy) => Command::Solid {
+                color: SolidColor::Red,
+            },
+            (Palette::Colorblind, StatusPreset::Available) => Command::Solid {
+                color: COLORBLIND_BLUE,
+            },
+            (Palette::Colorblind, StatusPreset::Warning) => Command::Wave {
+                color: COLORBLIND_ORANGE,
+                wave_pattern: Wave::Long,
+                wave_speed: PULSE_SPEED,
+                repeat_count: Repeat::Forever,
+            },
+            (Palette::Colorblind, StatusPreset::Busy) => Command::Strobe {
+                color: COLORBLIND_ORANGE,
+                strobe_speed: BLINK_SPEED,
+                repeat_count: Repeat::Forever,
+            },
+        }
+    }
+
+    // Both the warning and busy presets fall back to solid orange, busy is shown in another color.
+    fn command_for_in(&self, palette: Palette, capabilities: &Capabilities) -> Command {
+        match (
+            supported(self.command_in(palette), capabilities),
+            palette,
+            self,
+        ) {
+            (Command::Solid { .. }, Palette::Colorblind, StatusPreset::Busy) => Command::Solid {
+                color: COLORBLIND_VERMILLION,
+            },
+            (command, _, _) => command,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Palette {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Palette::Standard => "standard",
+                Palette::Colorblind => "colorblind",
+            }
+        )
+    }
+}
+
+impl FromStr for Palette {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "standard" => Ok(Palette::Standard),
+            "colorblind" => Ok(Palette::Colorblind),
+            _ => Err(Error::InvalidStatus),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn supported(command: Command, capabilities: &Capabilities) -> Command {
+    match command {
+        command if capabilities.supports(&command) => command,
+        Command::Wave {
+            color,
+            wave_speed,
+            repeat_count,
+            ..
+        } => supported(
+            Command::Strobe {
+                color,
+                strobe_speed: wave_speed,
+                repeat_count,
+            },
+            capabilities,
+        ),
+        Command::Strobe { color, .. } => Command::Solid { color },
+        command => command,
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{supported, Palette, StatusPreset};
+    use crate::{Capabilities, CommandKind, DeviceModel};
+    use std::collections::HashSet;
+
+    const PRESETS: [StatusPreset; 3] = [
+        StatusPreset::Available,
+        StatusPreset::Warning,
+        StatusPreset::Busy,
+    ];
+
+    #[test]
+    fn test_presets() {
+        let standard: Vec<String> = PRESETS
+            .iter()
+            .map(|preset| preset.command_in(Palette::Standard).to_string())
+            .collect();
+        assert_eq!(standard, vec!["solid green", "solid yellow", "solid red"]);
+
+        let colorblind: Vec<String> = PRESETS
+            .iter()
+            .map(|preset| preset.command_in(Palette::Colorblind).to_string())
+            .collect();
+        assert_eq!(
+            colorblind,
+            vec![
+                "solid 0072b2",
+                "wave e69f00 long 40 forever",
+                "strobe e69f00 10 forever",
+            ]
+        );
+        let signatures: HashSet<_> = colorblind
+            .iter()
+            .map(|command| command.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(signatures.len(), PRESETS.len());
+    }
+
+    #[test]
+    fn test_supported() {
+        let fallbacks = |capabilities: &Capabilities| -> Vec<String> {
+            PRESETS
+                .iter()
+                .map(|preset| {
+                    preset
+                        .command_for_in(Palette::Colorblind, capabilities)
+                        .to_string()
+                })
+                .collect()
+        };
+        assert_eq!(
+            fallbacks(&DeviceModel::Mute.capabilities()),
+            vec![
+                "solid 0072b2",
+                "strobe e69f00 40 forever",
+                "strobe e69f00 10 forever",
+            ]
+        );
+
+        let mut solid_only = Capabilities::all();
+        solid_only
+            .commands
+            .retain(|kind| matches!(kind, CommandKind::Solid | CommandKind::TurnOff));
+        let solid = fallbacks(&solid_only);
+        assert!(solid.iter().all(|command| command.starts_with("solid ")));
+        assert_eq!(solid.iter().collect::<HashSet<_>>().len(), PRESETS.len());
+        assert_eq!(
+            supported(
+                StatusPreset::Busy.command_in(Palette::Colorblind),
+                &solid_only
+            )
+            .to_string(),
+            "solid e69f00"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        for preset in PRESETS {
+            assert_eq!(preset.to_string().parse::<StatusPreset>().unwrap(), preset);
+        }
+        assert_eq!(
+            "Colorblind".parse::<Palette>().unwrap(),
+            Palette::Colorblind
+        );
+        assert!("red-green".parse::<Palette>().is_err());
+        assert!("away".parse::<StatusPreset>().is_err());
+    }
+}
@@ -0,0 +1,435 @@
+/*!
+A device that previews the light in the terminal, for working without hardware.
+
+A [TerminalDevice] implements the [Device](../trait.Device.html) and
+[TargetedDevice](../trait.TargetedDevice.html) traits by drawing the six LEDs of a Flag as ANSI
+true-color blocks, the back LEDs on the left and the front LEDs on the right, top to bottom:
+
+```text
+  ██  ██      6  3
+  ██  ██      5  2
+  ██  ██      4  1
+```
+
+Each change redraws the layout in place. Fades, strobes, waves, and patterns are animated in the
+background at the requested speed, the first frame is drawn before the call returns, and an
+animation is cancelled by the next call on the same device. The patterns are approximations of the
+firmware's patterns, the random patterns use a fixed sequence of colors for each number.
+
+# Example
+
+```rust,ignore
+use luxafor::terminal::TerminalDevice;
+use luxafor::{Device, Repeat, SolidColor, Speed};
+use luxafor::error::Result;
+
+fn preview() -> Result<()> {
+    let device = TerminalDevice::new();
+    device.set_color_strobe(SolidColor::Red, Speed::new(10), Repeat::times(5)?)?;
+    device.wait();
+    Ok(())
+}
+```
+
+*/
+
+use crate::error::Result;
+use crate::sequencer::{blend, Sequencer, Step};
+use crate::{
+    Capabilities, Device, Pattern, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice, Wave,
+};
+use std::io::Write;
+use std::num::NonZeroU8;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A device drawn in the terminal, see the [module documentation](index.html). Clones share the
+/// drawing, and cancel each other's animations, but each targets its own LEDs.
+///
+#[allow(missing_debug_implementations)]
+pub struct TerminalDevice {
+    shared: Arc<Shared>,
+    target: [bool; LEDS],
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+type Rgb = (u8, u8, u8);
+
+type Leds = [Rgb; LEDS];
+
+type Frame = Step<Leds>;
+
+struct Shared {
+    output: Mutex<Output>,
+    sequencer: Sequencer,
+}
+
+struct Output {
+    writer: Box<dyn Write + Send>,
+    leds: Leds,
+    drawn: bool,
+}
+
+const LEDS: usize = 6;
+const OFF: Rgb = (0, 0, 0);
+const FADE_STEPS: u32 = 16;
+const PATTERN_HOLD: Duration = Duration::from_millis(250);
+
+// The (back, front) LEDs, as indices `0..6` for LEDs `1..=6`, of each row from top to bottom.
+const ROWS: [(usize, usize); 3] = [(5, 2), (4, 1), (3, 0)];
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for TerminalDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for TerminalDevice {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            target: self.target,
+        }
+    }
+}
+
+impl TerminalDevice {
+    ///
+    /// Construct a new device drawn on standard output.
+    ///
+    pub fn new() -> Self {
+        Self::with_writer(std::io::stdout())
+    }
+
+    ///
+    /// Construct a new device drawn to `writer`.
+    ///
+    pub fn with_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                output: Mutex::new(Output {
+                    writer: Box::new(writer),
+                    leds: [OFF; LEDS],
+                    drawn: false,
+                }),
+                sequencer: Sequencer::default(),
+            }),
+            target: [true; LEDS],
+        }
+    }
+
+    ///
+    /// Return the color of each LED, `1..=6`, as last drawn.
+    ///
+    pub fn leds(&self) -> [(u8, u8, u8); 6] {
+        self.shared.output.lock().unwrap().leds
+    }
+
+    fn show(&self, color: Rgb) -> Result<()> {
+        self.shared.sequencer.show(|| {
+            let leds = self.targeted(self.leds(), color);
+            self.shared.draw(&leds)
+        })
+    }
+
+    fn animate(&self, cycle: Vec<Frame>, repeat_count: Repeat) -> Result<()> {
+        let shared = self.shared.clone();
+        self.shared
+            .sequencer
+            .play(self.id(), cycle, repeat_count, None, move |leds| {
+                shared.draw(leds)
+            })
+    }
+
+    fn targeted(&self, mut leds: Leds, color: Rgb) -> Leds {
+        for (led, targeted) in leds.iter_mut().zip(self.target) {
+            if targeted {
+                *led = color;
+            }
+        }
+        leds
+    }
+}
+
+impl Device for TerminalDevice {
+    fn id(&self) -> String {
+        "terminal".to_string()
+    }
+
+    fn turn_off(&self) -> Result<()> {
+        self.show(OFF)
+    }
+
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
+        self.show(color.to_rgb())
+    }
+
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
+        let from = self.leds();
+        let to = self.targeted(from, color.to_rgb());
+        let hold = fade_duration.as_duration() / FADE_STEPS;
+        let frames = (1..=FADE_STEPS)
+            .map(|step| {
+                let fraction = f64::from(step) / f64::from(FADE_STEPS);
+                let mut leds = from;
+                for (led, to) in leds.iter_mut().zip(to) {
+                    *led = blend(*led, to, fraction);
+                }
+                Frame { value: leds, hold }
+            })
+            .collect();
+        self.animate(frames, Repeat::Times(NonZeroU8::MIN))
+    }
+
+    fn set_color_strobe(
+        &self,
+        color: SolidColor,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        let leds = self.leds();
+        let hold = strobe_speed.as_duration();
+        self.animate(
+            vec![
+                Frame {
+                    value: self.targeted(leds, color.to_rgb()),
+                    hold,
+                },
+                Frame {
+                    value: self.targeted(leds, OFF),
+                    hold,
+                },
+            ],
+            repeat_count,
+        )
+    }
+
+    fn set_color_wave(
+        &self,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
+        self.animate(
+            wave_frames(color.to_rgb(), &wave_pattern, wave_speed.as_duration()),
+            repeat_count,
+        )
+    }
+
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
+        self.animate(pattern_frames(&pattern), repeat_count)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::all()
+    }
+
+    fn wait(&self) {
+        self.shared.sequencer.wait()
+    }
+}
+
+impl TargetedDevice for TerminalDevice {
+    fn set_specific_led(&mut self, led: SpecificLED) -> Result<()> {
+        self.target = match led {
+            SpecificLED::All => [true; LEDS],
+            SpecificLED::AllFront => [true, true, true, false, false, false],
+            SpecificLED::AllBack => [false, false, false, true, true, true],
+            SpecificLED::Number(n) => {
+                let mut target = [false; LEDS];
+                target[usize::from(n.number()) - 1] = true;
+                target
+            }
+        };
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Shared {
+    fn draw(&self, leds: &Leds) -> Result<()> {
+        let mut output = self.output.lock().unwrap();
+        let mut frame = String::new();
+        if output.drawn {
+            frame.push_str(&format!("\x1b[{}A", ROWS.len()));
+        }
+        for (back, front) in ROWS {
+            frame.push_str(&format!(
+                "\r  {}  {}      {}  {}\x1b[K\n",
+                block(leds[back]),
+                block(leds[front]),
+                back + 1,
+                front + 1,
+            ));
+        }
+        output.writer.write_all(frame.as_bytes())?;
+        output.writer.flush()?;
+        output.leds = *leds;
+        output.drawn = true;
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn block(color: Rgb) -> String {
+    if color == OFF {
+        "░░".to_string()
+    } else {
+        let (red, green, blue) = color;
+        format!("\x1b[38;2;{};{};{}m██\x1b[0m", red, green, blue)
+    }
+}
+
+fn wave_frames(color: Rgb, wave_pattern: &Wave, cycle: Duration) -> Vec<Frame> {
+    // The wave rises from the bottom row to the top, overlapping waves leave a dimmed trail.
+    let hold = match wave_pattern {
+        Wave::Short | Wave::OverlappingShort => cycle / ROWS.len() as u32,
+        Wave::Long | Wave::OverlappingLong => cycle * 2 / ROWS.len() as u32,
+    };
+    let trail = blend(OFF, color, 0.3);
+    let mut frames: Vec<Frame> = (0..ROWS.len())
+        .rev()
+        .map(|row| {
+            let mut leds = [OFF; LEDS];
+            let (back, front) = ROWS[row];
+            leds[back] = color;
+            leds[front] = color;
+            if matches!(wave_pattern, Wave::OverlappingShort | Wave::OverlappingLong)
+                && row + 1 < ROWS.len()
+            {
+                let (back, front) = ROWS[row + 1];
+                leds[back] = trail;
+                leds[front] = trail;
+            }
+            Frame { value: leds, hold }
+        })
+        .collect();
+    if let (Wave::Short | Wave::Long, Some(last)) = (wave_pattern, frames.last()) {
+        frames.push(Frame {
+            value: [OFF; LEDS],
+            hold: last.hold,
+        });
+    }
+    frames
+}
+
+fn pattern_frames(pattern: &Pattern) -> Vec<Frame> {
+    let red = SolidColor::Red.to_rgb();
+    let blue = SolidColor::Blue.to_rgb();
+    let frame = |leds: Leds| Frame {
+        value: leds,
+        hold: PATTERN_HOLD,
+    };
+    match pattern {
+        Pattern::Police => vec![
+            frame([red, red, red, blue, blue, blue]),
+            frame([blue, blue, blue, red, red, red]),
+        ],
+        Pattern::TrafficLights => [SolidColor::Green, SolidColor::Yellow, SolidColor::Red]
+            .iter()
+            .map(|color| frame([color.to_rgb(); LEDS]))
+            .collect(),
+        Pattern::Random(random) => {
+            let mut seed = u32::from(random.number()).wrapping_mul(0x9E37_79B9);
+            let mut next = move || {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let [_, red, green, blue] = seed.to_be_bytes();
+                (red, green, blue)
+            };
+            (0..6)
+                .map(|_| {
+                    let mut leds = [OFF; LEDS];
+                    leds.iter_mut().for_each(|led| *led = next());
+                    frame(leds)
+                })
+                .collect()
+        }
+        #[cfg(target_os = "windows")]
+        Pattern::Rainbow | Pattern::Sea | Pattern::WhiteWave | Pattern::Synthetic => [
+            SolidColor::Red,
+            SolidColor::Yellow,
+            SolidColor::Green,
+            SolidColor::Cyan,
+            SolidColor::Blue,
+            SolidColor::Magenta,
+        ]
+        .iter()
+        .map(|color| frame([color.to_rgb(); LEDS]))
+        .collect(),
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::TerminalDevice;
+    use crate::mock::MockWriter;
+    use crate::{Device, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice, Wave};
+
+    #[test]
+    fn test_draw_targeted_leds() {
+        let screen = MockWriter::default();
+        let mut device = TerminalDevice::with_writer(screen.clone());
+        device.set_specific_led(SpecificLED::AllFront).unwrap();
+        device.set_solid_color(SolidColor::Red).unwrap();
+        device.set_specific_led(SpecificLED::AllBack).unwrap();
+        device.set_solid_color(SolidColor::Blue).unwrap();
+        let red = (255, 0, 0);
+        let blue = (0, 0, 255);
+        assert_eq!(device.leds(), [red, red, red, blue, blue, blue]);
+
+        let text = screen.text();
+        assert!(text.starts_with("\r  ░░  \x1b[38;2;255;0;0m██\x1b[0m      6  3\x1b[K\n"));
+        assert!(text.contains("\x1b[3A\r  \x1b[38;2;0;0;255m██\x1b[0m"));
+    }
+
+    #[test]
+    fn test_animations_complete() {
+        let device = TerminalDevice::with_writer(MockWriter::default());
+        device
+            .set_fade_to_color(SolidColor::Green, Speed::new(1))
+            .unwrap();
+        device.wait();
+        assert_eq!(device.leds(), [(0, 255, 0); 6]);
+
+        device
+            .set_color_strobe(SolidColor::Red, Speed::new(0), Repeat::times(2).unwrap())
+            .unwrap();
+        device.wait();
+        assert_eq!(device.leds(), [(0, 0, 0); 6]);
+
+        device
+            .set_color_wave(
+                SolidColor::Blue,
+                Wave::Short,
+                Speed::new(1),
+                Repeat::Forever,
+            )
+            .unwrap();
+        device.turn_off().unwrap();
+        device.wait();
+        assert_eq!(device.leds(), [(0, 0, 0); 6]);
+    }
+}
@@ -0,0 +1,385 @@
+/*!
+Countdown timers that use the LEDs on the light as a progress bar.
+
+A [Timer] runs through a sequence of [Phase]s, such as the work and break phases of a pomodoro.
+At the start of each phase all six LEDs are lit in the phase color and, as time passes, they are
+turned off one at a time so that the light empties over the phase. When the time remaining reaches
+the warning period the whole light strobes in the phase color until the phase ends. A light whose
+capabilities do not include LED targeting, such as the single LED Orb or Mute, has no progress
+bar; it stays in the phase color until the warning strobe. A device that is not a
+[TargetedDevice](../trait.TargetedDevice.html) at all, such as a webhook or a group, can still run a
+timer in the same way with [Timer::run_untargeted].
+
+# Example
+
+```rust,ignore
+use luxafor::timer::{pomodoro, Timer};
+use luxafor::usb_hid::USBDeviceDiscovery;
+use luxafor::SolidColor;
+use luxafor::error::Result;
+use std::time::Duration;
+
+fn focus() -> Result<()> {
+    let discovery = USBDeviceDiscovery::new()?;
+    let mut device = discovery.device()?;
+    let phases = pomodoro(
+        Duration::from_secs(25 * 60),
+        SolidColor::Red,
+        Duration::from_secs(5 * 60),
+        SolidColor::Green,
+        4,
+    );
+    let timer = Timer::new(phases, Duration::from_secs(30));
+    let _ = timer.run(&mut device)?;
+    device.turn_off()
+}
+```
+
+*/
+
+use crate::error::Result;
+use crate::{Device, LedIndex, Repeat, SolidColor, SpecificLED, Speed, TargetedDevice};
+use std::num::NonZeroU8;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A single timed phase, shown in one color.
+///
+#[derive(Clone, Debug)]
+pub struct Phase {
+    /// A name for the phase, used only for logging.
+    pub name: String,
+    /// How long the phase lasts.
+    pub duration: Duration,
+    /// The color the light shows during the phase.
+    pub color: SolidColor,
+}
+
+///
+/// A timer that shows the progress through a sequence of phases on a light.
+///
+#[derive(Debug)]
+pub struct Timer {
+    phases: Vec<Phase>,
+    warning: Duration,
+    cancelled: Arc<AtomicBool>,
+}
+
+///
+/// The number of LEDs used for the progress bar, on a light that can target each LED.
+///
+pub const LED_COUNT: u8 = LedIndex::COUNT;
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const MAX_TICK: Duration = Duration::from_millis(100);
+const TICKS_PER_LED: u32 = 10;
+
+const WARNING_STROBE_SPEED: Speed = Speed::new(20);
+const WARNING_REPEAT_COUNT: Repeat = Repeat::Times(NonZeroU8::MAX);
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the phases for a pomodoro session of `cycles` work phases, each followed by a break
+/// except for the last.
+///
+pub fn pomodoro(
+    work: Duration,
+    work_color: SolidColor,
+    rest: Duration,
+    rest_color: SolidColor,
+    cycles: u16,
+) -> Vec<Phase> {
+    let mut phases = Vec::new();
+    for cycle in 1..=cycles {
+        phases.push(Phase::new(
+            &format!("work {}", cycle),
+            work,
+            work_color.clone(),
+        ));
+        if cycle < cycles {
+            phases.push(Phase::new(
+                &format!("break {}", cycle),
+                rest,
+                rest_color.clone(),
+            ));
+        }
+    }
+    phases
+}
+
+///
+/// Return the number of LEDs that should be lit when `remaining` time is left of a phase with the
+/// total `duration`; this is always at least one until the phase completes.
+///
+pub fn lit_leds(remaining: Duration, duration: Duration) -> u8 {
+    if duration.is_zero() || remaining.is_zero() {
+        0
+    } else if remaining >= duration {
+        LED_COUNT
+    } else {
+        let fraction = remaining.as_secs_f64() / duration.as_secs_f64();
+        (fraction * f64::from(LED_COUNT)).ceil() as u8
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Phase {
+    ///
+    /// Construct a new phase.
+    ///
+    pub fn new(name: &str, duration: Duration, color: SolidColor) -> Self {
+        Self {
+            name: name.to_string(),
+            duration,
+            color,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Timer {
+    ///
+    /// Construct a new timer; the light strobes for the last `warning` period of each phase.
+    ///
+    pub fn new(phases: Vec<Phase>, warning: Duration) -> Self {
+        Self {
+            phases,
+            warning,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    ///
+    /// Return a flag that, when set, cancels the running timer. This is intended to be set from
+    /// a signal handler.
+    ///
+    pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    ///
+    /// Run the timer to completion, returning `true`, or until it is cancelled, returning `false`.
+    /// The light is left in its last state, it is up to the caller to turn it off or restore any
+    /// previous state.
+    ///
+    pub fn run<D: TargetedDevice>(&self, device: &mut D) -> Result<bool> {
+        self.run_with(device, Some(D::set_specific_led))
+    }
+
+    ///
+    /// Run the timer, as `run` does, on a device that cannot target LEDs. Each phase is shown in
+    /// its color, without a progress bar, until the warning strobe.
+    ///
+    pub fn run_untargeted<D: Device + ?Sized>(&self, device: &D) -> Result<bool> {
+        self.run_with(&mut &*device, None)
+    }
+
+    // Targeting the LEDs, with `set_led`, is only needed for the progress bar.
+    fn run_with<D: Device + ?Sized>(
+        &self,
+        device: &mut D,
+        set_led: Option<fn(&mut D, SpecificLED) -> Result<()>>,
+    ) -> Result<bool> {
+        for phase in &self.phases {
+            if !self.run_phase(device, set_led, phase)? {
+                info!("Timer cancelled during phase '{}'", phase.name);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn run_phase<D: Device + ?Sized>(
+        &self,
+        device: &mut D,
+        set_led: Option<fn(&mut D, SpecificLED) -> Result<()>>,
+        phase: &Phase,
+    ) -> Result<bool> {
+        info!(
+            "Starting phase '{}' for {:?} in {}",
+            phase.name, phase.duration, phase.color
+        );
+        let tick = (phase.duration / (u32::from(LED_COUNT) * TICKS_PER_LED))
+            .clamp(Duration::from_millis(1), MAX_TICK);
+        let start = Instant::now();
+        let mut lit = LED_COUNT;
+        let mut warned = false;
+        let progress = set_led.filter(|_| device.capabilities().led_targeting);
+        if progress.is_none() {
+            debug!(
+                "Device '{}' has no progress bar, it cannot target LEDs",
+                device.id()
+            );
+        }
+
+        if let Some(set_led) = set_led {
+            set_led(device, SpecificLED::All)?;
+        }
+        device.set_solid_color(phase.color.clone())?;
+
+        loop {
+            if self.cancelled.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+            let remaining = phase.duration.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                return Ok(true);
+            }
+            if !warned && remaining <= self.warning {
+                debug!("Phase '{}' ends in {:?}", phase.name, remaining);
+                if let Some(set_led) = set_led {
+                    set_led(device, SpecificLED::All)?;
+                }
+                device.set_color_strobe(
+                    phase.color.clone(),
+                    WARNING_STROBE_SPEED,
+                    WARNING_REPEAT_COUNT,
+                )?;
+                warned = true;
+            } else if let Some(set_led) = progress.filter(|_| !warned) {
+                let now_lit = lit_leds(remaining, phase.duration);
+                while lit > now_lit && lit > 1 {
+                    set_led(device, SpecificLED::Number(LedIndex::new(lit)?))?;
+                    device.set_solid_color(SolidColor::Custom {
+                        red: 0,
+                        green: 0,
+                        blue: 0,
+                    })?;
+                    lit -= 1;
+                }
+            }
+            std::thread::sleep(tick.min(remaining));
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{lit_leds, pomodoro, Phase, Timer};
+    use crate::mock::MockDevice;
+    use crate::{Device, DeviceModel, SolidColor};
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lit_leds() {
+        let duration = Duration::from_secs(60);
+        assert_eq!(lit_leds(Duration::from_secs(60), duration), 6);
+        assert_eq!(lit_leds(Duration::from_secs(55), duration), 6);
+        assert_eq!(lit_leds(Duration::from_secs(50), duration), 5);
+        assert_eq!(lit_leds(Duration::from_secs(11), duration), 2);
+        assert_eq!(lit_leds(Duration::from_secs(1), duration), 1);
+        assert_eq!(lit_leds(Duration::ZERO, duration), 0);
+    }
+
+    #[test]
+    fn test_pomodoro_phases() {
+        let phases = pomodoro(
+            Duration::from_secs(25 * 60),
+            SolidColor::Red,
+            Duration::from_secs(5 * 60),
+            SolidColor::Green,
+            3,
+        );
+        let names: Vec<&str> = phases.iter().map(|phase| phase.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["work 1", "break 1", "work 2", "break 2", "work 3"]
+        );
+    }
+
+    #[test]
+    fn test_timer_progress_and_warning() {
+        let mut device = MockDevice::default();
+        let timer = Timer::new(
+            vec![Phase::new(
+                "test",
+                Duration::from_millis(600),
+                SolidColor::Blue,
+            )],
+            Duration::from_millis(150),
+        );
+        assert!(timer.run(&mut device).unwrap());
+        let calls = device.calls();
+        assert_eq!(&calls[..2], &["led all", "solid blue"]);
+        assert!(calls.contains(&"led 6".to_string()));
+        assert!(!calls.contains(&"led 1".to_string()));
+        assert_eq!(
+            &calls[calls.len() - 2..],
+            &["led all", "strobe blue 20 255"]
+        );
+    }
+
+    #[test]
+    fn test_timer_on_single_led_model() {
+        let mut device = MockDevice::with_capabilities(DeviceModel::Orb.capabilities());
+        let timer = Timer::new(
+            vec![Phase::new(
+                "test",
+                Duration::from_millis(300),
+                SolidColor::Green,
+            )],
+            Duration::from_millis(100),
+        );
+        assert!(timer.run(&mut device).unwrap());
+        assert_eq!(
+            device.calls(),
+            vec!["led all", "solid green", "led all", "strobe green 20 255"]
+        );
+    }
+
+    #[test]
+    fn test_timer_untargeted() {
+        let device = MockDevice::default();
+        let untargeted: &dyn Device = &device;
+        let timer = Timer::new(
+            vec![
+                Phase::new("work", Duration::from_millis(200), SolidColor::Red),
+                Phase::new("rest", Duration::from_millis(200), SolidColor::Green),
+            ],
+            Duration::from_millis(100),
+        );
+        assert!(timer.run_untargeted(untargeted).unwrap());
+        assert_eq!(
+            device.calls(),
+            vec![
+                "solid red",
+                "strobe red 20 255",
+                "solid green",
+                "strobe green 20 255"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timer_cancelled() {
+        let mut device = MockDevice::default();
+        let timer = Timer::new(
+            vec![Phase::new("test", Duration::from_secs(60), SolidColor::Red)],
+            Duration::ZERO,
+        );
+        timer.cancel_handle().store(true, Ordering::SeqCst);
+        assert!(!timer.run(&mut device).unwrap());
+        assert_eq!(device.calls(), vec!["led all", "solid red"]);
+    }
+}
@@ -26,9 +26,10 @@ identifier_, must always be set to `0x00`. Also, trailing `0x00` values need not
 
 1. The values for LED, COLOR, WTYPE, and PTYPE, are shown in the corresponding tables below.
 1. The values for RED, GREEN, BLUE, are `0..255` and correspond to standard RGB color.
-1. The value for TIME is the number of seconds to fade from the current color to the new color specified.
-1. The value for SPEED is the time to cycle through the change.
-1. The value of REPEAT is the number of times to repeat the wave or pattern.
+1. The value for TIME is the time to fade from the current color to the new color specified, in units of
+   50 milliseconds, the same units as SPEED; both are a `Speed` in the `Device` trait.
+1. The value for SPEED is the time to cycle through the change, in units of 50 milliseconds.
+1. The value of REPEAT is the number of times to repeat the wave or pattern, `0x00` repeats forever.
 1. The response data for the get version and serial number command group is described below.
 
 ## LED values
@@ -102,10 +103,43 @@ The addressable LEDs on the _Flag_ USB product are as follows, shown in vertical
 
 The serial number is returned as a pair, (high,low) bytes.
 
+## Models
+
+All models share the vendor and product IDs above, the model is detected from the product string
+of the HID descriptor, see `DeviceModel::from_product`. The Flag, Colorblind Flag, and Bluetooth
+Pro have the six LEDs shown above; the Orb and Mute button have a single LED, and the Mute button
+does not support waves or patterns.
+
+## Backends
+
+By default the `hidapi` library is used to find and open devices. On Linux the `usb-hidraw`
+feature replaces it with a pure Rust backend that scans `/sys/class/hidraw` for the vendor and
+product IDs above and writes to the `/dev/hidrawN` node directly, without the C library or
+`libudev`; it takes precedence if both features are enabled. The API is the same for either.
+
+## Button input reports
+
+The Mute button also sends input reports when its button is pressed or released, these are
+decoded by [USBDevice::next_event] and [USBDevice::events], see the
+[button module](../button/index.html) for the report format.
+
 */
 
-use crate::{Device, Pattern, SolidColor, SpecificLED, TargetedDevice, Wave};
+use crate::button::{ButtonEvent, ButtonState, InputReports, DEFAULT_LONG_PRESS};
+use crate::error::{Error, Result};
+use crate::hid::{LUXAFOR_PRODUCT_ID, LUXAFOR_VENDOR_ID};
+#[cfg(all(target_os = "linux", feature = "usb-hidraw"))]
+use crate::hidraw::{HidApi, HidDevice};
+use crate::registry::DeviceUri;
+use crate::{
+    Capabilities, Command, Device, DeviceModel, Pattern, Repeat, SolidColor, SpecificLED, Speed,
+    TargetedDevice, Wave,
+};
+#[cfg(not(all(target_os = "linux", feature = "usb-hidraw")))]
 use hidapi::{HidApi, HidDevice};
+use std::ffi::CString;
+use std::sync::Mutex;
+use std::time::Duration;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -126,16 +160,28 @@ pub struct USBDeviceDiscovery {
 pub struct USBDevice {
     hid_device: HidDevice,
     id: String,
+    model: DeviceModel,
     target_led: u8,
+    button: Mutex<ButtonState>,
+}
+
+///
+/// A light found by [USBDeviceDiscovery::lights], which may be opened by its path.
+///
+#[derive(Clone, Debug)]
+pub struct DiscoveredLight {
+    /// The model, detected from the product string.
+    pub model: DeviceModel,
+    /// The platform-specific HID path.
+    pub path: String,
+    /// The serial number, if the light reports one.
+    pub serial: Option<String>,
 }
 
 // ------------------------------------------------------------------------------------------------
 // API Constants
 // ------------------------------------------------------------------------------------------------
 
-const LUXAFOR_VENDOR_ID: u16 = 0x04d8;
-const LUXAFOR_PRODUCT_ID: u16 = 0xf372;
-
 const HID_REPORT_ID: u8 = 0;
 
 const MODE_SIMPLE: u8 = 0;
@@ -176,6 +222,26 @@ const PATTERN_RAINBOW_WAVE: u8 = 8;
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Return the device for a `usb:` URI; `usb:` is the first light found, `usb:serial=1234` the
+/// light with that serial number, `usb:path=/dev/hidraw3` the light at that HID path, and
+/// `usb:model=orb` the first light of that model.
+///
+pub fn open(uri: &DeviceUri) -> Result<USBDevice> {
+    let discovery = USBDeviceDiscovery::new()?;
+    match (
+        uri.option("serial"),
+        uri.option("path"),
+        uri.option("model"),
+    ) {
+        (Some(serial), _, _) => discovery.device_with_serial(serial),
+        (None, Some(path), _) => discovery.device_at_path(path),
+        (None, None, Some(model)) => discovery.device_for_model(model.parse()?),
+        (None, None, None) if uri.target().is_empty() => discovery.device(),
+        _ => Err(Error::InvalidDeviceURI),
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -184,28 +250,65 @@ impl USBDeviceDiscovery {
     ///
     /// Construct a new discovery object, this initializes the USB HID interface and thus can fail.
     ///
-    pub fn new() -> crate::error::Result<Self> {
-        match HidApi::new() {
-            Ok(hid_api) => Ok(Self { hid_api }),
-            Err(err) => {
-                error!("Could not connect to USB, error: {:?}", err);
-                Err(crate::error::Error::DeviceNotFound)
-            }
-        }
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            hid_api: HidApi::new()?,
+        })
     }
 
     ///
     /// Return a device, if found, that corresponds to a Luxafor light.
     ///
-    pub fn device(&self) -> crate::error::Result<USBDevice> {
-        let result = self.hid_api.open(LUXAFOR_VENDOR_ID, LUXAFOR_PRODUCT_ID);
-        match result {
-            Ok(hid_device) => USBDevice::new(hid_device),
-            Err(err) => {
-                error!("Could not open HID device: {:?}", err);
-                Err(crate::error::Error::DeviceNotFound)
-            }
-        }
+    pub fn device(&self) -> Result<USBDevice> {
+        let hid_device = self.hid_api.open(LUXAFOR_VENDOR_ID, LUXAFOR_PRODUCT_ID)?;
+        USBDevice::new(hid_device)
+    }
+
+    ///
+    /// Return each connected light, with its model.
+    ///
+    pub fn lights(&self) -> Vec<DiscoveredLight> {
+        self.hid_api
+            .device_list()
+            .filter(|info| {
+                info.vendor_id() == LUXAFOR_VENDOR_ID && info.product_id() == LUXAFOR_PRODUCT_ID
+            })
+            .map(|info| DiscoveredLight {
+                model: DeviceModel::from_product(info.product_string().unwrap_or_default()),
+                path: info.path().to_string_lossy().to_string(),
+                serial: info.serial_number().map(str::to_string),
+            })
+            .collect()
+    }
+
+    ///
+    /// Return the Luxafor light with the serial number `serial`, if found.
+    ///
+    pub fn device_with_serial(&self, serial: &str) -> Result<USBDevice> {
+        let hid_device = self
+            .hid_api
+            .open_serial(LUXAFOR_VENDOR_ID, LUXAFOR_PRODUCT_ID, serial)?;
+        USBDevice::new(hid_device)
+    }
+
+    ///
+    /// Return the light at the platform-specific HID path `path`, such as `/dev/hidraw3`.
+    ///
+    pub fn device_at_path(&self, path: &str) -> Result<USBDevice> {
+        let path = CString::new(path).map_err(|_| Error::InvalidDeviceURI)?;
+        USBDevice::new(self.hid_api.open_path(&path)?)
+    }
+
+    ///
+    /// Return the first light of `model`, if found.
+    ///
+    pub fn device_for_model(&self, model: DeviceModel) -> Result<USBDevice> {
+        let light = self
+            .lights()
+            .into_iter()
+            .find(|light| light.model == model)
+            .ok_or(Error::DeviceNotFound)?;
+        self.device_at_path(&light.path)
     }
 }
 
@@ -216,95 +319,125 @@ impl Device for USBDevice {
         self.id.clone()
     }
 
-    fn turn_off(&self) -> crate::error::Result<()> {
+    fn turn_off(&self) -> Result<()> {
         info!("Turning device '{}' off", self.id);
-        self.write(&[HID_REPORT_ID, MODE_SIMPLE, SIMPLE_COLOR_OFF])
+        self.send(
+            Command::TurnOff,
+            &[HID_REPORT_ID, MODE_SIMPLE, SIMPLE_COLOR_OFF],
+        )
     }
 
-    fn set_solid_color(&self, color: SolidColor) -> crate::error::Result<()> {
+    fn set_solid_color(&self, color: SolidColor) -> Result<()> {
         info!("Setting the color of device '{}' to {}", self.id, color);
-        let (r, g, b) = self.color_to_bytes(color);
-        self.write(&[HID_REPORT_ID, MODE_SOLID, self.target_led, r, g, b])
+        let (r, g, b) = color.to_rgb();
+        self.send(
+            Command::Solid { color },
+            &[HID_REPORT_ID, MODE_SOLID, self.target_led, r, g, b],
+        )
     }
 
-    fn set_fade_to_color(&self, color: SolidColor, fade_duration: u8) -> crate::error::Result<()> {
+    fn set_fade_to_color(&self, color: SolidColor, fade_duration: Speed) -> Result<()> {
         info!(
             "Setting the fade-to color of device '{}' to {}, over {}",
             self.id, color, fade_duration
         );
-        let (r, g, b) = self.color_to_bytes(color);
-        self.write(&[
-            HID_REPORT_ID,
-            MODE_FADE,
-            self.target_led,
-            r,
-            g,
-            b,
+        let (r, g, b) = color.to_rgb();
+        let command = Command::Fade {
+            color,
             fade_duration,
-        ])
+        };
+        self.send(
+            command,
+            &[
+                HID_REPORT_ID,
+                MODE_FADE,
+                self.target_led,
+                r,
+                g,
+                b,
+                fade_duration.units(),
+            ],
+        )
     }
 
     fn set_color_strobe(
         &self,
         color: SolidColor,
-        strobe_speed: u8,
-        repeat_count: u8,
-    ) -> crate::error::Result<()> {
+        strobe_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
         info!(
             "Setting the device '{}' to strobe {}, at {}, {} times",
             self.id, color, strobe_speed, repeat_count
         );
-        let (r, g, b) = self.color_to_bytes(color);
-        self.write(&[
-            HID_REPORT_ID,
-            MODE_STROBE,
-            self.target_led,
-            r,
-            g,
-            b,
+        let (r, g, b) = color.to_rgb();
+        let command = Command::Strobe {
+            color,
             strobe_speed,
-            0x00,
             repeat_count,
-        ])
+        };
+        self.send(
+            command,
+            &[
+                HID_REPORT_ID,
+                MODE_STROBE,
+                self.target_led,
+                r,
+                g,
+                b,
+                strobe_speed.units(),
+                0x00,
+                repeat_count.to_firmware(),
+            ],
+        )
     }
 
     fn set_color_wave(
         &self,
         color: SolidColor,
         wave_pattern: Wave,
-        wave_speed: u8,
-        repeat_count: u8,
-    ) -> crate::error::Result<()> {
+        wave_speed: Speed,
+        repeat_count: Repeat,
+    ) -> Result<()> {
         info!(
             "Setting the device '{}' to wave {}, at {}, {} times",
             self.id, color, wave_speed, repeat_count
         );
-        let wave_pattern = match wave_pattern {
+        let wave_type = match wave_pattern {
             Wave::Short => WAVE_SHORT,
             Wave::Long => WAVE_LONG,
             Wave::OverlappingShort => WAVE_OVERLAPPING_SHORT,
             Wave::OverlappingLong => WAVE_OVERLAPPING_LONG,
         };
-        let (r, g, b) = self.color_to_bytes(color);
-        self.write(&[
-            HID_REPORT_ID,
-            MODE_WAVE,
+        let (r, g, b) = color.to_rgb();
+        let command = Command::Wave {
+            color,
             wave_pattern,
-            r,
-            g,
-            b,
-            0x00,
-            repeat_count,
             wave_speed,
-        ])
+            repeat_count,
+        };
+        self.send(
+            command,
+            &[
+                HID_REPORT_ID,
+                MODE_WAVE,
+                wave_type,
+                r,
+                g,
+                b,
+                0x00,
+                repeat_count.to_firmware(),
+                wave_speed.units(),
+            ],
+        )
     }
 
-    fn set_pattern(&self, pattern: Pattern, repeat_count: u8) -> crate::error::Result<()> {
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> Result<()> {
         info!("Setting the pattern of device '{}' to {}", self.id, pattern);
-        let pattern = match pattern {
+        let pattern_type = match pattern {
             Pattern::Police => PATTERN_POLICE,
             Pattern::TrafficLights => PATTERN_LUXAFOR,
-            Pattern::Random(n) => match n {
+            Pattern::Random(n) => match n.number() {
                 1 => PATTERN_RANDOM_1,
                 2 => PATTERN_RANDOM_2,
                 3 => PATTERN_RANDOM_3,
@@ -320,32 +453,85 @@ impl Device for USBDevice {
             #[cfg(target_os = "windows")]
             Pattern::Synthetic => 11,
         };
-        self.write(&[HID_REPORT_ID, MODE_PATTERN, pattern, repeat_count])
+        let command = Command::Pattern {
+            pattern,
+            repeat_count,
+        };
+        self.send(
+            command,
+            &[
+                HID_REPORT_ID,
+                MODE_PATTERN,
+                pattern_type,
+                repeat_count.to_firmware(),
+            ],
+        )
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.model.capabilities()
     }
 }
 
 impl TargetedDevice for USBDevice {
-    fn set_specific_led(&mut self, led: SpecificLED) -> crate::error::Result<()> {
+    fn set_specific_led(&mut self, led: SpecificLED) -> Result<()> {
+        if let Err(e) = self.model.check_led(&led) {
+            return Err(e.with_context(&self.id, Some(&format!("led {}", led))));
+        }
         self.target_led = match led {
             SpecificLED::All => LED_ALL,
             SpecificLED::AllFront => LED_FRONT_ALL,
             SpecificLED::AllBack => LED_BACK_ALL,
-            SpecificLED::Number(n) => match n {
+            SpecificLED::Number(n) => match n.number() {
                 1 => LED_FRONT_BOTTOM,
                 2 => LED_FRONT_MIDDLE,
                 3 => LED_FRONT_TOP,
                 4 => LED_BACK_BOTTOM,
                 5 => LED_BACK_MIDDLE,
-                6 => LED_BACK_TOP,
-                _ => return Err(crate::error::Error::InvalidLED),
+                _ => LED_BACK_TOP,
             },
         };
         Ok(())
     }
 }
 
+impl InputReports for USBDevice {
+    fn read_report(&self, buffer: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        let timeout = timeout.map_or(-1, |timeout| {
+            i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+        });
+        Ok(self.hid_device.read_timeout(buffer, timeout)?)
+    }
+}
+
 impl USBDevice {
-    fn new(hid_device: HidDevice) -> crate::error::Result<USBDevice> {
+    ///
+    /// Return the model of this light.
+    ///
+    pub fn model(&self) -> DeviceModel {
+        self.model
+    }
+
+    ///
+    /// Wait at most `timeout`, or forever if `None`, for the next event from the button; return
+    /// `None` if there was no event before the timeout. Only the Mute button has a button to
+    /// read.
+    ///
+    pub fn next_event(&self, timeout: Option<Duration>) -> Result<Option<ButtonEvent>> {
+        crate::button::next_event(self, &self.button, DEFAULT_LONG_PRESS, timeout)
+            .map_err(|e| e.with_context(&self.id, None))
+    }
+
+    ///
+    /// Return a blocking iterator over the events from the button.
+    ///
+    pub fn events(&self) -> impl Iterator<Item = Result<ButtonEvent>> + '_ {
+        std::iter::from_fn(move || self.next_event(None).transpose())
+    }
+
+    fn new(hid_device: HidDevice) -> Result<USBDevice> {
+        let product = hid_device.get_product_string().ok().flatten();
+        let model = DeviceModel::from_product(product.as_deref().unwrap_or_default());
         let id = format!(
             "{}::{}::{}",
             hid_device
@@ -361,27 +547,22 @@ impl USBDevice {
                 .unwrap_or(Some("<error>".to_string()))
                 .unwrap_or("<unknown>".to_string()),
         );
+        debug!("Device '{}' is a {} light", id, model);
         Ok(Self {
             hid_device,
             id,
+            model,
             target_led: LED_ALL,
+            button: Default::default(),
         })
     }
 
-    fn color_to_bytes(&self, color: SolidColor) -> (u8, u8, u8) {
-        match color {
-            SolidColor::Red => (255, 0, 0),
-            SolidColor::Green => (0, 255, 0),
-            SolidColor::Yellow => (255, 255, 0),
-            SolidColor::Blue => (0, 0, 255),
-            SolidColor::White => (255, 255, 255),
-            SolidColor::Cyan => (0, 255, 255),
-            SolidColor::Magenta => (255, 0, 255),
-            SolidColor::Custom { red, green, blue } => (red, green, blue),
-        }
+    fn send(&self, command: Command, buffer: &[u8]) -> Result<()> {
+        self.write(buffer)
+            .map_err(|e| e.with_context(&self.id, Some(&command.to_string())))
     }
 
-    fn write(&self, buffer: &[u8]) -> crate::error::Result<()> {
+    fn write(&self, buffer: &[u8]) -> Result<()> {
         trace!(
             "writing [{:?}]",
             buffer
@@ -390,24 +571,20 @@ impl USBDevice {
                 .collect::<Vec<String>>()
                 .join(", ")
         );
-        let result = self.hid_device.write(buffer);
-        match result {
-            Ok(bytes_written) => {
-                if bytes_written == buffer.len() {
-                    Ok(())
-                } else {
-                    error!(
-                        "Bytes written, {}, did not match buffer length {}",
+        let bytes_written = self.hid_device.write(buffer)?;
+        if bytes_written == buffer.len() {
+            Ok(())
+        } else {
+            Err(Error::IoError {
+                source: std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    format!(
+                        "wrote {} bytes of {} to the HID device",
                         bytes_written,
                         buffer.len()
-                    );
-                    Err(crate::error::Error::InvalidRequest)
-                }
-            }
-            Err(err) => {
-                error!("Could not write to HID device: {:?}", err);
-                Err(crate::error::Error::InvalidRequest)
-            }
+                    ),
+                ),
+            })
         }
     }
 }
@@ -1,9 +1,15 @@
 /*!
 Implementation of the Device trait for webhook connected lights.
 
+By default requests are sent to the Luxafor cloud API, however [`new_device_with_endpoint`] allows
+the requests to be sent to any server that implements the same API, for example the local server
+provided by the `server` module.
+
 */
 
-use crate::{Device, Pattern, SolidColor, Wave};
+use crate::error::Error;
+use crate::registry::DeviceUri;
+use crate::{Capabilities, Command, CommandKind, Device, Pattern, Repeat, SolidColor, Speed, Wave};
 use reqwest::blocking::Client;
 
 // ------------------------------------------------------------------------------------------------
@@ -16,8 +22,14 @@ use reqwest::blocking::Client;
 #[derive(Clone, Debug)]
 pub struct WebhookDevice {
     id: String,
+    endpoint: String,
 }
 
+///
+/// The endpoint of the Luxafor cloud API, used unless another endpoint is provided.
+///
+pub const DEFAULT_ENDPOINT: &str = "https://api.luxafor.com/webhook/v1/actions";
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
@@ -25,21 +37,39 @@ pub struct WebhookDevice {
 ///
 /// Return a device implementation for a webhook connected light.
 ///
-pub fn new_device_for(device_id: &str) -> crate::error::Result<impl Device> {
+pub fn new_device_for(device_id: &str) -> crate::error::Result<WebhookDevice> {
+    new_device_with_endpoint(device_id, DEFAULT_ENDPOINT)
+}
+
+///
+/// Return a device implementation for a webhook connected light, where the actions are sent to
+/// `endpoint` rather than the Luxafor cloud API. The endpoint is the base URL to which the action
+/// names, `solid_color`, `blink`, and `pattern`, are appended.
+///
+pub fn new_device_with_endpoint(
+    device_id: &str,
+    endpoint: &str,
+) -> crate::error::Result<WebhookDevice> {
     if !device_id.is_empty() && device_id.chars().all(|c| c.is_ascii_hexdigit()) {
         Ok(WebhookDevice {
             id: device_id.to_string(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
         })
     } else {
-        Err(crate::error::Error::InvalidDeviceID)
+        Err(Error::InvalidDeviceID)
     }
 }
 
-// ------------------------------------------------------------------------------------------------
-// Private Types
-// ------------------------------------------------------------------------------------------------
-
-const API_V1: &str = "https://api.luxafor.com/webhook/v1/actions";
+///
+/// Return a device implementation for a `webhook:` URI, such as `webhook:2a0f2c73b72`; the option
+/// `endpoint` is used as in [`new_device_with_endpoint`].
+///
+pub fn open(uri: &DeviceUri) -> crate::error::Result<WebhookDevice> {
+    match uri.option("endpoint") {
+        None => new_device_for(uri.target()),
+        Some(endpoint) => new_device_with_endpoint(uri.target(), endpoint),
+    }
+}
 
 // ------------------------------------------------------------------------------------------------
 // Implementations
@@ -87,24 +117,26 @@ impl Device for WebhookDevice {
             .replace("COLOR", &color.to_string())
         };
 
-        let url = &format!("{}/{}", API_V1, "solid_color");
-
-        send_request(url, body)
+        self.send(Command::Solid { color }, "solid_color", body)
     }
 
     fn set_fade_to_color(
         &self,
-        _color: SolidColor,
-        _fade_duration: u8,
+        color: SolidColor,
+        fade_duration: Speed,
     ) -> crate::error::Result<()> {
-        Err(crate::error::Error::UnsupportedCommand)
+        let command = Command::Fade {
+            color,
+            fade_duration,
+        };
+        Err(Error::UnsupportedCommand.with_context(&self.id, Some(&command.to_string())))
     }
 
     fn set_color_strobe(
         &self,
         color: SolidColor,
-        _strobe_speed: u8,
-        repeat_count: u8,
+        strobe_speed: Speed,
+        repeat_count: Repeat,
     ) -> crate::error::Result<()> {
         info!(
             "Setting the strobe color of device '{}' to {}",
@@ -127,7 +159,7 @@ impl Device for WebhookDevice {
 }"#
             .replace("DID", &self.id.to_string())
             .replace("COLOR", &color.to_string())
-            .replace("RPT", &repeat_count.to_string())
+            .replace("RPT", &repeat_count.to_firmware().to_string())
         } else {
             r#"{
   "userId": "DID",
@@ -138,25 +170,34 @@ impl Device for WebhookDevice {
 }"#
             .replace("DID", &self.id.to_string())
             .replace("COLOR", &color.to_string())
-            .replace("RPT", &repeat_count.to_string())
+            .replace("RPT", &repeat_count.to_firmware().to_string())
         };
 
-        let url = &format!("{}/{}", API_V1, "blink");
-
-        send_request(url, body)
+        let command = Command::Strobe {
+            color,
+            strobe_speed,
+            repeat_count,
+        };
+        self.send(command, "blink", body)
     }
 
     fn set_color_wave(
         &self,
-        _color: SolidColor,
-        _wave_pattern: Wave,
-        _wave_speed: u8,
-        _repeat_count: u8,
+        color: SolidColor,
+        wave_pattern: Wave,
+        wave_speed: Speed,
+        repeat_count: Repeat,
     ) -> crate::error::Result<()> {
-        Err(crate::error::Error::UnsupportedCommand)
+        let command = Command::Wave {
+            color,
+            wave_pattern,
+            wave_speed,
+            repeat_count,
+        };
+        Err(Error::UnsupportedCommand.with_context(&self.id, Some(&command.to_string())))
     }
 
-    fn set_pattern(&self, pattern: Pattern, repeat_count: u8) -> crate::error::Result<()> {
+    fn set_pattern(&self, pattern: Pattern, repeat_count: Repeat) -> crate::error::Result<()> {
         info!("Setting the pattern of device '{}' to {}", self.id, pattern);
         warn!(
             "Ignoring repeat count {}, not supported in the webhook API",
@@ -172,9 +213,38 @@ impl Device for WebhookDevice {
         .replace("DID", &self.id.to_string())
         .replace("PATTERN", &pattern.to_string());
 
-        let url = &format!("{}/{}", API_V1, "pattern");
+        let command = Command::Pattern {
+            pattern,
+            repeat_count,
+        };
+        self.send(command, "pattern", body)
+    }
 
-        send_request(url, body)
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            commands: [
+                CommandKind::TurnOff,
+                CommandKind::Solid,
+                CommandKind::Strobe,
+                CommandKind::Pattern,
+            ]
+            .into_iter()
+            .collect(),
+            led_targeting: false,
+            patterns: Pattern::all(),
+            waves: Vec::new(),
+            repeat: [CommandKind::Strobe].into_iter().collect(),
+            speed: Default::default(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl WebhookDevice {
+    fn send(&self, command: Command, action: &str, body: String) -> crate::error::Result<()> {
+        let url = format!("{}/{}", self.endpoint, action);
+        send_request(&url, body).map_err(|e| e.with_context(&self.id, Some(&command.to_string())))
     }
 }
 
@@ -192,15 +262,81 @@ fn send_request(api: &str, body: String) -> crate::error::Result<()> {
         .header("Content-Type", "application/json")
         .body(body)
         .send()
-        .map_err(crate::error::Error::from)?;
+        .map_err(Error::from)?;
 
     if result.status().is_success() {
         info!("call successful");
         Ok(())
     } else {
-        let status_code = result.status().as_u16();
-        error!("call failed");
-        error!("{:?}", result.text());
-        Err(crate::error::Error::UnexpectedError(status_code))
+        let status = result.status().as_u16();
+        let body = result.text().unwrap_or_default();
+        error!("call failed with status {}", status);
+        Err(Error::Http { status, body })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{new_device_for, new_device_with_endpoint};
+    use crate::error::Error;
+    use crate::{Command, Device, SolidColor, Speed};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = new_device_for("2a0f2c73b72").unwrap().capabilities();
+        assert!(!capabilities.led_targeting);
+        for text in ["fade red 60", "wave blue short 30 5"] {
+            let command: Command = text.parse().unwrap();
+            assert!(capabilities.check(&command).is_err());
+        }
+        for text in ["off", "solid red", "strobe red 10 5", "pattern police 5"] {
+            let command: Command = text.parse().unwrap();
+            assert!(capabilities.check(&command).is_ok());
+        }
+        assert!(!capabilities
+            .repeat
+            .contains(&"pattern police 5".parse::<Command>().unwrap().kind()));
+    }
+
+    #[test]
+    fn test_error_context() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/actions", listener.local_addr().unwrap());
+        let _ = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            let body = "Too many requests";
+            let response = format!(
+                "HTTP/1.1 429 Too Many Requests\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let device = new_device_with_endpoint("2a0f2c73b72", &endpoint).unwrap();
+        let error = device.set_solid_color(SolidColor::Red).unwrap_err();
+        assert_eq!(error.device_id(), Some("2a0f2c73b72"));
+        assert_eq!(error.command(), Some("solid red"));
+        assert!(
+            matches!(error.root(), Error::Http { status: 429, body } if body == "Too many requests")
+        );
+        assert_eq!(
+            error.to_string(),
+            "Could not apply 'solid red' to device '2a0f2c73b72'; source: The server returned HTTP status 429: Too many requests"
+        );
+
+        let error = device
+            .set_fade_to_color(SolidColor::Red, Speed::new(60))
+            .unwrap_err();
+        assert_eq!(error.command(), Some("fade red 60"));
+        assert!(matches!(error.root(), Error::UnsupportedCommand));
     }
 }